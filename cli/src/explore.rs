@@ -0,0 +1,414 @@
+//! `cli explore <FILE>`: a terminal UI with the source on one side and
+//! the collapsible tree of rules on the other; the source of the
+//! selected rule is highlighted.
+//!
+//! | key                 | action                                   |
+//! |---------------------|------------------------------------------|
+//! | `↑`/`k`, `↓`/`j`    | select the previous/next rule            |
+//! | `→`/`l`, `←`/`h`    | expand the rule/collapse it (or go up)   |
+//! | `space`/`enter`     | toggle the rule                          |
+//! | `/`                 | search for a rule by (part of) its name  |
+//! | `n`, `N`            | select the next/previous match           |
+//! | `q`/`esc`           | quit                                     |
+
+use std::io::{stdout, Result};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use p_pest::export::{Tree, TreeNode};
+use parser::ast::Span;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    text::{Line, Span as TextSpan, Text},
+    widgets::{Block, List, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+/// A rule in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Item {
+    rule: String,
+    span: Span,
+    depth: usize,
+    parent: Option<usize>,
+    has_children: bool,
+}
+
+fn flatten(node: &TreeNode, depth: usize, parent: Option<usize>, items: &mut Vec<Item>) {
+    let idx = items.len();
+    items.push(Item {
+        rule: node.kind.clone(),
+        span: node.span,
+        depth,
+        parent,
+        has_children: !node.children.is_empty(),
+    });
+    for child in &node.children {
+        flatten(child, depth + 1, Some(idx), items);
+    }
+}
+
+/// The state of the explorer; kept apart from the terminal so that it
+/// can be driven (and tested) without one.
+#[derive(Debug)]
+pub struct Explorer {
+    /// every rule in the tree in pre-order
+    items: Vec<Item>,
+    expanded: Vec<bool>,
+    /// the index (into `items`) of the selected rule
+    selected: usize,
+    /// the search being typed (if any)
+    typing: Option<String>,
+    /// the last search
+    query: String,
+}
+
+impl Explorer {
+    /// an explorer of the tree with only its top-level rules expanded
+    pub fn new(tree: &Tree) -> Self {
+        let mut items = vec![];
+        for node in &tree.nodes {
+            flatten(node, 0, None, &mut items);
+        }
+        let expanded = items.iter().map(|i| i.depth == 0).collect();
+
+        Self {
+            items,
+            expanded,
+            selected: 0,
+            typing: None,
+            query: String::new(),
+        }
+    }
+
+    fn is_visible(&self, idx: usize) -> bool {
+        let mut parent = self.items[idx].parent;
+        while let Some(p) = parent {
+            if !self.expanded[p] {
+                return false;
+            }
+            parent = self.items[p].parent;
+        }
+        true
+    }
+
+    /// the indexes of the rules which aren't within a collapsed rule
+    fn visible(&self) -> Vec<usize> {
+        (0..self.items.len())
+            .filter(|i| self.is_visible(*i))
+            .collect()
+    }
+
+    /// the span of the selected rule
+    pub fn selected_span(&self) -> Option<Span> {
+        self.items.get(self.selected).map(|i| i.span)
+    }
+
+    pub fn selected_rule(&self) -> Option<&str> {
+        self.items.get(self.selected).map(|i| i.rule.as_str())
+    }
+
+    fn step(&mut self, forward: bool) {
+        let visible = self.visible();
+        let Some(pos) = visible.iter().position(|i| *i == self.selected) else {
+            return;
+        };
+        let next = match forward {
+            true => visible.get(pos + 1),
+            false => pos.checked_sub(1).and_then(|p| visible.get(p)),
+        };
+        if let Some(next) = next {
+            self.selected = *next;
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.step(true)
+    }
+
+    pub fn previous(&mut self) {
+        self.step(false)
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(expanded) = self.expanded.get_mut(self.selected) {
+            *expanded = true;
+        }
+    }
+
+    /// collapses the selected rule or -- when it's already collapsed
+    /// (or has no children) -- selects its parent
+    pub fn collapse(&mut self) {
+        let Some(item) = self.items.get(self.selected) else {
+            return;
+        };
+        if item.has_children && self.expanded[self.selected] {
+            self.expanded[self.selected] = false;
+        } else if let Some(parent) = item.parent {
+            self.selected = parent;
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        if let Some(expanded) = self.expanded.get_mut(self.selected) {
+            *expanded = !*expanded;
+        }
+    }
+
+    /// selects the next (or previous) rule whose name contains the
+    /// query, expanding the rules it's within so that it's visible
+    pub fn find(&mut self, query: &str, forward: bool) -> bool {
+        self.query = query.to_string();
+        let len = self.items.len();
+        let found = (1..=len)
+            .map(|offset| match forward {
+                true => (self.selected + offset) % len,
+                false => (self.selected + len - offset) % len,
+            })
+            .find(|idx| self.items[*idx].rule.contains(query));
+
+        match found {
+            Some(idx) => {
+                self.selected = idx;
+                let mut parent = self.items[idx].parent;
+                while let Some(p) = parent {
+                    self.expanded[p] = true;
+                    parent = self.items[p].parent;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// handles a key press; returns `false` once the explorer should quit
+    pub fn on_key(&mut self, key: KeyCode) -> bool {
+        if let Some(query) = &mut self.typing {
+            match key {
+                KeyCode::Char(c) => query.push(c),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Enter => {
+                    let query = self.typing.take().unwrap_or_default();
+                    self.find(&query, true);
+                }
+                KeyCode::Esc => self.typing = None,
+                _ => (),
+            }
+            return true;
+        }
+
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.next(),
+            KeyCode::Up | KeyCode::Char('k') => self.previous(),
+            KeyCode::Right | KeyCode::Char('l') => self.expand(),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle(),
+            KeyCode::Char('/') => self.typing = Some(String::new()),
+            KeyCode::Char('n') => {
+                let query = self.query.clone();
+                self.find(&query, true);
+            }
+            KeyCode::Char('N') => {
+                let query = self.query.clone();
+                self.find(&query, false);
+            }
+            _ => (),
+        }
+        true
+    }
+}
+
+/// the source with the span highlighted; tabs are expanded so that
+/// they're drawn consistently
+fn source(content: &str, span: Span) -> Text<'_> {
+    let highlight = Style::new().black().on_yellow();
+    let mut lines = vec![];
+    let mut start = 0;
+
+    for line in content.split_inclusive('\n') {
+        let end = start + line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let (from, to) = (
+            span.start.clamp(start, start + text.len()) - start,
+            span.end.clamp(start, start + text.len()) - start,
+        );
+        lines.push(Line::from(vec![
+            TextSpan::raw(text[..from].replace('\t', "    ")),
+            TextSpan::styled(text[from..to].replace('\t', "    "), highlight),
+            TextSpan::raw(text[to..].replace('\t', "    ")),
+        ]));
+        start = end;
+    }
+
+    Text::from(lines)
+}
+
+fn draw(frame: &mut Frame, explorer: &Explorer, content: &str) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.size());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main);
+
+    let span = explorer.selected_span().unwrap_or_default();
+    // keep the start of the highlight in view
+    let line = content[..span.start.min(content.len())]
+        .matches('\n')
+        .count();
+    let scroll = line.saturating_sub(left.height as usize / 3) as u16;
+    frame.render_widget(
+        Paragraph::new(source(content, span))
+            .block(Block::bordered().title(" source "))
+            .scroll((scroll, 0)),
+        left,
+    );
+
+    let visible = explorer.visible();
+    let rows: Vec<String> = visible
+        .iter()
+        .map(|idx| {
+            let item = &explorer.items[*idx];
+            let marker = match (item.has_children, explorer.expanded[*idx]) {
+                (false, _) => " ",
+                (true, true) => "▾",
+                (true, false) => "▸",
+            };
+            format!("{}{} {}", "  ".repeat(item.depth), marker, item.rule)
+        })
+        .collect();
+    let mut state =
+        ListState::default().with_selected(visible.iter().position(|i| *i == explorer.selected));
+    frame.render_stateful_widget(
+        List::new(rows)
+            .block(Block::bordered().title(" rules "))
+            .highlight_style(Style::new().reversed()),
+        right,
+        &mut state,
+    );
+
+    let status_line = match &explorer.typing {
+        Some(query) => format!("/{}", query),
+        None => format!(
+            "{} {}..{}  |  ↑↓ select  ←→ collapse/expand  / search  n/N next/previous  q quit",
+            explorer.selected_rule().unwrap_or_default(),
+            span.start,
+            span.end
+        ),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+}
+
+/// Puts the terminal into raw mode on the alternate screen until it's
+/// dropped; as that happens while unwinding too, the terminal is
+/// restored even when the explorer fails or panics.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen)?;
+
+        Ok(Self)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        // there's nothing more which can be done if restoring fails
+        let _ = disable_raw_mode();
+        let _ = execute!(stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Opens the explorer for the content until the user quits.
+pub fn run(content: &str, tree: &Tree) -> Result<()> {
+    let mut explorer = Explorer::new(tree);
+
+    let _raw = RawTerminal::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+
+    loop {
+        terminal.draw(|frame| draw(frame, &explorer, content))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !explorer.on_key(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p_pest::{parse_rule, Rule};
+
+    const CONTENT: &str = "# Foo\n\n- a\n- b\n";
+
+    fn explorer() -> Explorer {
+        let pairs = parse_rule(Rule::file, CONTENT).unwrap();
+        Explorer::new(&Tree::from_pairs(pairs))
+    }
+
+    #[test]
+    fn rules_expand_and_collapse() {
+        let mut explorer = explorer();
+        assert_eq!(explorer.visible().len(), 4);
+
+        explorer.next();
+        assert_eq!(explorer.selected_rule(), Some("block"));
+        explorer.expand();
+        explorer.next();
+        assert_eq!(explorer.selected_rule(), Some("leaf_block"));
+
+        explorer.collapse();
+        assert_eq!(explorer.selected_rule(), Some("block"));
+        explorer.collapse();
+        explorer.next();
+        assert_eq!(explorer.selected_rule(), Some("block"));
+        assert_eq!(explorer.selected_span(), Some(Span::new(7, 15)));
+    }
+
+    #[test]
+    fn rules_are_found_by_name() {
+        let mut explorer = explorer();
+
+        for key in "/item\n".chars() {
+            let key = match key {
+                '\n' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            assert!(explorer.on_key(key));
+        }
+        assert_eq!(explorer.selected_rule(), Some("list_item"));
+        assert_eq!(explorer.selected_span(), Some(Span::new(7, 11)));
+        assert!(explorer.is_visible(explorer.selected));
+
+        explorer.on_key(KeyCode::Char('n'));
+        assert_eq!(explorer.selected_span(), Some(Span::new(11, 15)));
+        explorer.on_key(KeyCode::Char('N'));
+        assert_eq!(explorer.selected_span(), Some(Span::new(7, 11)));
+        assert!(!explorer.on_key(KeyCode::Char('q')));
+    }
+
+    #[test]
+    fn the_selected_span_is_highlighted() {
+        let text = source("ab\ncd\n", Span::new(1, 4));
+        let line = |idx: usize| -> Vec<String> {
+            text.lines[idx]
+                .spans
+                .iter()
+                .map(|s| s.content.to_string())
+                .collect()
+        };
+
+        assert_eq!(line(0), vec!["a", "b", ""]);
+        assert_eq!(line(1), vec!["", "c", "d"]);
+    }
+}
@@ -1,6 +1,99 @@
 use std::{fmt::Display, fs::read_to_string};
 
-use clap::{arg, command, value_parser, Arg};
+use clap::{arg, command, value_parser, Arg, Command};
+use p_pest::parkdown::Parkdown;
+use p_pest::{export::Tree, Rule};
+use parser::{
+    diagnostic::Diagnostic,
+    graph::{render, GraphFormat, GraphNode, GraphOptions},
+    pipeline::{PipelineConfig, PluginConfig, PluginRegistry},
+};
+
+mod explore;
+mod rule;
+mod snapshots;
+
+/// what the CLI prints once the file is parsed
+enum Target {
+    Html,
+    Tokens,
+    Json,
+    Mdast,
+    Graph(GraphFormat),
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Html => write!(f, "HTML"),
+            Target::Tokens => write!(f, "tokens"),
+            Target::Json => write!(f, "JSON"),
+            Target::Mdast => write!(f, "mdast"),
+            Target::Graph(format) => write!(f, "{:?}", format),
+        }
+    }
+}
+
+/// the plugin enabled with `--plugin`; its options may follow its name
+/// as JSON (e.g., `rewrite-links={"base":"https://docs.com"}`)
+fn plugin_config(plugin: &str) -> Result<PluginConfig, String> {
+    match plugin.split_once('=') {
+        Some((name, options)) => Ok(PluginConfig {
+            name: name.to_string(),
+            options: serde_json::from_str(options).map_err(|e| {
+                format!(
+                    "the options of the plugin '{}' aren't valid JSON: {}",
+                    name, e
+                )
+            })?,
+        }),
+        None => Ok(PluginConfig {
+            name: plugin.to_string(),
+            options: serde_json::Value::Null,
+        }),
+    }
+}
+
+/// the plugins enabled in the config file (if any) followed by those
+/// enabled with `--plugin`
+fn pipeline_config(
+    config: Option<&String>,
+    plugins: Vec<String>,
+) -> Result<PipelineConfig, String> {
+    let mut pipeline: PipelineConfig = match config {
+        Some(file) => {
+            let content = read_to_string(file)
+                .map_err(|e| format!("problems loading the config from \"{}\": {}", file, e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("the config in \"{}\" isn't valid: {}", file, e))?
+        }
+        None => PipelineConfig::default(),
+    };
+    for plugin in plugins {
+        pipeline.plugins.push(plugin_config(&plugin)?);
+    }
+
+    Ok(pipeline)
+}
+
+/// the diagnostic as `<severity>: <message> at <line>:<column>`; the
+/// line and column (of where its span starts) both start at 1
+fn describe(diagnostic: &Diagnostic, content: &str) -> String {
+    let before = &content[..diagnostic.span.start];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let severity = match &diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.severity, code),
+        None => diagnostic.severity.to_string(),
+    };
+
+    format!(
+        "{}: {} at {}:{}",
+        severity,
+        diagnostic.message,
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1
+    )
+}
 
 fn main() {
     let matches = command!()
@@ -15,27 +108,270 @@ fn main() {
                 .value_name("t")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            arg!(--json "prints the parse tree as JSON; regions which fail to parse are `failed` nodes")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("t"),
+        )
+        .arg(
+            arg!(--mdast "prints the tree as mdast (JSON) for use with remark")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["t", "json"]),
+        )
+        .arg(
+            arg!(--graph <FORMAT> "prints the grammar's parse tree as a graph")
+                .value_parser(["dot", "mindmap", "flowchart"])
+                .conflicts_with_all(["t", "json", "mdast"]),
+        )
+        .arg(
+            arg!(--collapse "collapses silent and leaf nodes of the graph")
+                .action(clap::ArgAction::SetTrue)
+                .requires("graph"),
+        )
+        .arg(
+            arg!(--"max-text" <CHARS> "truncates the text shown in the graph")
+                .value_parser(value_parser!(usize))
+                .requires("graph"),
+        )
+        .arg(
+            arg!(-c --config <CONFIG> "a JSON file listing the plugins to run and their options")
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            arg!(-p --plugin <PLUGIN> "a plugin to run, with any options as `name=<JSON>`; may be repeated")
+                .value_parser(value_parser!(String))
+                .action(clap::ArgAction::Append),
+        )
+        .subcommand(
+            Command::new("explore")
+                .about("explores the rules parsed from the file in a terminal UI")
+                .arg(arg!(<FILE>).value_parser(value_parser!(String))),
+        )
+        .subcommand(
+            Command::new("rule")
+                .about("parses the file (or text) against a single rule of the grammar")
+                .arg(arg!(<RULE> "the name of the rule (e.g., heading)"))
+                .arg(arg!([FILE]).value_parser(value_parser!(String)))
+                .arg(
+                    arg!(-t --text <TEXT> "the text to parse rather than a file; `\\n` is a newline")
+                        .conflicts_with("FILE"),
+                )
+                .arg(
+                    arg!(--list "lists the rules of the grammar")
+                        .action(clap::ArgAction::SetTrue)
+                        .exclusive(true),
+                ),
+        )
+        .subcommand(
+            Command::new("snapshots")
+                .about("reviews the grammar's snapshots which don't match")
+                .arg(
+                    arg!([DIR] "the fixtures directory")
+                        .value_parser(value_parser!(String))
+                        .default_value(concat!(env!("CARGO_MANIFEST_DIR"), "/../p_pest/tests/fixtures")),
+                )
+                .arg(
+                    arg!(--accept "accepts every snapshot without asking")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    arg!(--reject "rejects every snapshot without asking")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("accept"),
+                ),
+        )
+        .args_conflicts_with_subcommands(true)
         .get_matches();
 
+    if let Some(("snapshots", args)) = matches.subcommand() {
+        let dir = args
+            .get_one::<String>("DIR")
+            .expect("the fixtures directory");
+        let review = match (args.get_flag("accept"), args.get_flag("reject")) {
+            (true, _) => snapshots::Review::Accept,
+            (_, true) => snapshots::Review::Reject,
+            _ => snapshots::Review::Ask,
+        };
+        return snapshots::run(std::path::Path::new(dir), review)
+            .unwrap_or_else(|e| panic!("{:?}", e));
+    }
+
+    if let Some(("rule", args)) = matches.subcommand() {
+        if args.get_flag("list") {
+            for rule in p_pest::rules::RULES {
+                println!("{}", p_pest::rules::name(*rule));
+            }
+            return;
+        }
+        let name = args
+            .get_one::<String>("RULE")
+            .expect("the rule to parse with");
+        let rule: Rule = name.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        let content = match (
+            args.get_one::<String>("text"),
+            args.get_one::<String>("FILE"),
+        ) {
+            (Some(text), _) => text.replace("\\n", "\n"),
+            (None, Some(file)) => read_to_string(file).unwrap_or_else(|e| {
+                println!("Problems loading the file contents from: \"{}\"!\n", &file);
+                panic!("{:?}", e);
+            }),
+            (None, None) => {
+                eprintln!("either a FILE or --text is required");
+                std::process::exit(2);
+            }
+        };
+
+        match rule::report(rule, &content) {
+            Ok(report) => print!("{}", report),
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(("explore", explore)) = matches.subcommand() {
+        let file = explore
+            .get_one::<String>("FILE")
+            .expect("the markdown file you want to explore");
+        let content = read_to_string(file).unwrap_or_else(|e| {
+            println!("Problems loading the file contents from: \"{}\"!\n", &file);
+            panic!("{:?}", e);
+        });
+        // regions which fail to parse are shown rather than stopping
+        // the rest of the file being explored
+        return explore::run(&content, &Tree::from_content(&content))
+            .unwrap_or_else(|e| panic!("the explorer failed: {:?}", e));
+    }
+
     let file = matches
         .get_one::<String>("FILE")
         .expect("the markdown file you want to parse");
 
     let target = if matches.get_flag("t") {
-        Target::HTML
+        Target::Html
+    } else if matches.get_flag("json") {
+        Target::Json
+    } else if matches.get_flag("mdast") {
+        Target::Mdast
+    } else if let Some(format) = matches.get_one::<String>("graph") {
+        Target::Graph(match format.as_str() {
+            "mindmap" => GraphFormat::MermaidMindmap,
+            "flowchart" => GraphFormat::MermaidFlowchart,
+            _ => GraphFormat::Dot,
+        })
     } else {
         Target::Tokens
     };
 
+    let mut pipeline = pipeline_config(
+        matches.get_one::<String>("config"),
+        matches
+            .get_many::<String>("plugin")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+    )
+    .and_then(|config| {
+        PluginRegistry::builtin()
+            .pipeline(&config)
+            .map_err(|e| e.to_string())
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
     let content = read_to_string(file).unwrap_or_else(|e| {
         println!("Problems loading the file contents from: \"{}\"!\n", &file);
         panic!("{:?}", e);
     });
 
-    println!(
-        "Parsing {} [{} chars, to {}]:\n",
-        &file,
-        &content.len(),
-        target
-    );
+    // JSON, mdast and graphs are printed on their own so it can be piped to other tools
+    if !matches!(target, Target::Json | Target::Mdast | Target::Graph(_)) {
+        println!(
+            "Parsing {} [{} chars, to {}]:\n",
+            &file,
+            &content.len(),
+            target
+        );
+    }
+
+    let md = Parkdown::new(&content);
+    let parsed = md.parse();
+    for diagnostic in parsed.diagnostics() {
+        eprintln!("{}", describe(diagnostic, &content));
+    }
+    let parsed = parsed.transform(&mut pipeline).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    match target {
+        Target::Html => println!("{}", parsed.to_html()),
+        Target::Tokens => println!("{:#?}", parsed.tree()),
+        Target::Json => println!("{}", parsed.export().to_json_pretty().unwrap()),
+        Target::Mdast => println!("{}", parsed.to_mdast().to_json().unwrap()),
+        Target::Graph(format) => {
+            let options = GraphOptions {
+                format,
+                collapse_silent: matches.get_flag("collapse"),
+                collapse_leaves: matches.get_flag("collapse"),
+                max_text: matches.get_one::<usize>("max-text").copied(),
+            };
+            // regions which fail to parse are drawn as `failed` nodes
+            let tree = Tree::from_content(&content);
+            print!("{}", render(&GraphNode::from(&tree.nodes[0]), &options));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::ast::Span;
+    use serde_json::json;
+
+    #[test]
+    fn plugins_take_their_options_as_json() {
+        let config = pipeline_config(
+            None,
+            vec![
+                "strip-comments".to_string(),
+                r#"rewrite-links={"base":"https://docs.com"}"#.to_string(),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.plugins[0].options, serde_json::Value::Null);
+        assert_eq!(config.plugins[1].name, "rewrite-links");
+        assert_eq!(
+            config.plugins[1].options,
+            json!({ "base": "https://docs.com" })
+        );
+        assert!(PluginRegistry::builtin().pipeline(&config).is_ok());
+
+        assert!(pipeline_config(None, vec!["rewrite-links={".to_string()]).is_err());
+        assert!(pipeline_config(Some(&"missing.json".to_string()), vec![]).is_err());
+    }
+
+    #[test]
+    fn diagnostics_describe_where_they_are() {
+        let content = "# Foo\n\nbär ```stray\n";
+        let diagnostic = Diagnostic::warning("unable to parse", Span::new(11, 19));
+
+        assert_eq!(
+            describe(&diagnostic, content),
+            "warning: unable to parse at 3:4"
+        );
+        assert_eq!(
+            describe(&diagnostic.with_code("MD001"), content),
+            "warning[MD001]: unable to parse at 3:4"
+        );
+    }
 }
@@ -0,0 +1,60 @@
+//! `cli rule <RULE> [FILE | --text TEXT]`: parses the input against a
+//! single rule of the grammar and reports the tree of rules, the span
+//! which was consumed and whatever remains; a local playground for the
+//! grammar.
+
+use p_pest::{export::Tree, rules::try_rule, Rule};
+
+/// The report of parsing the content against the rule; an `Err` when
+/// the rule doesn't match.
+pub fn report(rule: Rule, content: &str) -> Result<String, String> {
+    let found = try_rule(rule, content).map_err(|e| e.to_string())?;
+    let mut report = String::new();
+
+    for node in Tree::from_pairs(found.pairs).nodes {
+        report.push_str(&node.outline());
+    }
+    if report.is_empty() {
+        report.push_str("(a silent rule produces no tree)\n");
+    }
+    match found.consumed {
+        Some(consumed) => report.push_str(&format!(
+            "\nconsumed: {}..{} ({} bytes)\n",
+            consumed.start,
+            consumed.end,
+            consumed.len()
+        )),
+        None => report.push_str("\nconsumed: unknown (a silent rule doesn't record it)\n"),
+    }
+    match found.remainder {
+        Some("") => report.push_str("remainder: (none)\n"),
+        Some(remainder) => report.push_str(&format!("remainder: {:?}\n", remainder)),
+        None => report.push_str("remainder: unknown\n"),
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_report_the_tree_and_remainder() {
+        assert_eq!(
+            report(Rule::heading, "## Foo\nbar").unwrap(),
+            concat!(
+                "heading 0..7\n",
+                "  h2 0..7\n",
+                "    title 3..6 \"Foo\"\n",
+                "\n",
+                "consumed: 0..7 (7 bytes)\n",
+                "remainder: \"bar\"\n",
+            )
+        );
+        assert!(report(Rule::heading, "bar").is_err());
+        assert!(report(Rule::opt3_space, "  x")
+            .unwrap()
+            .contains("consumed: unknown"));
+    }
+}
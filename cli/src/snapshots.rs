@@ -0,0 +1,111 @@
+//! `cli snapshots [DIR]`: reviews the snapshots of the grammar's
+//! fixtures which didn't match (see `p_pest::snapshot`) by showing each
+//! one's diff and asking whether to accept or reject it.
+
+use std::{
+    fs::read_to_string,
+    io::{stdin, stdout, Write},
+    path::Path,
+};
+
+use p_pest::snapshot::{pending, Pending};
+use similar::{ChangeTag, TextDiff};
+
+/// What to do with each pending snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Review {
+    /// ask about each snapshot
+    Ask,
+    Accept,
+    Reject,
+}
+
+/// the diff from the committed snapshot (if any) to the new one
+fn diff(snapshot: &Pending) -> String {
+    let old = read_to_string(&snapshot.snapshot).unwrap_or_default();
+    let new = read_to_string(&snapshot.pending).unwrap_or_default();
+
+    TextDiff::from_lines(&old, &new)
+        .iter_all_changes()
+        .map(|change| {
+            let sign = match change.tag() {
+                ChangeTag::Delete => "-",
+                ChangeTag::Insert => "+",
+                ChangeTag::Equal => " ",
+            };
+            format!("{} {}", sign, change)
+        })
+        .collect()
+}
+
+fn ask() -> char {
+    loop {
+        print!("[a]ccept, [r]eject or [s]kip? ");
+        stdout().flush().ok();
+        let mut answer = String::new();
+        if stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+            return 's';
+        }
+        match answer.trim().chars().next() {
+            Some(c @ ('a' | 'r' | 's')) => return c,
+            _ => continue,
+        }
+    }
+}
+
+/// Reviews every pending snapshot in the directory.
+pub fn run(dir: &Path, review: Review) -> color_eyre::Result<()> {
+    let snapshots = pending(dir)?;
+    if snapshots.is_empty() {
+        println!("no snapshots are waiting for review");
+        return Ok(());
+    }
+
+    for snapshot in snapshots {
+        let answer = match review {
+            Review::Accept => 'a',
+            Review::Reject => 'r',
+            Review::Ask => {
+                println!("\n{}", snapshot.snapshot.display());
+                if !snapshot.snapshot.exists() {
+                    println!("(a new snapshot)");
+                }
+                print!("{}", diff(&snapshot));
+                ask()
+            }
+        };
+        match answer {
+            'a' => snapshot.accept()?,
+            'r' => snapshot.reject()?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn diffs_show_changed_lines() {
+        let dir = std::env::temp_dir().join(format!("cli_snapshots_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.md.snap"), "same\nold\n").unwrap();
+        fs::write(dir.join("a.md.snap.new"), "same\nnew\n").unwrap();
+
+        let snapshots = pending(&dir).unwrap();
+        assert_eq!(diff(&snapshots[0]), "  same\n- old\n+ new\n");
+
+        run(&dir, Review::Accept).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md.snap")).unwrap(),
+            "same\nnew\n"
+        );
+        assert!(pending(&dir).unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
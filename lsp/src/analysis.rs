@@ -0,0 +1,468 @@
+//! The language features provided by the server; these work on byte
+//! offsets into the document and leave the conversion to protocol
+//! positions to the caller.
+
+use lsp_types::{CompletionItem, CompletionItemKind};
+use p_pest::{document::Document, Markdown, Rule};
+use parser::{
+    ast::{Node, NodeKind, Span},
+    diagnostic::Diagnostic,
+    emoji,
+    lint::Linter,
+};
+use pest::Parser;
+
+/// the HTML tags offered as completions after a `<`
+const HTML_TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "address",
+    "article",
+    "aside",
+    "audio",
+    "b",
+    "blockquote",
+    "br",
+    "button",
+    "caption",
+    "code",
+    "dd",
+    "del",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "i",
+    "iframe",
+    "img",
+    "input",
+    "ins",
+    "kbd",
+    "label",
+    "li",
+    "main",
+    "mark",
+    "nav",
+    "ol",
+    "p",
+    "picture",
+    "pre",
+    "q",
+    "s",
+    "section",
+    "small",
+    "source",
+    "span",
+    "strong",
+    "sub",
+    "summary",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+    "video",
+];
+
+/// where a link points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkTarget {
+    /// an inline link to a URI (e.g., `[foo](https://foo.com)`)
+    Uri(String),
+    /// an inline link to a heading in the document (e.g., `[foo](#foo)`)
+    Anchor(String),
+    /// a reference link (e.g., `[foo][bar]` or `[bar]`)
+    Reference(String),
+}
+
+/// a link found in the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub span: Span,
+    pub text: String,
+    pub target: LinkTarget,
+}
+
+/// a link reference definition (e.g., `[bar]: https://bar.com "Bar"`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkDefinition {
+    pub span: Span,
+    /// the normalized label
+    pub label: String,
+    pub dest: String,
+    pub title: Option<String>,
+}
+
+/// a heading in the document along with its anchor
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub span: Span,
+    pub level: u8,
+    pub title: String,
+    pub slug: String,
+}
+
+/// Labels are matched case-insensitively with any run of whitespace
+/// treated as a single space.
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn walk<'a>(nodes: impl Iterator<Item = &'a Node>, f: &mut dyn FnMut(&'a Node)) {
+    for node in nodes {
+        f(node);
+        walk(node.children.iter(), f);
+    }
+}
+
+pub fn headings(doc: &Document) -> Vec<Heading> {
+    let nodes: Vec<Node> = doc.nodes().collect();
+    let mut headings = vec![];
+    walk(nodes.iter(), &mut |node| {
+        if let NodeKind::Heading { level, id } = &node.kind {
+            headings.push(Heading {
+                span: node.span,
+                level: *level,
+                title: node.text_content(),
+                slug: id.clone().unwrap_or_default(),
+            });
+        }
+    });
+
+    headings
+}
+
+/// the spans of all code blocks; links aren't recognized inside them
+fn code_spans(doc: &Document) -> Vec<Span> {
+    let nodes: Vec<Node> = doc.nodes().collect();
+    let mut spans = vec![];
+    walk(nodes.iter(), &mut |node| {
+        if let NodeKind::CodeBlock { .. } = node.kind {
+            spans.push(node.span);
+        }
+    });
+
+    spans
+}
+
+fn within(spans: &[Span], offset: usize) -> bool {
+    spans.iter().any(|s| s.start <= offset && offset < s.end)
+}
+
+pub fn definitions(doc: &Document) -> Vec<LinkDefinition> {
+    let content = doc.content();
+    let code = code_spans(doc);
+    let mut definitions = vec![];
+    let mut start = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_start = start;
+        start += line.len();
+        if within(&code, line_start) {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let rest = &line[indent..];
+        if indent > 3 || !rest.starts_with('[') {
+            continue;
+        }
+        let close = match rest.find("]:") {
+            Some(close) if close > 1 => close,
+            _ => continue,
+        };
+        let label = &rest[1..close];
+        if label.contains(['[', ']']) {
+            continue;
+        }
+
+        let mut parts = rest[close + 2..].trim().splitn(2, char::is_whitespace);
+        let dest = match parts.next() {
+            Some(dest) if !dest.is_empty() => dest.trim_start_matches('<').trim_end_matches('>'),
+            _ => continue,
+        };
+        let title = parts
+            .next()
+            .map(|t| t.trim().trim_matches(['"', '\'', '(', ')']).to_string())
+            .filter(|t| !t.is_empty());
+
+        definitions.push(LinkDefinition {
+            span: Span::new(line_start, line_start + line.trim_end().len()),
+            label: normalize_label(label),
+            dest: dest.to_string(),
+            title,
+        });
+    }
+
+    definitions
+}
+
+/// the bracketed text starting at `pos` (which must be a `[`) on
+/// a single line; returns the text and the position after the `]`
+fn bracketed(content: &str, pos: usize) -> Option<(&str, usize)> {
+    let rest = &content[pos + 1..];
+    let close = rest.find([']', '\n', '['])?;
+    if &rest[close..close + 1] != "]" {
+        return None;
+    }
+
+    Some((&rest[..close], pos + close + 2))
+}
+
+pub fn links(doc: &Document) -> Vec<Link> {
+    let content = doc.content();
+    let code = code_spans(doc);
+    let definitions: Vec<Span> = definitions(doc).iter().map(|d| d.span).collect();
+    let mut links = vec![];
+    let mut pos = 0;
+
+    while let Some(idx) = content[pos..].find('[') {
+        let start = pos + idx;
+        pos = start + 1;
+        if within(&code, start) || within(&definitions, start) {
+            continue;
+        }
+
+        // links can't span lines so only the rest of the line is parsed
+        let line_end = content[start..]
+            .find('\n')
+            .map(|i| start + i)
+            .unwrap_or(content.len());
+        if let Ok(pairs) = Markdown::parse(Rule::link, &content[start..line_end]) {
+            let link = pairs.as_str();
+            let mut text = "";
+            let mut dest = "";
+            for part in pairs.flat_map(|p| p.into_inner()) {
+                match part.as_rule() {
+                    Rule::link_desc => text = part.as_str(),
+                    Rule::link_uri => dest = part.as_str(),
+                    _ => (),
+                }
+            }
+            let dest = dest.trim_start_matches('(').trim_end_matches(')').trim();
+            let target = match dest.strip_prefix('#') {
+                Some(anchor) => LinkTarget::Anchor(anchor.to_string()),
+                None => LinkTarget::Uri(dest.to_string()),
+            };
+
+            links.push(Link {
+                span: Span::new(start, start + link.len()),
+                text: text
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string(),
+                target,
+            });
+            pos = start + link.len();
+            continue;
+        }
+
+        let (text, after) = match bracketed(content, start) {
+            Some(found) => found,
+            None => continue,
+        };
+        // `[text][label]` or the collapsed `[text][]` form
+        let (label, end) = match content[after..].starts_with('[') {
+            true => match bracketed(content, after) {
+                Some(("", end)) => (text, end),
+                Some((label, end)) => (label, end),
+                None => (text, after),
+            },
+            false => (text, after),
+        };
+        if text.is_empty() || content[end..].starts_with(['(', ':']) {
+            continue;
+        }
+
+        links.push(Link {
+            span: Span::new(start, end),
+            text: text.to_string(),
+            target: LinkTarget::Reference(normalize_label(label)),
+        });
+        pos = end;
+    }
+
+    links
+}
+
+fn link_at(doc: &Document, offset: usize) -> Option<Link> {
+    links(doc)
+        .into_iter()
+        .find(|l| l.span.start <= offset && offset < l.span.end)
+}
+
+/// The span which the item at `offset` refers to; this is the heading
+/// for an anchor link and the definition for a reference link.
+pub fn definition(doc: &Document, offset: usize) -> Option<Span> {
+    match link_at(doc, offset)?.target {
+        LinkTarget::Anchor(anchor) => headings(doc)
+            .into_iter()
+            .find(|h| h.slug == anchor)
+            .map(|h| h.span),
+        LinkTarget::Reference(label) => definitions(doc)
+            .into_iter()
+            .find(|d| d.label == label)
+            .map(|d| d.span),
+        LinkTarget::Uri(_) => None,
+    }
+}
+
+/// A preview -- in Markdown -- of the link at `offset` along with
+/// the span of the link.
+pub fn hover(doc: &Document, offset: usize) -> Option<(Span, String)> {
+    let link = link_at(doc, offset)?;
+    let preview = match &link.target {
+        LinkTarget::Uri(uri) => format!("[{}]({})", uri, uri),
+        LinkTarget::Anchor(anchor) => match headings(doc).into_iter().find(|h| &h.slug == anchor) {
+            Some(h) => format!("{} {}", "#".repeat(h.level as usize), h.title),
+            None => format!("no heading found for `#{}`", anchor),
+        },
+        LinkTarget::Reference(label) => {
+            match definitions(doc).into_iter().find(|d| &d.label == label) {
+                Some(d) => match d.title {
+                    Some(title) => format!("**{}**\n\n[{}]({})", title, d.dest, d.dest),
+                    None => format!("[{}]({})", d.dest, d.dest),
+                },
+                None => return None,
+            }
+        }
+    };
+
+    Some((link.span, preview))
+}
+
+/// Completions for the text preceding `offset`; emoji shortcodes are
+/// offered after `::` and tag names after `<`.
+pub fn completion(doc: &Document, offset: usize) -> Vec<CompletionItem> {
+    let content = doc.content();
+    let line_start = content[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let before = &content[line_start..offset];
+    let word_start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let word = &before[word_start..];
+    let prefix = &before[..word_start];
+
+    if prefix.ends_with("::") {
+        emoji::matching(word)
+            .map(|(name, emoji)| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::VALUE),
+                detail: Some(emoji.to_string()),
+                ..CompletionItem::default()
+            })
+            .collect()
+    } else if prefix.ends_with('<') || prefix.ends_with("</") {
+        HTML_TAGS
+            .iter()
+            .filter(|t| t.starts_with(word))
+            .map(|tag| CompletionItem {
+                label: tag.to_string(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                ..CompletionItem::default()
+            })
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// the problems found while parsing along with those from the linter
+pub fn diagnostics(doc: &Document, linter: &Linter) -> Vec<Diagnostic> {
+    let mut diagnostics = doc.diagnostics();
+    diagnostics.extend(linter.lint(doc.content(), &doc.tree()));
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MD: &str = "# Test Markdown\n\n- [Topic 1](#topic-1)\n- [bar][Bar  Ref] and [qux]\n\n## Topic 1\n\n[bar ref]: https://bar.com \"The Bar\"\n";
+
+    fn offset_of(needle: &str) -> usize {
+        MD.find(needle).unwrap()
+    }
+
+    #[test]
+    fn headings_have_slugs() {
+        let doc = Document::new(MD);
+        let slugs: Vec<String> = headings(&doc).into_iter().map(|h| h.slug).collect();
+
+        assert_eq!(slugs, vec!["test-markdown", "topic-1"]);
+    }
+
+    #[test]
+    fn anchor_links_go_to_their_heading() {
+        let doc = Document::new(MD);
+        let target = definition(&doc, offset_of("#topic-1")).unwrap();
+
+        assert_eq!(&MD[target.start..target.end], "## Topic 1\n");
+    }
+
+    #[test]
+    fn reference_links_go_to_their_definition() {
+        let doc = Document::new(MD);
+        let target = definition(&doc, offset_of("[bar]") + 1).unwrap();
+
+        assert_eq!(
+            &MD[target.start..target.end],
+            "[bar ref]: https://bar.com \"The Bar\""
+        );
+        // an undefined reference has nowhere to go
+        assert_eq!(definition(&doc, offset_of("[qux]") + 1), None);
+    }
+
+    #[test]
+    fn hover_previews_links() {
+        let doc = Document::new(MD);
+        let (span, preview) = hover(&doc, offset_of("[bar]") + 1).unwrap();
+
+        assert_eq!(&MD[span.start..span.end], "[bar][Bar  Ref]");
+        assert_eq!(preview, "**The Bar**\n\n[https://bar.com](https://bar.com)");
+        assert_eq!(hover(&doc, offset_of("[Topic 1]")).unwrap().1, "## Topic 1");
+    }
+
+    #[test]
+    fn completes_emoji_and_tags() {
+        let doc = Document::new("::thu\n\n<de");
+        let emoji: Vec<String> = completion(&doc, 5).into_iter().map(|c| c.label).collect();
+        let tags: Vec<String> = completion(&doc, 10).into_iter().map(|c| c.label).collect();
+
+        assert_eq!(
+            emoji,
+            vec![
+                "thumbs_up",
+                "thumbs-up",
+                "thumbsUp",
+                "thumbs_down",
+                "thumbs-down"
+            ]
+        );
+        assert_eq!(tags, vec!["del", "details"]);
+        assert!(completion(&doc, 0).is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+//! A language server for the project's Markdown dialect which
+//! communicates with the client over stdio.
+
+mod analysis;
+mod position;
+mod server;
+
+use lsp_server::Connection;
+use server::{capabilities, linter, Server, ServerError};
+
+fn main() -> Result<(), ServerError> {
+    // stdout is used by the protocol so all logging goes to stderr
+    eprintln!("starting the markdown language server");
+
+    let (connection, io_threads) = Connection::stdio();
+    let params = connection.initialize(serde_json::to_value(capabilities())?)?;
+    let linter = linter(&serde_json::from_value(params)?);
+    Server::new(connection, linter).run()?;
+    io_threads.join()?;
+
+    eprintln!("shutting down the markdown language server");
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use lsp_types::{Position, Range};
+use parser::ast::Span;
+
+/// Converts between byte offsets in the content and the line/column
+/// positions used by the protocol; columns are counted in UTF-16
+/// code units.
+pub struct LineIndex<'a> {
+    content: &'a str,
+    /// the byte offset where each line starts
+    lines: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(content: &'a str) -> Self {
+        let mut lines = vec![0];
+        lines.extend(content.match_indices('\n').map(|(idx, _)| idx + 1));
+
+        Self { content, lines }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.content.len());
+        let line = self.lines.partition_point(|start| *start <= offset) - 1;
+        let start = self.lines[line];
+        let character = self.content[start..offset].encode_utf16().count();
+
+        Position::new(line as u32, character as u32)
+    }
+
+    /// the byte offset of a position; positions past the end of a
+    /// line (or the content) are clamped
+    pub fn offset(&self, position: Position) -> usize {
+        let start = match self.lines.get(position.line as usize) {
+            Some(start) => *start,
+            None => return self.content.len(),
+        };
+        let line = self.content[start..]
+            .split_inclusive('\n')
+            .next()
+            .unwrap_or("")
+            .trim_end_matches(['\n', '\r']);
+
+        let mut units = 0;
+        for (idx, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return start + idx;
+            }
+            units += c.len_utf16();
+        }
+
+        start + line.len()
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    pub fn span(&self, range: Range) -> Span {
+        Span::new(self.offset(range.start), self.offset(range.end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_utf16_code_units() {
+        let content = "# 👍 up\nnext\n";
+        let index = LineIndex::new(content);
+
+        // the emoji is 4 bytes but 2 UTF-16 code units
+        assert_eq!(index.position(6), Position::new(0, 4));
+        assert_eq!(index.offset(Position::new(0, 4)), 6);
+        assert_eq!(index.position(10), Position::new(1, 0));
+        assert_eq!(index.offset(Position::new(1, 99)), 14);
+        assert_eq!(index.offset(Position::new(9, 0)), content.len());
+    }
+}
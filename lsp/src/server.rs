@@ -0,0 +1,471 @@
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+    CompletionOptions, CompletionParams, CompletionResponse, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, Location, MarkupContent, MarkupKind, NumberOrString, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, SymbolKind, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use p_pest::document::Document;
+use parser::{
+    diagnostic::Severity,
+    lint::{LintConfig, Linter},
+};
+use serde::de::DeserializeOwned;
+
+use crate::{analysis, position::LineIndex};
+
+pub type ServerError = Box<dyn Error + Sync + Send>;
+
+/// the features the server provides
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_string(), "<".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// the linter configured by the `.markdownlint.json` in the root of the
+/// client's workspace, if it has one
+pub fn linter(params: &InitializeParams) -> Linter {
+    let file = params
+        .root_uri
+        .as_ref()
+        .and_then(|uri| uri.to_file_path().ok())
+        .map(|root| root.join(".markdownlint.json"))
+        .filter(|file| file.is_file());
+
+    match file {
+        Some(file) => match LintConfig::from_file(&file.to_string_lossy()) {
+            Ok(config) => Linter::new(config),
+            Err(err) => {
+                eprintln!("ignoring {}: {}", file.display(), err);
+                Linter::default()
+            }
+        },
+        None => Linter::default(),
+    }
+}
+
+fn params<P: DeserializeOwned>(params: serde_json::Value) -> Result<P, ServerError> {
+    Ok(serde_json::from_value(params)?)
+}
+
+/// A language server for the documents opened by a client.
+pub struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+    linter: Linter,
+}
+
+impl Server {
+    pub fn new(connection: Connection, linter: Linter) -> Self {
+        Self {
+            connection,
+            documents: HashMap::new(),
+            linter,
+        }
+    }
+
+    /// handles messages from the client until it asks the
+    /// server to shut down
+    pub fn run(&mut self) -> Result<(), ServerError> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.request(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                // there's no response to a notification so a bad one
+                // is only logged rather than ending the session
+                Message::Notification(notification) => {
+                    if let Err(err) = self.notification(notification) {
+                        eprintln!("failed to handle a notification: {}", err);
+                    }
+                }
+                Message::Response(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            DocumentSymbolRequest::METHOD => params(request.params)
+                .and_then(|p| self.document_symbols(p))
+                .and_then(|r| Ok(serde_json::to_value(r)?)),
+            GotoDefinition::METHOD => params(request.params)
+                .and_then(|p| self.definition(p))
+                .and_then(|r| Ok(serde_json::to_value(r)?)),
+            HoverRequest::METHOD => params(request.params)
+                .and_then(|p| self.hover(p))
+                .and_then(|r| Ok(serde_json::to_value(r)?)),
+            Completion::METHOD => params(request.params)
+                .and_then(|p| self.completion(p))
+                .and_then(|r| Ok(serde_json::to_value(r)?)),
+            method => {
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request: {}", method),
+                )
+            }
+        };
+
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(err) => Response::new_err(
+                id,
+                lsp_server::ErrorCode::InvalidParams as i32,
+                err.to_string(),
+            ),
+        }
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<(), ServerError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let p: DidOpenTextDocumentParams = params(notification.params)?;
+                let uri = p.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), Document::new(&p.text_document.text));
+                self.publish_diagnostics(uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let p: DidChangeTextDocumentParams = params(notification.params)?;
+                let uri = p.text_document.uri;
+                if let Some(doc) = self.documents.get_mut(&uri) {
+                    for change in p.content_changes {
+                        match change.range {
+                            Some(range) => {
+                                let span = LineIndex::new(doc.content()).span(range);
+                                doc.edit(span.start..span.end, &change.text);
+                            }
+                            None => *doc = Document::new(&change.text),
+                        }
+                    }
+                    self.publish_diagnostics(uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let p: DidCloseTextDocumentParams = params(notification.params)?;
+                self.documents.remove(&p.text_document.uri);
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn document(&self, uri: &Url) -> Result<&Document, ServerError> {
+        self.documents
+            .get(uri)
+            .ok_or_else(|| format!("unknown document: {}", uri).into())
+    }
+
+    fn publish_diagnostics(&self, uri: Url) -> Result<(), ServerError> {
+        let doc = self.document(&uri)?;
+        let index = LineIndex::new(doc.content());
+        let diagnostics = analysis::diagnostics(doc, &self.linter)
+            .into_iter()
+            .map(|d| lsp_types::Diagnostic {
+                range: index.range(d.span),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Info => DiagnosticSeverity::INFORMATION,
+                }),
+                code: d.code.map(NumberOrString::String),
+                source: Some("parkdown".to_string()),
+                message: d.message,
+                ..lsp_types::Diagnostic::default()
+            })
+            .collect();
+
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        self.connection
+            .sender
+            .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                params,
+            )))?;
+
+        Ok(())
+    }
+
+    /// the document and the byte offset of a position within it
+    fn locate(&self, p: &TextDocumentPositionParams) -> Result<(&Document, usize), ServerError> {
+        let doc = self.document(&p.text_document.uri)?;
+        let offset = LineIndex::new(doc.content()).offset(p.position);
+
+        Ok((doc, offset))
+    }
+
+    fn document_symbols(
+        &self,
+        p: DocumentSymbolParams,
+    ) -> Result<DocumentSymbolResponse, ServerError> {
+        let doc = self.document(&p.text_document.uri)?;
+        let index = LineIndex::new(doc.content());
+        // the headings form a hierarchy based on their level; the stack
+        // holds the "open" headings which later headings can nest under
+        let mut stack: Vec<(u8, DocumentSymbol)> = vec![];
+        let mut symbols = vec![];
+
+        let close = |stack: &mut Vec<(u8, DocumentSymbol)>, symbols: &mut Vec<DocumentSymbol>| {
+            if let Some((_, symbol)) = stack.pop() {
+                match stack.last_mut() {
+                    Some((_, parent)) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+                    None => symbols.push(symbol),
+                }
+            }
+        };
+
+        for heading in analysis::headings(doc) {
+            while matches!(stack.last(), Some((level, _)) if *level >= heading.level) {
+                close(&mut stack, &mut symbols);
+            }
+            let range = index.range(heading.span);
+            #[allow(deprecated)]
+            let symbol = DocumentSymbol {
+                name: heading.title,
+                detail: Some(format!("#{}", heading.slug)),
+                kind: SymbolKind::STRING,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children: None,
+            };
+            stack.push((heading.level, symbol));
+        }
+        while !stack.is_empty() {
+            close(&mut stack, &mut symbols);
+        }
+
+        Ok(DocumentSymbolResponse::Nested(symbols))
+    }
+
+    fn definition(
+        &self,
+        p: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>, ServerError> {
+        let (doc, offset) = self.locate(&p.text_document_position_params)?;
+        let index = LineIndex::new(doc.content());
+
+        Ok(analysis::definition(doc, offset).map(|span| {
+            GotoDefinitionResponse::Scalar(Location::new(
+                p.text_document_position_params.text_document.uri.clone(),
+                index.range(span),
+            ))
+        }))
+    }
+
+    fn hover(&self, p: HoverParams) -> Result<Option<Hover>, ServerError> {
+        let (doc, offset) = self.locate(&p.text_document_position_params)?;
+        let index = LineIndex::new(doc.content());
+
+        Ok(analysis::hover(doc, offset).map(|(span, preview)| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: preview,
+            }),
+            range: Some(index.range(span)),
+        }))
+    }
+
+    fn completion(&self, p: CompletionParams) -> Result<Option<CompletionResponse>, ServerError> {
+        let (doc, offset) = self.locate(&p.text_document_position)?;
+        let items = analysis::completion(doc, offset);
+
+        Ok(match items.is_empty() {
+            true => None,
+            false => Some(CompletionResponse::Array(items)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_server::RequestId;
+    use lsp_types::{
+        DocumentSymbolParams, Position, TextDocumentIdentifier, TextDocumentItem,
+        VersionedTextDocumentIdentifier,
+    };
+    use std::thread;
+
+    fn notify<N: NotificationTrait>(client: &Connection, params: N::Params) {
+        client
+            .sender
+            .send(Message::Notification(Notification::new(
+                N::METHOD.to_string(),
+                params,
+            )))
+            .unwrap();
+    }
+
+    fn diagnostics(client: &Connection) -> PublishDiagnosticsParams {
+        match client.receiver.recv().unwrap() {
+            Message::Notification(n) => serde_json::from_value(n.params).unwrap(),
+            message => panic!("unexpected message: {:?}", message),
+        }
+    }
+
+    fn shutdown(client: &Connection, id: i32) {
+        client
+            .sender
+            .send(Message::Request(Request::new(
+                RequestId::from(id),
+                "shutdown".to_string(),
+                (),
+            )))
+            .unwrap();
+        assert!(matches!(client.receiver.recv(), Ok(Message::Response(_))));
+        notify::<lsp_types::notification::Exit>(client, ());
+    }
+
+    #[test]
+    fn publishes_diagnostics_and_symbols() {
+        let (server, client) = Connection::memory();
+        let handle = thread::spawn(move || Server::new(server, Linter::default()).run().unwrap());
+        let uri = Url::parse("file:///test.md").unwrap();
+
+        notify::<DidOpenTextDocument>(
+            &client,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "markdown".to_string(),
+                    1,
+                    "# Foo\n\n### Bar\n".to_string(),
+                ),
+            },
+        );
+        let published = diagnostics(&client);
+        assert_eq!(published.diagnostics.len(), 1);
+        assert_eq!(
+            published.diagnostics[0].code,
+            Some(NumberOrString::String("MD001".to_string()))
+        );
+
+        // fixing the heading level clears the diagnostic
+        notify::<DidChangeTextDocument>(
+            &client,
+            DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+                content_changes: vec![lsp_types::TextDocumentContentChangeEvent {
+                    range: Some(lsp_types::Range::new(
+                        Position::new(2, 0),
+                        Position::new(2, 3),
+                    )),
+                    range_length: None,
+                    text: "##".to_string(),
+                }],
+            },
+        );
+        assert!(diagnostics(&client).diagnostics.is_empty());
+
+        let request = Request::new(
+            RequestId::from(1),
+            DocumentSymbolRequest::METHOD.to_string(),
+            DocumentSymbolParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            },
+        );
+        client.sender.send(Message::Request(request)).unwrap();
+        let symbols: DocumentSymbolResponse = match client.receiver.recv().unwrap() {
+            Message::Response(r) => serde_json::from_value(r.result.unwrap()).unwrap(),
+            message => panic!("unexpected message: {:?}", message),
+        };
+        match symbols {
+            DocumentSymbolResponse::Nested(symbols) => {
+                assert_eq!(symbols.len(), 1);
+                assert_eq!(symbols[0].name, "Foo");
+                assert_eq!(symbols[0].children.as_ref().unwrap()[0].name, "Bar");
+            }
+            _ => panic!("expected nested symbols"),
+        }
+
+        shutdown(&client, 2);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn bad_notifications_are_ignored() {
+        let (server, client) = Connection::memory();
+        let handle = thread::spawn(move || Server::new(server, Linter::default()).run().unwrap());
+
+        client
+            .sender
+            .send(Message::Notification(Notification::new(
+                DidOpenTextDocument::METHOD.to_string(),
+                serde_json::json!({ "textDocument": "oops" }),
+            )))
+            .unwrap();
+
+        // the server is still handling requests
+        let request = Request::new(
+            RequestId::from(1),
+            HoverRequest::METHOD.to_string(),
+            serde_json::json!({
+                "textDocument": { "uri": "file:///test.md" },
+                "position": { "line": 0, "character": 0 },
+            }),
+        );
+        client.sender.send(Message::Request(request)).unwrap();
+        match client.receiver.recv().unwrap() {
+            Message::Response(r) => assert_eq!(r.id, RequestId::from(1)),
+            message => panic!("unexpected message: {:?}", message),
+        }
+
+        shutdown(&client, 2);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn linter_is_configured_by_the_workspace() {
+        let root = std::env::temp_dir().join("parkdown-lsp-config");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".markdownlint.json"), r#"{ "MD001": false }"#).unwrap();
+        let params = InitializeParams {
+            root_uri: Some(Url::from_directory_path(&root).unwrap()),
+            ..InitializeParams::default()
+        };
+
+        let config = linter(&params).config;
+        assert_eq!(
+            config.rules.get("MD001"),
+            Some(&parser::lint::RuleSetting::Enabled(false))
+        );
+        assert_eq!(
+            linter(&InitializeParams::default()).config,
+            LintConfig::default()
+        );
+    }
+}
@@ -1,14 +1,383 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+//! Markdown parsed by **nom** as the events of `parser::event`, so its
+//! output goes through the same renderers and transforms as the other
+//! backends.
+//!
+//! Only a minimal set of blocks is parsed: ATX headings, thematic
+//! breaks, fenced code blocks and paragraphs. The text of a heading or
+//! paragraph isn't parsed for inline content; it's a `Text` per line.
+//!
+//! ```
+//! use p_nom::Events;
+//! use parser::event::to_html;
+//!
+//! let html = to_html(Events::new("# Foo\n\nbar\n")).unwrap();
+//!
+//! assert_eq!(html, "<h1>Foo</h1>\n<p>bar</p>");
+//! ```
+
+use std::collections::VecDeque;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{take_while, take_while_m_n},
+    character::complete::{line_ending, not_line_ending, space0, space1},
+    combinator::{eof, peek, recognize, value, verify},
+    error::{Error, ErrorKind},
+    multi::many0,
+    sequence::{pair, preceded, terminated},
+    IResult, Offset,
+};
+use parser::{
+    ast::Span,
+    event::{Event, Tag},
+};
+
+/// A block which has been parsed; the text is borrowed from the content
+/// so the span of each part can be found.
+#[derive(Debug, Clone, PartialEq)]
+enum Block<'a> {
+    Heading {
+        level: u8,
+        title: &'a str,
+    },
+    Rule,
+    Code {
+        lang: Option<String>,
+        info: Option<String>,
+        code: &'a str,
+    },
+    Paragraph {
+        lines: Vec<&'a str>,
+    },
+}
+
+/// the end of a line or of the input
+fn eol(input: &str) -> IResult<&str, &str> {
+    alt((line_ending, eof))(input)
+}
+
+/// up to three spaces of indentation
+fn indent(input: &str) -> IResult<&str, &str> {
+    take_while_m_n(0, 3, |c| c == ' ')(input)
+}
+
+/// a line with nothing but spaces or tabs; it must consume something
+/// so that it can be repeated
+fn blank_line(input: &str) -> IResult<&str, &str> {
+    verify(recognize(pair(space0, eol)), |line: &str| !line.is_empty())(input)
+}
+
+/// a line along with its line ending
+fn line(input: &str) -> IResult<&str, &str> {
+    terminated(not_line_ending, eol)(input)
+}
+
+/// an ATX heading; a closing sequence of `#`s isn't part of its title
+fn heading(input: &str) -> IResult<&str, Block<'_>> {
+    let (input, _) = indent(input)?;
+    let (input, hashes) = take_while_m_n(1, 6, |c| c == '#')(input)?;
+    let (input, title) = terminated(
+        alt((preceded(space1, not_line_ending), value("", peek(eol)))),
+        eol,
+    )(input)?;
+
+    let title = title.trim();
+    let title = match title.trim_end_matches('#') {
+        "" => "",
+        open if open.ends_with([' ', '\t']) => open.trim_end(),
+        _ => title,
+    };
+
+    Ok((
+        input,
+        Block::Heading {
+            level: hashes.len() as u8,
+            title,
+        },
+    ))
+}
+
+/// three or more of the same `*`, `-` or `_` characters (which may be
+/// separated by spaces or tabs) on a line of their own
+fn thematic_break(input: &str) -> IResult<&str, Block<'_>> {
+    let (input, _) = indent(input)?;
+    let (input, _) = terminated(
+        verify(not_line_ending, |line: &str| {
+            let marks: Vec<char> = line.chars().filter(|c| !matches!(c, ' ' | '\t')).collect();
+            marks.len() >= 3
+                && matches!(marks[0], '*' | '-' | '_')
+                && marks.iter().all(|c| *c == marks[0])
+        }),
+        eol,
+    )(input)?;
+
+    Ok((input, Block::Rule))
+}
+
+/// whether the line closes a fence of the length
+fn closes(line: &str, fence: usize) -> bool {
+    let trimmed = line.trim();
+    line.len() - line.trim_start().len() <= 3
+        && trimmed.len() >= fence
+        && trimmed.chars().all(|c| c == '`')
+}
+
+/// a code block between fences of three or more backticks; the closing
+/// fence must be at least as long as the opening one and without one
+/// the block ends with the input
+fn fenced_code(input: &str) -> IResult<&str, Block<'_>> {
+    let (input, _) = indent(input)?;
+    let (input, fence) = verify(take_while(|c| c == '`'), |f: &str| f.len() >= 3)(input)?;
+    let (input, info) = line(input)?;
+    if info.contains('`') {
+        return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+
+    let mut rest = input;
+    let code = loop {
+        if rest.is_empty() {
+            break input;
+        }
+        let (next, code_line) = line(rest)?;
+        if closes(code_line, fence.len()) {
+            let code = &input[..input.offset(rest)];
+            rest = next;
+            break code;
+        }
+        rest = next;
+    };
+    let code = code.strip_suffix('\n').unwrap_or(code);
+    let code = code.strip_suffix('\r').unwrap_or(code);
+
+    let (lang, info) = info.trim().split_once(' ').unwrap_or((info.trim(), ""));
+    let some = |s: &str| (!s.trim().is_empty()).then(|| s.trim().to_string());
+
+    Ok((
+        rest,
+        Block::Code {
+            lang: some(lang),
+            info: some(info),
+            code,
+        },
+    ))
+}
+
+/// whether the line starts a block which ends a paragraph
+fn interrupts(input: &str) -> bool {
+    alt((heading, thematic_break, fenced_code))(input).is_ok()
+}
+
+/// the lines up to an empty line or a line which starts another block
+fn paragraph(input: &str) -> IResult<&str, Block<'_>> {
+    let mut lines = vec![];
+    let mut rest = input;
+
+    while !rest.is_empty() && (lines.is_empty() || !interrupts(rest)) {
+        let (next, text) = line(rest)?;
+        if text.trim().is_empty() {
+            break;
+        }
+        lines.push(text.trim());
+        rest = next;
+    }
+
+    match lines.is_empty() {
+        true => Err(nom::Err::Error(Error::new(input, ErrorKind::Verify))),
+        false => Ok((rest, Block::Paragraph { lines })),
+    }
+}
+
+/// the next block after any empty lines
+fn block(input: &str) -> IResult<&str, Block<'_>> {
+    preceded(
+        many0(blank_line),
+        alt((heading, thematic_break, fenced_code, paragraph)),
+    )(input)
+}
+
+/// The events of the blocks nom parses from the content; the blocks are
+/// within a `Document` just as they are in the tree.
+pub struct Events<'a> {
+    content: &'a str,
+    /// the content which hasn't been parsed yet
+    rest: &'a str,
+    /// the events of the last block which haven't been read yet
+    queue: VecDeque<(Event, Span)>,
+    started: bool,
+    ended: bool,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            rest: content,
+            queue: VecDeque::new(),
+            started: false,
+            ended: false,
+        }
+    }
+
+    /// the span of text borrowed from the content
+    fn span(&self, text: &str) -> Span {
+        let start = self.content.offset(text);
+        Span::new(start, start + text.len())
+    }
+
+    /// parses the next block into the queue; once nothing but empty
+    /// lines are left there are no more blocks
+    fn read(&mut self) -> bool {
+        let Ok((rest, block)) = block(self.rest) else {
+            self.rest = "";
+            return false;
+        };
+        let consumed = &self.rest[..self.rest.offset(rest)];
+        let start = consumed.len() - consumed.trim_start_matches([' ', '\t', '\r', '\n']).len();
+        let span = self.span(&consumed[start..]);
+        self.rest = rest;
+
+        match block {
+            Block::Heading { level, title } => {
+                let tag = Tag::Heading { level, id: None };
+                self.queue.push_back((Event::Start(tag.clone()), span));
+                if !title.is_empty() {
+                    self.queue
+                        .push_back((Event::Text(title.to_string()), self.span(title)));
+                }
+                self.queue.push_back((Event::End(tag), span));
+            }
+            Block::Rule => self.queue.push_back((Event::Rule, span)),
+            Block::Code { lang, info, code } => {
+                let tag = Tag::CodeBlock { lang, info };
+                self.queue.push_back((Event::Start(tag.clone()), span));
+                self.queue
+                    .push_back((Event::Text(code.to_string()), self.span(code)));
+                self.queue.push_back((Event::End(tag), span));
+            }
+            Block::Paragraph { lines } => {
+                self.queue.push_back((Event::Start(Tag::Paragraph), span));
+                let mut previous: Option<Span> = None;
+                for text in lines {
+                    let text_span = self.span(text);
+                    if let Some(previous) = previous {
+                        let between = Span::new(previous.end, text_span.start);
+                        self.queue.push_back((Event::SoftBreak, between));
+                    }
+                    self.queue
+                        .push_back((Event::Text(text.to_string()), text_span));
+                    previous = Some(text_span);
+                }
+                self.queue.push_back((Event::End(Tag::Paragraph), span));
+            }
+        }
+
+        true
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = (Event, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let document = Span::new(0, self.content.len());
+        if !self.started {
+            self.started = true;
+            return Some((Event::Start(Tag::Document), document));
+        }
+
+        while self.queue.is_empty() && !self.rest.is_empty() {
+            if !self.read() {
+                break;
+            }
+        }
+        if let Some(event) = self.queue.pop_front() {
+            return Some(event);
+        }
+
+        match self.ended {
+            true => None,
+            false => {
+                self.ended = true;
+                Some((Event::End(Tag::Document), document))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parser::{
+        ast::{Node, NodeKind},
+        event::{to_html, to_tree},
+    };
+
+    #[test]
+    fn events_build_the_shared_tree() {
+        let md = "# Foo #\n\n***\n```rs\nlet x = 1;\nlet y = 2;\n```\n\nsome\ntext\n";
+        let tree = to_tree(Events::new(md)).unwrap();
+        let kinds: Vec<&NodeKind> = tree.children.iter().map(|c| &c.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                &NodeKind::Heading { level: 1, id: None },
+                &NodeKind::ThematicBreak,
+                &NodeKind::CodeBlock {
+                    lang: Some("rs".to_string()),
+                    info: None
+                },
+                &NodeKind::Paragraph,
+            ]
+        );
+        assert_eq!(
+            tree.children[0].children,
+            vec![Node::text("Foo", Span::new(2, 5))]
+        );
+        assert_eq!(
+            tree.children[2].children,
+            vec![Node::text("let x = 1;\nlet y = 2;", Span::new(19, 40))]
+        );
+        assert_eq!(tree.children[3].span, Span::new(46, 56));
+        assert_eq!(tree.children[3].children[1].kind, NodeKind::SoftBreak);
+    }
+
+    #[test]
+    fn blocks_interrupt_a_paragraph() {
+        let md = "text\n## Heading\nmore\n---\n";
+
+        assert_eq!(
+            to_html(Events::new(md)).unwrap(),
+            "<p>text</p>\n<h2>Heading</h2>\n<p>more</p>\n<hr />"
+        );
+    }
+
+    #[test]
+    fn code_is_only_closed_by_a_fence_as_long_as_its_own() {
+        let md = "````md\n```\nnot closed\n````\nafter\n";
+        let tree = to_tree(Events::new(md)).unwrap();
+
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(
+            tree.children[0].children[0].text_content(),
+            "```\nnot closed"
+        );
+        assert_eq!(tree.children[1].kind, NodeKind::Paragraph);
+
+        let unclosed = to_tree(Events::new("```\ncode\n")).unwrap();
+        assert_eq!(unclosed.children[0].text_content(), "code");
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn empty_content_is_an_empty_document() {
+        let events: Vec<(Event, Span)> = Events::new("\n  \n").collect();
+
+        assert_eq!(
+            events,
+            vec![
+                (Event::Start(Tag::Document), Span::new(0, 4)),
+                (Event::End(Tag::Document), Span::new(0, 4)),
+            ]
+        );
     }
 }
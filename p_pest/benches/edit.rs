@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use p_pest::document::Document;
+
+/// a large document made up of many different kinds of blocks
+fn large_document() -> String {
+    let section =
+        "- foo\n- bar\n  - baz\n\n```rust\nlet x = 1;\n```\n\n::smile { size: \"2x\" }\n\n---\n\n";
+    (0..2_000)
+        .map(|n| format!("## Section {}\n\n{}", n, section))
+        .collect()
+}
+
+fn edit(c: &mut Criterion) {
+    let content = large_document();
+    let middle = content.len() / 2;
+    let item = middle + content[middle..].find("- foo").unwrap() + 2;
+    let heading = middle + content[middle..].find("## Section").unwrap() + 3;
+
+    c.bench_function("full parse", |b| {
+        b.iter(|| Document::new(black_box(&content)))
+    });
+
+    // each iteration is a _single_ edit; a character is inserted and
+    // then deleted by the next iteration so the document doesn't grow
+    let mut doc = Document::new(&content);
+    for (name, at) in [("edit a list item", item), ("edit a heading", heading)] {
+        let mut inserted = false;
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                match inserted {
+                    false => doc.edit(at..at, black_box("x")),
+                    true => doc.edit(at..at + 1, black_box("")),
+                };
+                inserted = !inserted;
+            })
+        });
+        if inserted {
+            doc.edit(at..at + 1, "");
+        }
+    }
+}
+
+criterion_group!(benches, edit);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+//! Prints the JSON Schema of the exported parse tree; run with
+//! `cargo run --example schema > schema/tree.schema.json` after
+//! changing the shape of the export.
+
+fn main() {
+    let schema = p_pest::export::schema();
+
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+}
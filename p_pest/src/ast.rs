@@ -0,0 +1,508 @@
+use parser::{
+    ast::{Node, NodeKind, Span},
+    entity::decode,
+};
+use pest::{iterators::Pair, Parser};
+
+use crate::{parkdown::parse_blocks, Markdown, Rule};
+
+/// the `Span` of a pair, moved forward by `offset`
+fn span_of(pair: &Pair<Rule>, offset: usize) -> Span {
+    let span = pair.as_span();
+    Span::new(span.start(), span.end()).offset(offset)
+}
+
+/// a `Text` node for the portion of `raw` which remains after
+/// trimming; `start` is where `raw` starts in the source. Character
+/// references in the text are decoded.
+fn trimmed_text(raw: &str, start: usize, trim: &[char]) -> Node {
+    let left = raw.len() - raw.trim_start_matches(trim).len();
+    let text = raw.trim_matches(trim);
+
+    Node::text(
+        &decode(text),
+        Span::new(start + left, start + left + text.len()),
+    )
+}
+
+/// The inline nodes (text, links, line breaks, etc.) found in `raw`
+/// once it's trimmed; `start` is where `raw` starts in the source.
+/// Adjacent text is merged into a single `Text` node.
+fn inline_content(raw: &str, start: usize) -> Vec<Node> {
+    let trim = [' ', '\t', '\r', '\n'];
+    let left = raw.len() - raw.trim_start_matches(trim).len();
+    let text = raw.trim_matches(trim);
+
+    let nodes: Vec<Node> = match Markdown::parse(Rule::inlines, text) {
+        Ok(pairs) => pairs.flat_map(|p| to_nodes(p, start + left)).collect(),
+        Err(_) => return vec![trimmed_text(raw, start, &trim)],
+    };
+
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match (merged.last_mut(), &node.kind) {
+            (Some(last), NodeKind::Text(text)) if last.span.end == node.span.start => {
+                if let NodeKind::Text(previous) = &mut last.kind {
+                    previous.push_str(text);
+                    last.span = Span::new(last.span.start, node.span.end);
+                    continue;
+                }
+                merged.push(node);
+            }
+            _ => merged.push(node),
+        }
+    }
+
+    merged
+}
+
+/// The title of an ATX heading (the `raw` text of `h1`-`h6`) along with
+/// where it starts in `raw`; the opening sequence of `#` and any
+/// closing sequence are removed so `# C#` is titled `C#`.
+pub(crate) fn heading_title(raw: &str) -> (usize, &str) {
+    let blank = [' ', '\t'];
+    let rest = raw.trim_start_matches(blank);
+    let rest = rest.trim_start_matches('#');
+    let rest = rest.trim_start_matches(blank);
+
+    (raw.len() - rest.len(), without_closing_sequence(rest))
+}
+
+/// A heading's title without its closing sequence (or trailing
+/// whitespace); a run of `#` at the end is only a closing sequence
+/// when it's preceded by a space or tab (or there's nothing else).
+pub(crate) fn without_closing_sequence(title: &str) -> &str {
+    let blank = [' ', '\t'];
+    let title = title.trim_end_matches([' ', '\t', '\r', '\n']);
+    let unclosed = title.trim_end_matches('#');
+
+    match unclosed.is_empty() || unclosed.ends_with(blank) {
+        true => unclosed.trim_end_matches(blank),
+        false => title,
+    }
+}
+
+fn heading(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let level = match pair.as_rule() {
+        Rule::h1 => 1,
+        Rule::h2 => 2,
+        Rule::h3 => 3,
+        Rule::h4 => 4,
+        Rule::h5 => 5,
+        _ => 6,
+    };
+    let (left, text) = heading_title(pair.as_str());
+    let start = span.start + left;
+    let title = Node::text(&decode(text), Span::new(start, start + text.len()));
+
+    Node::with_children(NodeKind::Heading { level, id: None }, span, vec![title])
+}
+
+/// the key/value pairs found under an `attrs` rule; _boolean_
+/// attributes have an empty value
+fn attributes(pair: Pair<Rule>) -> Vec<(String, String)> {
+    pair.into_inner()
+        .filter(|p| p.as_rule() == Rule::attr)
+        .map(|attr| {
+            let mut key = "".to_string();
+            let mut value = "".to_string();
+            for part in attr.into_inner() {
+                match part.as_rule() {
+                    Rule::attr_name => key = part.as_str().to_string(),
+                    Rule::value | Rule::single_quoted_value | Rule::unquoted_value => {
+                        value = part.as_str().to_string()
+                    }
+                    _ => (),
+                }
+            }
+            (key, value)
+        })
+        .collect()
+}
+
+/// whether the opening tag of a block tag is followed by an empty line;
+/// as with CommonMark's HTML blocks, only then is the inner content
+/// parsed as Markdown
+fn starts_with_empty_line(inner: &str) -> bool {
+    let mut lines = inner.split_inclusive('\n');
+    matches!(
+        (lines.next(), lines.next()),
+        (Some(first), Some(second)) if first.trim().is_empty() && second.trim().is_empty()
+    )
+}
+
+/// a tag along with its inner content; self-closing tags have none
+fn block_tag(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let mut name = "".to_string();
+    let mut attrs = vec![];
+    let mut children = vec![];
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::tagname => name = part.as_str().to_string(),
+            Rule::attrs => attrs = attributes(part),
+            Rule::inner if starts_with_empty_line(part.as_str()) => {
+                let start = span_of(&part, offset).start;
+                children.extend(parse_dedented(part.as_str(), start));
+            }
+            // content which isn't Markdown is raw HTML
+            Rule::inner if !part.as_str().is_empty() => children.push(Node::new(
+                NodeKind::Html(part.as_str().to_string()),
+                span_of(&part, offset),
+            )),
+            _ => (),
+        }
+    }
+
+    Node::with_children(NodeKind::Tag { name, attrs }, span, children)
+}
+
+/// an HTML block which is kept as raw HTML; the line ending
+/// which terminates the block isn't part of it
+fn html(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let raw = pair.as_str().trim_end_matches(['\r', '\n']);
+
+    Node::new(
+        NodeKind::Html(raw.to_string()),
+        Span::new(span.start, span.start + raw.len()),
+    )
+}
+
+fn link(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let mut dest = "".to_string();
+    let mut children = vec![];
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::link_desc => {
+                let start = span_of(&part, offset).start;
+                children.push(trimmed_text(part.as_str(), start, &['[', ']']));
+            }
+            Rule::link_uri => {
+                let uri = part.as_str().trim_start_matches('(').trim_end_matches(')');
+                dest = decode(uri).into_owned()
+            }
+            _ => (),
+        }
+    }
+
+    Node::with_children(NodeKind::Link { dest }, span, children)
+}
+
+fn image(pair: Pair<Rule>, offset: usize) -> Node {
+    let link = link(pair, offset);
+
+    match link.kind {
+        NodeKind::Link { dest } => {
+            Node::with_children(NodeKind::Image { src: dest }, link.span, link.children)
+        }
+        _ => link,
+    }
+}
+
+/// the props of a `dict` (or `csv`) as key/value pairs
+fn props(pair: Pair<Rule>) -> Vec<(String, String)> {
+    pair.into_inner()
+        .filter(|p| p.as_rule() == Rule::prop)
+        .map(|prop| {
+            let mut key = "".to_string();
+            let mut value = "".to_string();
+            for part in prop.into_inner() {
+                match part.as_rule() {
+                    Rule::prop_name => key = part.as_str().to_string(),
+                    Rule::value => value = part.as_str().to_string(),
+                    _ => (),
+                }
+            }
+            (key, value)
+        })
+        .collect()
+}
+
+fn emoji(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let block = pair.as_rule() == Rule::block_emoji;
+    let mut name = "".to_string();
+    let mut attrs = vec![];
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::emoji_name => name = part.as_str().to_string(),
+            Rule::dict => attrs = props(part),
+            _ => (),
+        }
+    }
+
+    if block {
+        Node::new(NodeKind::BlockEmoji { name, attrs }, span)
+    } else {
+        Node::new(NodeKind::Emoji { name }, span)
+    }
+}
+
+/// moves every span in the node's subtree with the given mapping
+pub(crate) fn map_spans(node: &mut Node, map: &dyn Fn(usize) -> usize) {
+    node.span = Span::new(map(node.span.start), map(node.span.end));
+    for child in node.children.iter_mut() {
+        map_spans(child, map);
+    }
+}
+
+/// Parses indented lines (e.g., those which follow the first line of a
+/// list item) as blocks of their own. The common indentation is removed
+/// before parsing and the resulting spans are mapped back to their place
+/// in the source.
+fn parse_dedented(raw: &str, start: usize) -> Vec<Node> {
+    let indent = raw
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    parse_stripped(raw, start, &|line| {
+        let leading = line.len() - line.trim_start_matches([' ', '\t']).len();
+        leading.min(indent)
+    })
+}
+
+/// the length of a block quote line's `>` marker along with the
+/// indentation before it and the space (or tab) after it
+fn quote_marker(line: &str) -> usize {
+    let leading = line.len() - line.trim_start_matches(' ').len();
+    match line[leading..].strip_prefix('>') {
+        Some(rest) if rest.starts_with([' ', '\t']) => leading + 2,
+        Some(_) => leading + 1,
+        None => 0,
+    }
+}
+
+/// Parses lines as blocks of their own once `strip` bytes (given by
+/// the function for each line) are removed from the start of each
+/// line; the resulting spans are mapped back to their place in the
+/// source.
+fn parse_stripped(raw: &str, start: usize, strip: &dyn Fn(&str) -> usize) -> Vec<Node> {
+    let mut content = String::with_capacity(raw.len());
+    // (start in the stripped content, start in the source)
+    let mut lines: Vec<(usize, usize)> = vec![];
+    let mut source_pos = start;
+
+    for line in raw.split_inclusive('\n') {
+        let strip = strip(line);
+        lines.push((content.len(), source_pos + strip));
+        content.push_str(&line[strip..]);
+        source_pos += line.len();
+    }
+
+    let map = |pos: usize| match lines.iter().rev().find(|(stripped, _)| *stripped <= pos) {
+        Some((stripped, source)) => source + (pos - stripped),
+        None => start + pos,
+    };
+
+    let (mut nodes, _) = parse_blocks(&content);
+    for node in nodes.iter_mut() {
+        map_spans(node, &map);
+    }
+
+    nodes
+}
+
+/// whether the line starts a block (other than a paragraph)
+fn starts_block(line: &str) -> bool {
+    let line = line.trim_start();
+    Markdown::parse(Rule::leaf_block, line).is_ok()
+        || Markdown::parse(Rule::list_item, line).is_ok()
+        || Markdown::parse(Rule::block_quote, line).is_ok()
+}
+
+fn list_item(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let item = pair.as_str();
+    let mut marker = "".to_string();
+    let mut text: Option<Span> = None;
+    let mut continuation: Option<Span> = None;
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::list_marker | Rule::ordered_marker => marker = part.as_str().to_string(),
+            Rule::list_text => text = Some(span_of(&part, offset)),
+            Rule::list_continuation => {
+                let part_span = span_of(&part, offset);
+                continuation = match continuation {
+                    Some(c) => Some(Span::new(c.start, part_span.end)),
+                    None => Some(part_span),
+                };
+            }
+            _ => (),
+        }
+    }
+
+    let slice = |s: Span| &item[s.start - span.start..s.end - span.start];
+    let mut children = vec![];
+
+    if let Some(text) = text {
+        let mut end = text.end;
+
+        // lines which directly follow the first line -- and don't start
+        // a block of their own -- continue the item's first paragraph
+        if let Some(c) = continuation {
+            let mut pos = c.start;
+            for line in slice(c).split_inclusive('\n') {
+                if line.trim().is_empty() || starts_block(line) {
+                    break;
+                }
+                end = pos + line.trim_end().len();
+                pos += line.len();
+            }
+            continuation = if pos < c.end {
+                Some(Span::new(pos, c.end))
+            } else {
+                None
+            };
+        }
+
+        let paragraph = Span::new(text.start, end);
+        children.push(Node::with_children(
+            NodeKind::Paragraph,
+            paragraph,
+            inline_content(slice(paragraph), paragraph.start),
+        ));
+    }
+
+    if let Some(c) = continuation {
+        children.extend(parse_dedented(slice(c), c.start));
+    }
+
+    Node::with_children(NodeKind::ListItem { marker }, span, children)
+}
+
+fn list(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let items: Vec<Node> = pair
+        .into_inner()
+        .filter(|p| p.as_rule() == Rule::list_item)
+        .map(|p| list_item(p, offset))
+        .collect();
+    let ordered = match items.first().map(|i| &i.kind) {
+        Some(NodeKind::ListItem { marker }) => marker.ends_with(['.', ')']),
+        _ => false,
+    };
+
+    Node::with_children(NodeKind::List { ordered }, span, items)
+}
+
+/// strips the indentation from an indented `code_line`
+fn unindent(line: &str) -> &str {
+    if let Some(line) = line.strip_prefix('\t') {
+        line
+    } else {
+        line.strip_prefix("    ").unwrap_or(line)
+    }
+}
+
+fn code_block(pair: Pair<Rule>, offset: usize) -> Node {
+    let span = span_of(&pair, offset);
+    let mut lang = None;
+    let mut info = None;
+    let mut lines: Vec<&str> = vec![];
+
+    for part in pair.into_inner() {
+        match part.as_rule() {
+            Rule::fence_defn => {
+                for defn in part.into_inner() {
+                    match defn.as_rule() {
+                        Rule::fence_lang if !defn.as_str().is_empty() => {
+                            lang = Some(defn.as_str().to_string())
+                        }
+                        Rule::fence_info => info = Some(defn.as_str().trim().to_string()),
+                        _ => (),
+                    }
+                }
+            }
+            Rule::code_line => lines.push(unindent(part.as_str())),
+            Rule::fenced_code_line => lines.push(part.as_str()),
+            _ => (),
+        }
+    }
+
+    let code = lines.join("\n");
+    Node::with_children(
+        NodeKind::CodeBlock { lang, info },
+        span,
+        vec![Node::text(&code, span)],
+    )
+}
+
+/// Converts a pest `Pair` into zero or more nodes of the shared
+/// AST. Structural rules (e.g., `block`) have no node of their own
+/// and are replaced by their children.
+///
+/// - `offset` is added to all spans and should be set when the pair
+///   was parsed from a slice of the full document
+pub fn to_nodes(pair: Pair<Rule>, offset: usize) -> Vec<Node> {
+    let span = span_of(&pair, offset);
+
+    match pair.as_rule() {
+        Rule::EOI => vec![],
+        Rule::file => vec![Node::with_children(
+            NodeKind::Document,
+            span,
+            pair.into_inner()
+                .flat_map(|p| to_nodes(p, offset))
+                .collect(),
+        )],
+        Rule::h1 | Rule::h2 | Rule::h3 | Rule::h4 | Rule::h5 | Rule::h6 => {
+            vec![heading(pair, offset)]
+        }
+        Rule::thematic_break => vec![Node::new(NodeKind::ThematicBreak, span)],
+        Rule::toc => vec![Node::new(NodeKind::Toc, span)],
+        Rule::paragraph => vec![Node::with_children(
+            NodeKind::Paragraph,
+            span,
+            inline_content(pair.as_str(), span.start),
+        )],
+        Rule::indented_code_block | Rule::fenced_code_block => {
+            if span.is_empty() {
+                vec![]
+            } else {
+                vec![code_block(pair, offset)]
+            }
+        }
+        Rule::block_tag => vec![block_tag(pair, offset)],
+        Rule::self_closing_tag => vec![block_tag(pair, offset)],
+        Rule::raw_html | Rule::html_literal | Rule::html_lines => vec![html(pair, offset)],
+        Rule::link => vec![link(pair, offset)],
+        Rule::image => vec![image(pair, offset)],
+        Rule::emoji | Rule::block_emoji => vec![emoji(pair, offset)],
+        Rule::list => vec![list(pair, offset)],
+        Rule::block_quote => vec![Node::with_children(
+            NodeKind::BlockQuote,
+            span,
+            parse_stripped(pair.as_str(), span.start, &quote_marker),
+        )],
+        Rule::entity_ref | Rule::dec_num_ref | Rule::hex_num_ref | Rule::inline_text => {
+            vec![Node::text(&decode(pair.as_str()), span)]
+        }
+        Rule::soft_break => vec![Node::new(NodeKind::SoftBreak, span)],
+        Rule::hard_break => vec![Node::new(NodeKind::HardBreak, span)],
+        _ => {
+            let children: Vec<Pair<Rule>> = pair.clone().into_inner().collect();
+            if children.is_empty() {
+                if span.is_empty() {
+                    vec![]
+                } else {
+                    vec![Node::text(pair.as_str(), span)]
+                }
+            } else {
+                children
+                    .into_iter()
+                    .flat_map(|p| to_nodes(p, offset))
+                    .collect()
+            }
+        }
+    }
+}
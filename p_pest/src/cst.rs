@@ -0,0 +1,243 @@
+use parser::{
+    ast::Span,
+    cst::{CstElement, CstNode, Token, TokenKind},
+};
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
+
+use crate::{
+    ast::without_closing_sequence,
+    parkdown::{regions, Region},
+    Markdown, Rule,
+};
+
+/// Adds the section of `source` between `from` and `to` -- which no
+/// rule claimed -- to `children` as trivia; `offset` is where `source`
+/// starts in the document.
+fn push_trivia(
+    children: &mut Vec<CstElement>,
+    source: &str,
+    (from, to): (usize, usize),
+    offset: usize,
+) {
+    if from < to {
+        children.extend(
+            Token::trivia(&source[from..to], from + offset)
+                .into_iter()
+                .map(CstElement::Token),
+        );
+    }
+}
+
+/// The tokens of a pair which has no inner pairs; `start` is where the
+/// pair starts in the document. A heading's `title` is atomic so its
+/// closing sequence is split out as trivia here.
+fn leaf_tokens(rule: Rule, text: &str, start: usize) -> Vec<Token> {
+    match rule {
+        Rule::title => {
+            let title = without_closing_sequence(text);
+            let mut tokens = vec![];
+            if !title.is_empty() {
+                tokens.push(Token::new(TokenKind::Text, title, start));
+            }
+            tokens.extend(Token::trivia(&text[title.len()..], start + title.len()));
+
+            tokens
+        }
+        _ if text.is_empty() => vec![],
+        _ => vec![Token::new(TokenKind::Text, text, start)],
+    }
+}
+
+/// Converts a pair into a node of the syntax tree; any part of the pair
+/// which isn't covered by an inner pair (e.g., silent or atomic matches)
+/// becomes a token so that no content is lost.
+///
+/// `source` is the content the pair was parsed from and `offset` is
+/// where that content starts in the document.
+fn to_node(pair: Pair<Rule>, source: &str, offset: usize) -> CstNode {
+    let span = pair.as_span();
+    let kind = pair.as_rule();
+    let rule = format!("{:?}", kind);
+    let mut children = vec![];
+    let mut inner = pair.into_inner().peekable();
+
+    if inner.peek().is_none() {
+        children.extend(
+            leaf_tokens(kind, span.as_str(), span.start() + offset)
+                .into_iter()
+                .map(CstElement::Token),
+        );
+    } else {
+        let mut pos = span.start();
+        for child in inner {
+            let child_span = child.as_span();
+            push_trivia(&mut children, source, (pos, child_span.start()), offset);
+            children.push(CstElement::Node(to_node(child, source, offset)));
+            pos = child_span.end();
+        }
+        push_trivia(&mut children, source, (pos, span.end()), offset);
+    }
+
+    CstNode::new(
+        &rule,
+        Span::new(span.start(), span.end()).offset(offset),
+        children,
+    )
+}
+
+/// wraps the elements in a `document` node unless there's already
+/// a single node covering all of the source
+fn to_root(mut children: Vec<CstElement>, len: usize) -> CstNode {
+    if children.len() == 1 && children[0].span() == Span::new(0, len) {
+        if let CstElement::Node(node) = children.remove(0) {
+            return node;
+        }
+        unreachable!()
+    }
+
+    CstNode::new("document", Span::new(0, len), children)
+}
+
+/// Builds a lossless syntax tree from the pairs produced by parsing
+/// `source`. Any content following the pairs -- which the rule didn't
+/// consume -- is kept as an `Unparsed` token.
+pub fn from_pairs(pairs: Pairs<Rule>, source: &str) -> CstNode {
+    let mut children = vec![];
+    let mut pos = 0;
+
+    for pair in pairs {
+        let span = pair.as_span();
+        push_trivia(&mut children, source, (pos, span.start()), 0);
+        children.push(CstElement::Node(to_node(pair, source, 0)));
+        pos = span.end();
+    }
+    if pos < source.len() {
+        children.push(CstElement::Token(Token::new(
+            TokenKind::Unparsed,
+            &source[pos..],
+            pos,
+        )));
+    }
+
+    to_root(children, source.len())
+}
+
+/// Builds a lossless syntax tree for `source` using the given rule.
+///
+/// This never fails: when the `file` rule can't parse the content it
+/// is parsed block by block and any region which fails to parse is
+/// kept as an `Unparsed` token. For other rules, content which fails
+/// to parse is a single `Unparsed` token.
+pub fn parse(rule: Rule, source: &str) -> CstNode {
+    if let Ok(pairs) = Markdown::parse(rule, source) {
+        return from_pairs(pairs, source);
+    }
+    if rule != Rule::file {
+        let token = Token::new(TokenKind::Unparsed, source, 0);
+        return CstNode::new("document", token.span, vec![CstElement::Token(token)]);
+    }
+
+    let mut children = vec![];
+    let mut pos = 0;
+
+    for region in regions(source) {
+        match region {
+            Region::Block(pairs, start) => {
+                let block_source = &source[start..];
+                push_trivia(&mut children, source, (pos, start), 0);
+                for pair in pairs {
+                    pos = start + pair.as_span().end();
+                    children.push(CstElement::Node(to_node(pair, block_source, start)));
+                }
+            }
+            Region::Failed(span, _) => {
+                push_trivia(&mut children, source, (pos, span.start), 0);
+                children.push(CstElement::Token(Token::new(
+                    TokenKind::Unparsed,
+                    &source[span.start..span.end],
+                    span.start,
+                )));
+                pos = span.end;
+            }
+        }
+    }
+    push_trivia(&mut children, source, (pos, source.len()), 0);
+
+    CstNode::new("document", Span::new(0, source.len()), children)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn silent_markers_are_kept_as_trivia() {
+        let cst = parse(Rule::dict, "{ class: \"foo\" }");
+        let markers: Vec<&str> = cst
+            .tokens()
+            .into_iter()
+            .filter(|t| t.kind == TokenKind::Marker)
+            .map(|t| t.text.as_str())
+            .collect();
+
+        assert_eq!(cst.rule, "dict");
+        assert_eq!(markers, vec!["{", ":", "\"", "\"", "}"]);
+        assert_eq!(cst.find_all("prop").len(), 1);
+    }
+
+    #[test]
+    fn heading_markers_are_tokens() {
+        let cst = parse(Rule::heading, "  ## #1 C# ##\n");
+        let tokens: Vec<(TokenKind, &str)> = cst
+            .tokens()
+            .into_iter()
+            .map(|t| (t.kind, t.text.as_str()))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenKind::Whitespace, "  "),
+                (TokenKind::Marker, "##"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Text, "#1 C#"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Marker, "##"),
+                (TokenKind::Newline, "\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_byte_is_attributable() {
+        let md = "# Foo\n\n\n- bar\n  - baz\n\n```rust\nlet x = 1;\n```\n\n```stray\n\n::smile { a: \"b\" }\n";
+        let cst = parse(Rule::file, md);
+        let tokens = cst.tokens();
+
+        assert_eq!(cst.text(), md);
+        // the tokens are contiguous and cover the source
+        let mut pos = 0;
+        for token in &tokens {
+            assert_eq!(token.span.start, pos);
+            assert_eq!(&md[token.span.start..token.span.end], token.text);
+            pos = token.span.end;
+        }
+        assert_eq!(pos, md.len());
+        assert!(tokens
+            .iter()
+            .any(|t| t.kind == TokenKind::Unparsed && t.text == "```stray"));
+    }
+
+    proptest! {
+        #[test]
+        fn cst_reproduces_the_source(md in "[-#*`a-z0-9 \t\n:{}\"<>/!\\[\\]()]{0,120}") {
+            let cst = parse(Rule::file, &md);
+
+            prop_assert_eq!(cst.text(), md);
+        }
+    }
+}
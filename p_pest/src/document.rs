@@ -0,0 +1,430 @@
+use parser::{
+    ast::{Node, NodeKind, Span},
+    diagnostic::Diagnostic,
+    slug::{headings, slug, Slugger},
+};
+use pest::Parser;
+use std::ops::Range;
+
+use crate::{
+    ast::{map_spans, to_nodes},
+    parkdown::{next_region, skip_whitespace, Region},
+    Markdown, Rule,
+};
+
+/// A top-level block of the document along with the region of the
+/// content it was parsed from.
+///
+/// The spans of the block's nodes and diagnostics are _relative_ to
+/// where the block starts so that an edit only has to move the `span`
+/// of the blocks which follow it.
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    /// the region of the content which was parsed; this includes
+    /// any trailing line ending consumed by the block
+    span: Span,
+    nodes: Vec<Node>,
+    diagnostics: Vec<Diagnostic>,
+    /// the slug of each of the block's headings _before_ it's made
+    /// unique; kept so the ids can be assigned again without the
+    /// headings' text being slugged again
+    slugs: Vec<String>,
+    /// the id assigned to each of the block's headings
+    ids: Vec<String>,
+}
+
+/// sets the ids of the headings in the nodes, in document order
+fn set_ids<'a>(nodes: &mut [Node], ids: &mut impl Iterator<Item = &'a String>) {
+    for node in nodes {
+        if let NodeKind::Heading { id, .. } = &mut node.kind {
+            *id = ids.next().cloned();
+        }
+        set_ids(&mut node.children, ids);
+    }
+}
+
+impl Block {
+    /// the block at `span` whose nodes and diagnostics are relative to
+    /// where it starts
+    fn new(span: Span, nodes: Vec<Node>, diagnostics: Vec<Diagnostic>) -> Self {
+        let slugs = nodes
+            .iter()
+            .flat_map(headings)
+            .map(|(_, text)| slug(&text))
+            .collect();
+
+        Self {
+            span,
+            nodes,
+            diagnostics,
+            slugs,
+            ids: vec![],
+        }
+    }
+
+    /// gives the block's headings the ids; its nodes are only updated
+    /// when the ids have changed
+    fn assign_ids(&mut self, ids: Vec<String>) {
+        if ids != self.ids {
+            set_ids(&mut self.nodes, &mut ids.iter());
+            self.ids = ids;
+        }
+    }
+
+    fn parse(content: &str, pos: usize) -> (Block, usize) {
+        let (region, next) = next_region(content, pos);
+        let block = match region {
+            Region::Block(pairs, start) => Block::new(
+                Span::new(start, start + pairs.as_str().len()),
+                pairs.flat_map(|p| to_nodes(p, 0)).collect(),
+                vec![],
+            ),
+            Region::Failed(span, message) => {
+                let relative = Span::new(0, span.len());
+                Block::new(
+                    span,
+                    vec![Node::text(&content[span.start..span.end], relative)],
+                    vec![Diagnostic::warning(&message, relative)],
+                )
+            }
+        };
+
+        (block, next)
+    }
+
+    /// the block's nodes in their place in the content
+    fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.nodes.iter().map(|node| {
+            let mut node = node.clone();
+            map_spans(&mut node, &|pos| pos + self.span.start);
+            node
+        })
+    }
+
+    /// the block's diagnostics in their place in the content
+    fn diagnostics(&self) -> impl Iterator<Item = Diagnostic> + '_ {
+        let start = self.span.start;
+        self.diagnostics.iter().map(move |diagnostic| Diagnostic {
+            span: Span::new(diagnostic.span.start + start, diagnostic.span.end + start),
+            ..diagnostic.clone()
+        })
+    }
+}
+
+/// A Markdown document which is kept parsed as it's edited.
+///
+/// The document is parsed one block at a time so that an edit only
+/// needs to re-parse the blocks it touches; the blocks which follow
+/// are reused as soon as parsing gets back in step with the prior
+/// parse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    content: String,
+    blocks: Vec<Block>,
+}
+
+impl Document {
+    pub fn new(content: &str) -> Self {
+        // parsing the whole file at once is _much_ faster than parsing
+        // it block by block, so that's only done when the file fails
+        if let Ok(pairs) = Markdown::parse(Rule::file, content) {
+            let blocks = pairs
+                .flat_map(|file| file.into_inner())
+                .filter(|p| p.as_rule() == Rule::block)
+                .map(|block| {
+                    let span = Span::new(block.as_span().start(), block.as_span().end());
+                    let mut nodes = to_nodes(block, 0);
+                    for node in nodes.iter_mut() {
+                        map_spans(node, &|pos| pos - span.start);
+                    }
+                    Block::new(span, nodes, vec![])
+                })
+                .collect();
+
+            let mut doc = Self {
+                content: content.to_string(),
+                blocks,
+            };
+            doc.assign_ids(0, |_| true);
+
+            return doc;
+        }
+
+        let mut blocks = vec![];
+        let mut pos = skip_whitespace(content, 0);
+
+        while pos < content.len() {
+            let (block, next) = Block::parse(content, pos);
+            blocks.push(block);
+            pos = next;
+        }
+
+        let mut doc = Self {
+            content: content.to_string(),
+            blocks,
+        };
+        doc.assign_ids(0, |_| true);
+
+        doc
+    }
+
+    /// sets the ids of the headings in the blocks from `first` onward
+    /// whose slugs are `affected`; the affected headings before them only
+    /// need their slugs replayed to find the ids which are already taken
+    fn assign_ids(&mut self, first: usize, affected: impl Fn(&str) -> bool) {
+        let mut slugger = Slugger::default();
+        let before = self.blocks[..first].iter().flat_map(|b| b.slugs.iter());
+        for slug in before.filter(|s| affected(s)) {
+            slugger.unique(slug);
+        }
+        for block in self.blocks[first..].iter_mut() {
+            if block.ids.len() == block.slugs.len() && !block.slugs.iter().any(|s| affected(s)) {
+                continue;
+            }
+            let ids = (block.slugs.iter().enumerate())
+                .map(|(idx, slug)| match block.ids.get(idx) {
+                    Some(id) if !affected(slug) => id.clone(),
+                    _ => slugger.unique(slug),
+                })
+                .collect();
+            block.assign_ids(ids);
+        }
+    }
+
+    /// the current content of the document
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// the top-level nodes of the document; its headings have their ids
+    pub fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.blocks.iter().flat_map(|b| b.nodes())
+    }
+
+    /// the document as a tree
+    pub fn tree(&self) -> Node {
+        Node::with_children(
+            NodeKind::Document,
+            Span::new(0, self.content.len()),
+            self.nodes().collect(),
+        )
+    }
+
+    /// the problems found while parsing the document
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.blocks.iter().flat_map(|b| b.diagnostics()).collect()
+    }
+
+    /// Replaces the content in `range` with `new_text` and re-parses
+    /// only the blocks affected by the change.
+    ///
+    /// Returns the spans -- in the _updated_ content -- of the blocks
+    /// which were re-parsed; the spans of all other blocks are moved
+    /// to account for the change in length.
+    ///
+    /// Panics if the range is out of bounds or doesn't lie on `char`
+    /// boundaries.
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) -> Vec<Span> {
+        let delta = new_text.len() as isize - range.len() as isize;
+        // where the edit ends in the updated content
+        let edit_end = range.start + new_text.len();
+        self.content.replace_range(range.clone(), new_text);
+
+        // the block which precedes the edited block could grow into the
+        // edited region (e.g., a list gaining an item) so parsing resumes
+        // from the block _before_ the one which was edited
+        let first = self
+            .blocks
+            .iter()
+            .rposition(|b| b.span.start < range.start)
+            .map(|idx| idx.saturating_sub(1))
+            .unwrap_or(0);
+        let mut pos = match self.blocks.get(first) {
+            Some(block) if block.span.start < range.start => block.span.start,
+            _ => skip_whitespace(&self.content, 0),
+        };
+
+        let mut parsed = vec![];
+        // the first of the prior blocks which can be reused
+        let mut reuse = self.blocks.len();
+
+        while pos < self.content.len() {
+            // a block starting at the same place -- after the edit --
+            // as a prior block will parse exactly as it did before
+            if pos >= edit_end {
+                let prior = (pos as isize - delta) as usize;
+                if let Ok(idx) = self.blocks[first..].binary_search_by_key(&prior, |b| b.span.start)
+                {
+                    if prior >= range.end {
+                        reuse = first + idx;
+                        break;
+                    }
+                }
+            }
+
+            let (block, next) = Block::parse(&self.content, pos);
+            parsed.push(block);
+            pos = next;
+        }
+
+        // an edit can change the ids of the headings which follow it
+        // (e.g., by adding a heading with the same title) but only when
+        // it changes the slugs of the headings it re-parsed
+        let replaced = &self.blocks[first..reuse];
+        let same_slugs = replaced
+            .iter()
+            .flat_map(|b| b.slugs.iter())
+            .eq(parsed.iter().flat_map(|b| b.slugs.iter()));
+        let changed_slugs: Vec<String> = match same_slugs {
+            true => vec![],
+            false => (replaced.iter().chain(parsed.iter()))
+                .flat_map(|b| b.slugs.iter().cloned())
+                .collect(),
+        };
+        if same_slugs {
+            let mut prior = replaced.iter().flat_map(|b| b.ids.iter());
+            for block in parsed.iter_mut() {
+                let ids = prior.by_ref().take(block.slugs.len()).cloned().collect();
+                block.assign_ids(ids);
+            }
+        }
+
+        let changed = parsed.iter().map(|b| b.span).collect();
+        // as the nodes are relative to their block, the blocks which
+        // follow only need their own span moved
+        if delta != 0 {
+            let map = |pos: usize| pos.checked_add_signed(delta).unwrap_or(0);
+            for block in self.blocks[reuse..].iter_mut() {
+                block.span = Span::new(map(block.span.start), map(block.span.end));
+            }
+        }
+        self.blocks.splice(first..reuse, parsed);
+        if !same_slugs {
+            // only the headings whose slugs can collide with the slugs
+            // which were re-parsed can have a different id
+            self.assign_ids(first, |slug| changed_slugs.iter().any(|c| related(c, slug)));
+        }
+
+        changed
+    }
+}
+
+/// whether the ids made from two slugs can collide, i.e., one slug is
+/// the other or the other followed by a `-` suffix (as in `foo-1`)
+fn related(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    long.strip_prefix(short)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const MD: &str = "# Foo\n\nsome text\n\n\n- bar\n- baz\n\n```rust\nlet x = 1;\n```\n\n## Qux\n";
+
+    #[test]
+    fn only_the_edited_block_is_reparsed() {
+        let mut doc = Document::new(MD);
+        let changed = doc.edit(2..5, "Foobar");
+
+        assert_eq!(changed, vec![Span::new(0, 9)]);
+        assert_eq!(doc.tree(), Document::new(doc.content()).tree());
+        assert_eq!(doc.nodes().next().unwrap().text_content(), "Foobar");
+    }
+
+    #[test]
+    fn edits_between_blocks_reparse_the_prior_block() {
+        let mut doc = Document::new(MD);
+        // indenting the line after the list makes it part of
+        // the list's last item
+        let list = MD.find("- bar").unwrap();
+        let start = MD.find("- baz\n").unwrap() + 6;
+        let changed = doc.edit(start..start, "  more");
+
+        // the list (rather than only the edited line) is reparsed
+        assert!(changed.iter().any(|s| s.start == list));
+        assert_eq!(doc.tree(), Document::new(doc.content()).tree());
+    }
+
+    #[test]
+    fn diagnostics_move_with_their_blocks() {
+        let mut doc = Document::new("# Foo\n\n```stray\n");
+        doc.edit(0..0, "## Intro\n\n");
+
+        assert_eq!(doc.diagnostics().len(), 1);
+        assert_eq!(doc.diagnostics()[0].span, Span::new(17, 25));
+    }
+
+    #[test]
+    fn heading_ids_follow_edits() {
+        let mut doc = Document::new("# Foo\n\n# Bar\n");
+        doc.edit(0..0, "# Bar\n\n");
+
+        let kinds: Vec<NodeKind> = doc.nodes().map(|n| n.kind).collect();
+        assert_eq!(
+            kinds[2],
+            NodeKind::Heading {
+                level: 1,
+                id: Some("bar-1".to_string())
+            }
+        );
+
+        // renaming the first heading frees its id for the one after it
+        doc.edit(2..5, "Baz");
+        let kinds: Vec<NodeKind> = doc.nodes().map(|n| n.kind).collect();
+        assert_eq!(
+            kinds[2],
+            NodeKind::Heading {
+                level: 1,
+                id: Some("bar".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn heading_ids_follow_edits_to_suffixed_slugs() {
+        let mut doc = Document::new("# Foo 1\n\n# Foo\n\n# Foo\n");
+        let ids = |doc: &Document| -> Vec<NodeKind> { doc.nodes().map(|n| n.kind).collect() };
+        assert_eq!(
+            ids(&doc)[2],
+            NodeKind::Heading {
+                level: 1,
+                id: Some("foo-2".to_string())
+            }
+        );
+
+        // `foo-1` is no longer taken by the first heading
+        doc.edit(2..7, "Bar");
+        assert_eq!(
+            ids(&doc)[2],
+            NodeKind::Heading {
+                level: 1,
+                id: Some("foo-1".to_string())
+            }
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn edits_match_a_fresh_parse(
+            edits in prop::collection::vec(
+                (any::<prop::sample::Index>(), 0..6usize, "[-# a-z1\n`]{0,6}"),
+                1..6
+            )
+        ) {
+            let mut doc = Document::new(MD);
+
+            for (at, len, text) in edits {
+                let start = at.index(doc.content().len() + 1);
+                let end = (start + len).min(doc.content().len());
+                doc.edit(start..end, &text);
+
+                let fresh = Document::new(doc.content());
+                prop_assert_eq!(&doc.tree(), &fresh.tree(), "content:\n{}", doc.content());
+                prop_assert_eq!(doc.diagnostics(), fresh.diagnostics());
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! The content as a stream of `parser::event` events which -- unlike
+//! `Parkdown::parse` -- is parsed a block at a time as the stream is
+//! read, so only the current block's pairs and nodes are ever held.
+//!
+//! ```
+//! use p_pest::events::Events;
+//! use parser::event::to_html;
+//!
+//! let html = to_html(Events::new("# Foo\n\nbar\n")).unwrap();
+//!
+//! assert_eq!(html, "<h1 id=\"foo\">Foo</h1>\n<p>bar</p>");
+//! ```
+
+use std::collections::VecDeque;
+
+use parser::{
+    ast::{Node, Span},
+    diagnostic::Diagnostic,
+    event::{events, Event, Tag},
+    slug::{assign_ids_with, Slugger},
+};
+
+use crate::{
+    ast::to_nodes,
+    parkdown::{next_region, skip_whitespace, Region},
+};
+
+/// The events of the content, parsed block by block; the blocks are
+/// within a `Document` just as they are in the tree. As the blocks
+/// after it haven't been read, a `Toc` rendered with
+/// `parser::event::push_html` only lists the headings before it.
+pub struct Events<'a> {
+    content: &'a str,
+    /// where the next block starts
+    pos: usize,
+    /// the events of the last block which haven't been read yet
+    pending: VecDeque<(Event, Span)>,
+    /// shared by every block so heading ids are unique in the document
+    slugger: Slugger,
+    started: bool,
+    ended: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            pos: skip_whitespace(content, 0),
+            pending: VecDeque::new(),
+            slugger: Slugger::default(),
+            started: false,
+            ended: false,
+            diagnostics: vec![],
+        }
+    }
+
+    /// the problems found in the blocks read so far; as with `parse` a
+    /// region which fails to parse is kept as literal text
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// parses the next block, queueing its events
+    fn next_block(&mut self) {
+        let (region, next) = next_region(self.content, self.pos);
+        let nodes = match region {
+            Region::Block(pairs, pos) => pairs.flat_map(|p| to_nodes(p, pos)).collect(),
+            Region::Failed(span, message) => {
+                self.diagnostics.push(Diagnostic::warning(&message, span));
+                vec![Node::text(&self.content[span.start..span.end], span)]
+            }
+        };
+
+        for mut node in nodes {
+            assign_ids_with(&mut node, &mut self.slugger);
+            self.pending.extend(events(&node));
+        }
+        self.pos = next;
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = (Event, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let document = Span::new(0, self.content.len());
+        if !self.started {
+            self.started = true;
+            return Some((Event::Start(Tag::Document), document));
+        }
+
+        while self.pending.is_empty() && self.pos < self.content.len() {
+            self.next_block();
+        }
+        match self.pending.pop_front() {
+            Some(event) => Some(event),
+            None if !self.ended => {
+                self.ended = true;
+                Some((Event::End(Tag::Document), document))
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{initialize, parkdown::Parkdown};
+    use parser::event::to_tree;
+
+    #[test]
+    fn events_describe_the_same_tree_as_parsing() {
+        initialize();
+        let md = "# Foo\n\n- a\n- b\n\n> quote\n\n```rs\nlet x = 1;\n```\n\n# Foo\n";
+        let p = Parkdown::new(md);
+
+        assert_eq!(&to_tree(Events::new(md)).unwrap(), p.parse().tree());
+    }
+
+    #[test]
+    fn regions_which_fail_to_parse_are_text() {
+        initialize();
+        let md = "# Foobar\n\n```stray\n\n## Baz\n";
+        let mut events = Events::new(md);
+        let tree = to_tree(events.by_ref()).unwrap();
+
+        assert_eq!(&tree, Parkdown::new(md).parse().tree());
+        assert_eq!(events.diagnostics().len(), 1);
+    }
+}
@@ -0,0 +1,408 @@
+//! A stable, serializable form of the tree of `Pairs` produced by the
+//! grammar so tools which aren't written in Rust can consume the parse.
+//!
+//! The tree is exported as JSON (or -- with the `msgpack` feature --
+//! MessagePack) with each node's rule, byte span, line/column
+//! positions and children. The shape of the export is described by
+//! the JSON Schema in `schema/tree.schema.json` which `schema()`
+//! generates; the `version` changes whenever the shape does.
+//!
+//! ```
+//! use p_pest::{export::Tree, Markdown, Rule};
+//! use pest::Parser;
+//!
+//! let content = "# Foo\n";
+//! let pairs = Markdown::parse(Rule::heading, content).unwrap();
+//! let tree = Tree::from_pairs(pairs);
+//!
+//! assert_eq!(tree.nodes[0].kind, "heading");
+//! assert_eq!(tree.nodes[0].end.line, 2);
+//! ```
+
+use color_eyre::Result;
+use parser::{ast::Span, graph::GraphNode};
+use pest::{
+    iterators::{Pair, Pairs},
+    Parser,
+};
+use schemars::{schema::RootSchema, schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parkdown::{next_region, skip_whitespace, Region},
+    Markdown, Rule,
+};
+
+/// the version of the export's shape
+pub const VERSION: u32 = 1;
+
+/// the kind of the node for a region which failed to parse
+pub const FAILED: &str = "failed";
+
+/// A position in the source; both the line and column start at 1 and
+/// the column counts characters rather than bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A pair in the tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TreeNode {
+    /// the name of the rule which matched (e.g., `heading`)
+    pub kind: String,
+    /// the byte offsets of the match
+    #[schemars(with = "SpanSchema")]
+    pub span: Span,
+    pub start: Position,
+    pub end: Position,
+    /// the matched text; only leaves (i.e., nodes without children)
+    /// include it as it's otherwise the text of their children
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+// `Span` is defined in the `parser` crate which doesn't derive
+// `JsonSchema` so its schema is described here
+
+/// The byte offsets of a match in the source.
+#[derive(JsonSchema)]
+#[schemars(rename = "Span")]
+#[allow(dead_code)]
+struct SpanSchema {
+    start: usize,
+    end: usize,
+}
+
+/// The exported tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Tree {
+    /// the version of the export's shape (i.e., `VERSION`)
+    pub version: u32,
+    /// the top-level pairs
+    pub nodes: Vec<TreeNode>,
+}
+
+impl From<Pair<'_, Rule>> for TreeNode {
+    fn from(pair: Pair<'_, Rule>) -> Self {
+        let span = pair.as_span();
+        let position = |pos: pest::Position| {
+            let (line, column) = pos.line_col();
+            Position { line, column }
+        };
+        let children: Vec<TreeNode> = pair.clone().into_inner().map(TreeNode::from).collect();
+
+        Self {
+            kind: format!("{:?}", pair.as_rule()),
+            span: Span::new(span.start(), span.end()),
+            start: position(span.start_pos()),
+            end: position(span.end_pos()),
+            text: match children.is_empty() {
+                true => Some(span.as_str().to_string()),
+                false => None,
+            },
+            children,
+        }
+    }
+}
+
+/// the position of the byte offset within the content
+fn position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    Position {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+impl TreeNode {
+    /// moves the node -- which was parsed from the content at `offset`
+    /// -- and its descendants to where they are in the content
+    fn moved(mut self, content: &str, offset: usize) -> Self {
+        self.span = Span::new(self.span.start + offset, self.span.end + offset);
+        self.start = position(content, self.span.start);
+        self.end = position(content, self.span.end);
+        self.children = self
+            .children
+            .into_iter()
+            .map(|child| child.moved(content, offset))
+            .collect();
+
+        self
+    }
+
+    /// a `FAILED` node for the region of the content which didn't parse
+    pub(crate) fn failed(content: &str, span: Span) -> Self {
+        Self {
+            kind: FAILED.to_string(),
+            span,
+            start: position(content, span.start),
+            end: position(content, span.end),
+            text: Some(content[span.start..span.end].to_string()),
+            children: vec![],
+        }
+    }
+
+    /// The node and its descendants as an indented outline; a line per
+    /// node with its rule, span and (for leaves) text.
+    pub fn outline(&self) -> String {
+        fn lines(node: &TreeNode, depth: usize, out: &mut String) {
+            out.push_str(&format!(
+                "{}{} {}..{}",
+                "  ".repeat(depth),
+                node.kind,
+                node.span.start,
+                node.span.end
+            ));
+            if let Some(text) = &node.text {
+                out.push_str(&format!(" {:?}", text));
+            }
+            out.push('\n');
+            for child in &node.children {
+                lines(child, depth + 1, out);
+            }
+        }
+        let mut outline = String::new();
+        lines(self, 0, &mut outline);
+
+        outline
+    }
+}
+
+impl Tree {
+    pub fn from_pairs(pairs: Pairs<'_, Rule>) -> Self {
+        Self {
+            version: VERSION,
+            nodes: pairs.map(TreeNode::from).collect(),
+        }
+    }
+
+    /// The tree of a `file`; when the content doesn't parse as a whole
+    /// it's parsed a block at a time (as `Parkdown::parse` does) and each
+    /// region which fails is a `FAILED` node holding its text.
+    pub fn from_content(content: &str) -> Self {
+        if let Ok(pairs) = Markdown::parse(Rule::file, content) {
+            return Tree::from_pairs(pairs);
+        }
+
+        let mut nodes = vec![];
+        let mut pos = skip_whitespace(content, 0);
+        while pos < content.len() {
+            let (region, next) = next_region(content, pos);
+            match region {
+                Region::Block(pairs, start) => {
+                    nodes.extend(pairs.map(|p| TreeNode::from(p).moved(content, start)))
+                }
+                Region::Failed(span, _) => nodes.push(TreeNode::failed(content, span)),
+            }
+            pos = next;
+        }
+        let span = Span::new(0, content.len());
+        let file = TreeNode {
+            kind: format!("{:?}", Rule::file),
+            span,
+            start: position(content, span.start),
+            end: position(content, span.end),
+            text: None,
+            children: nodes,
+        };
+
+        Self {
+            version: VERSION,
+            nodes: vec![file],
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn to_json_pretty(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(self)?)
+    }
+
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+/// The pair (and its child-rules) as a `GraphNode` so that it can be
+/// rendered with `parser::graph::render`; leaves show their text.
+pub fn graph(pair: Pair<'_, Rule>) -> GraphNode {
+    let span = pair.as_span();
+    let children: Vec<GraphNode> = pair.clone().into_inner().map(graph).collect();
+    let text = match children.is_empty() {
+        true => Some(span.as_str()),
+        false => None,
+    };
+
+    GraphNode::new(
+        &format!("{:?}", pair.as_rule()),
+        text,
+        Span::new(span.start(), span.end()),
+        children,
+    )
+}
+
+/// The exported node (e.g., of `Tree::from_content`) as a `GraphNode`;
+/// as with `graph`, leaves show their text.
+impl From<&TreeNode> for GraphNode {
+    fn from(node: &TreeNode) -> Self {
+        GraphNode::new(
+            &node.kind,
+            node.text.as_deref(),
+            node.span,
+            node.children.iter().map(GraphNode::from).collect(),
+        )
+    }
+}
+
+/// the JSON Schema of the export
+pub fn schema() -> RootSchema {
+    schema_for!(Tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::graph::{render, GraphFormat, GraphOptions};
+
+    fn tree(rule: Rule, content: &str) -> Tree {
+        Tree::from_pairs(Markdown::parse(rule, content).unwrap())
+    }
+
+    #[test]
+    fn pairs_export_with_positions() {
+        let tree = tree(Rule::file, "# Foo\n\nbär [x](/y)\n");
+        let json: serde_json::Value = serde_json::from_str(&tree.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["version"], VERSION);
+        let paragraph = &json["nodes"][0]["children"][1]["children"][0]["children"][0];
+        assert_eq!(paragraph["kind"], "paragraph");
+        assert_eq!(paragraph["text"], "bär [x](/y)");
+        assert_eq!(
+            paragraph["span"],
+            serde_json::json!({ "start": 7, "end": 19 })
+        );
+        assert_eq!(
+            paragraph["start"],
+            serde_json::json!({ "line": 3, "column": 1 })
+        );
+        assert_eq!(
+            paragraph["end"],
+            serde_json::json!({ "line": 3, "column": 12 })
+        );
+        assert!(paragraph.get("children").is_none());
+    }
+
+    #[test]
+    fn content_which_fails_to_parse_exports_its_blocks() {
+        crate::initialize();
+        let content = "# Foo\n\n```stray\n\nbär\n";
+        let exported = Tree::from_content(content);
+        let file = &exported.nodes[0];
+
+        let kinds: Vec<&str> = file.children.iter().map(|n| n.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["block", FAILED, "block"]);
+        assert_eq!(file.children[1].text.as_deref(), Some("```stray"));
+        // the position's column counts characters rather than bytes
+        assert_eq!(file.children[2].span, Span::new(17, 21));
+        assert_eq!(file.children[2].start, Position { line: 5, column: 1 });
+        assert_eq!(file.children[2].end, Position { line: 5, column: 4 });
+
+        let content = "# Foo\n";
+        assert_eq!(Tree::from_content(content), tree(Rule::file, content));
+    }
+
+    #[test]
+    fn rule_chains_export() {
+        let chain = crate::RuleChain::parse(Rule::heading, "## Foo\n").unwrap();
+        let tree = chain.export();
+
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.nodes[0].children[0].kind, "h2");
+    }
+
+    #[test]
+    fn exports_round_trip() {
+        let tree = tree(Rule::file, "- a\n- b\n\n```rust\nlet x = 1;\n```\n");
+
+        assert_eq!(
+            Tree::from_json(&tree.to_json_pretty().unwrap()).unwrap(),
+            tree
+        );
+        #[cfg(feature = "msgpack")]
+        assert_eq!(
+            Tree::from_msgpack(&tree.to_msgpack().unwrap()).unwrap(),
+            tree
+        );
+    }
+
+    #[test]
+    fn rule_chains_render_as_graphs() {
+        let chain = crate::RuleChain::parse(Rule::file, "# Foo\n").unwrap();
+        let options = GraphOptions {
+            format: GraphFormat::MermaidMindmap,
+            collapse_silent: true,
+            collapse_leaves: true,
+            max_text: None,
+        };
+
+        assert_eq!(
+            chain.to_graph(&options),
+            concat!(
+                "mindmap\n",
+                "  n0[\"file\"]\n",
+                "    n1[\"block > leaf_block > heading > h1: Foo\"]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn content_which_fails_to_parse_renders_as_a_graph() {
+        crate::initialize();
+        let exported = Tree::from_content("# Foo\n\n```stray\n");
+        let options = GraphOptions {
+            format: GraphFormat::MermaidMindmap,
+            collapse_silent: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&GraphNode::from(&exported.nodes[0]), &options),
+            concat!(
+                "mindmap\n",
+                "  n0[\"file\"]\n",
+                "    n1[\"block > leaf_block > heading > h1\"]\n",
+                "      n2[\"title: Foo\"]\n",
+                "    n3[\"failed: ```stray\"]\n",
+            )
+        );
+    }
+
+    #[test]
+    fn the_schema_is_up_to_date() {
+        let schema = serde_json::to_string_pretty(&schema()).unwrap();
+
+        assert_eq!(
+            include_str!("../schema/tree.schema.json").trim_end(),
+            schema,
+            "run `cargo run --example schema > schema/tree.schema.json`"
+        );
+    }
+}
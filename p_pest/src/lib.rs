@@ -17,7 +17,18 @@ pub fn initialize() {
 #[grammar = "markdown.pest"]
 pub struct Markdown;
 
-use color_eyre::{eyre::eyre, eyre::Error as ColorError, eyre::Report, Result, Section};
+pub mod ast;
+pub mod cst;
+pub mod document;
+pub mod events;
+pub mod export;
+pub mod parkdown;
+pub mod rules;
+pub mod select;
+pub mod snapshot;
+pub mod typed;
+
+use color_eyre::{eyre::eyre, eyre::Report, Result, Section};
 use pest::iterators::{Pair, Pairs};
 
 /// **parse_rule**
@@ -25,8 +36,8 @@ use pest::iterators::{Pair, Pairs};
 /// Parses markdown content using a specified rule defined Markdown
 /// struct/parser
 #[instrument]
-pub fn parse_rule(rule: Rule, content: &str) -> Result<Pairs<Rule>, Report> {
-    let res = Markdown::parse(rule, &content);
+pub fn parse_rule(rule: Rule, content: &str) -> Result<Pairs<'_, Rule>, Report> {
+    let res = Markdown::parse(rule, content);
 
     match res {
         Ok(pairs) => Ok(pairs),
@@ -37,9 +48,10 @@ pub fn parse_rule(rule: Rule, content: &str) -> Result<Pairs<Rule>, Report> {
     }
 }
 
+#[allow(clippy::assertions_on_constants)]
 #[instrument]
-pub fn test_parse(rule: Rule, content: &str) -> Option<Pairs<Rule>> {
-    let res = Markdown::parse(rule, &content);
+pub fn test_parse(rule: Rule, content: &str) -> Option<Pairs<'_, Rule>> {
+    let res = Markdown::parse(rule, content);
 
     match res {
         Ok(pairs) => Some(pairs),
@@ -57,12 +69,12 @@ pub fn test_parse(rule: Rule, content: &str) -> Option<Pairs<Rule>> {
 /// Parses the passed in _content_ with the given _rule_ or **panics** if it
 /// can't parse.
 #[instrument]
-pub fn parse_or_panic(rule: Rule, content: &str) -> Pairs<Rule> {
+pub fn parse_or_panic(rule: Rule, content: &str) -> Pairs<'_, Rule> {
     parse_rule(rule, content).unwrap_or_else(|err| panic!("{:?}\n", err))
 }
 
 fn pad(level: usize, content: String) -> String {
-    let mut padding = format!("{}", " ".repeat(level * 2));
+    let mut padding = " ".repeat(level * 2);
     padding.push_str(&content);
 
     padding
@@ -149,12 +161,12 @@ impl<'a> TryFrom<Pairs<'a, Rule>> for RuleChain<'a> {
     type Error = Report;
 
     #[instrument]
-    fn try_from(value: Pairs<'a, Rule>) -> Result<RuleChain<'a>, Self::Error> {
-        let first: Option<Pair<'a, Rule>> = value.clone().next();
+    fn try_from(mut value: Pairs<'a, Rule>) -> Result<RuleChain<'a>, Self::Error> {
+        let first: Option<Pair<'a, Rule>> = value.next();
 
         if let Some(first) = first {
             let second = value.next();
-            if let Some(second) = second {
+            if second.is_some() {
                 Err(eyre!("The Pairs<Rule> structure passed in has more than one root rule which is not allowed for in a RuleChain!"))
             } else {
                 let chain: RuleChain<'a> = RuleChain::from(first);
@@ -176,7 +188,7 @@ impl<'a> RuleChain<'a> {
     }
 
     pub fn parse(rule: Rule, content: &'a str) -> Result<RuleChain<'a>> {
-        let res = Markdown::parse(rule, &content);
+        let res = Markdown::parse(rule, content);
 
         match res {
             Ok(pairs) => {
@@ -193,6 +205,28 @@ impl<'a> RuleChain<'a> {
         }
     }
 
+    /// This rule converted to one of the structs in `typed` (e.g.,
+    /// `typed::Heading`); unlike `get_rule_text` the struct's fields
+    /// are checked against the grammar at compile time.
+    pub fn typed<T: typed::FromPair>(&self) -> Result<T> {
+        T::from_pair(self.pair.clone())
+    }
+
+    /// This rule (and all of its child-rules) in the stable format
+    /// of `export::Tree`, ready to serialize as JSON.
+    pub fn export(&self) -> export::Tree {
+        export::Tree {
+            version: export::VERSION,
+            nodes: vec![self.pair.clone().into()],
+        }
+    }
+
+    /// Renders this rule (and all of its child-rules) as Graphviz DOT
+    /// or Mermaid text; a visual alternative to `describe`.
+    pub fn to_graph(&self, options: &parser::graph::GraphOptions) -> String {
+        parser::graph::render(&export::graph(self.pair.clone()), options)
+    }
+
     /// the _name_ of this rule
     #[instrument]
     pub fn name(&self) -> String {
@@ -237,7 +271,7 @@ impl<'a> RuleChain<'a> {
 
         if let Some(rules) = rules {
             let rules: Vec<String> = rules
-                .into_iter()
+                .iter()
                 .map(|r: &RuleChain| r.get_text())
                 .collect();
 
@@ -252,7 +286,7 @@ impl<'a> RuleChain<'a> {
     #[instrument]
     pub fn has_children(&self) -> bool {
         let arr: Vec<Pair<Rule>> = self.pair.clone().into_inner().collect();
-        arr.len() > 0
+        !arr.is_empty()
     }
 
     /// makes sure the mapping property is available
@@ -291,6 +325,7 @@ impl<'a> RuleChain<'a> {
         };
     }
 
+    #[allow(dead_code)]
     #[instrument]
     fn push_rule(&'a mut self, name: &str, rule: RuleChain<'a>) {
         let name = name.to_string();
@@ -308,7 +343,7 @@ impl<'a> RuleChain<'a> {
         kv.push(rule);
     }
 
-    pub fn get_rule_name(&'a self, rule_name: &str) -> Option<&Vec<RuleChain<'a>>> {
+    pub fn get_rule_name(&'a self, rule_name: &str) -> Option<&'a Vec<RuleChain<'a>>> {
         match self.mapping.get(rule_name) {
             Some(rules) => Some(rules),
             None => None,
@@ -327,7 +362,7 @@ impl<'a> RuleChain<'a> {
             Some(rules) => match rules.first() {
                 Some(rule) => {
                     let rule: RuleChain<'a> = RuleChain::new(rule.pair.clone());
-                    return Some(rule);
+                    Some(rule)
                 }
                 None => None,
             },
@@ -373,7 +408,7 @@ impl<'a> RuleChain<'a> {
             "\n[{} is \"{}\"], composed of [\n{}", //
             self.name(),
             self.get_text(),
-            self.describe_at_level(&children, 1 as usize)
+            self.describe_at_level(&children, 1_usize)
         );
 
         format!("{}", root_node)
@@ -392,7 +427,7 @@ impl<'a> RuleChain<'a> {
             };
             let mut inner: Pairs<Rule> = pair.into_inner();
             let children: &Vec<Pair<Rule>> = &inner.clone().collect();
-            let has_children = children.len() != 0;
+            let has_children = !children.is_empty();
             let is_orphan = children.len() == 1;
 
             // not a root level node
@@ -446,9 +481,47 @@ impl<'a> RuleChain<'a> {
         self.get_rule_name(rule).is_some()
     }
 
+    /// the number of times the given rule was found anywhere
+    /// within this rule's rule chain (including the rule itself);
+    /// it's an error if the grammar has no such rule
     #[instrument]
-    pub fn how_many(&'a mut self, rule: &str) -> usize {
-        todo!()
+    pub fn how_many(&self, rule: &str) -> Result<usize> {
+        let rule: Rule = rule.parse()?;
+        self.count(rules::name(rule))
+    }
+
+    /// The rules anywhere within this rule's rule chain (including the
+    /// rule itself) which match a CSS-like selector, in document order.
+    ///
+    /// ```
+    /// use p_pest::{RuleChain, Rule};
+    ///
+    /// let chain = RuleChain::parse(Rule::file, "# Foo\n\n```ts\nlet x = 1;\n```\n").unwrap();
+    /// let code = chain.select("fenced_code_block[lang=ts]").unwrap();
+    ///
+    /// assert_eq!(code.len(), 1);
+    /// assert_eq!(chain.count("block > leaf_block heading h1").unwrap(), 1);
+    /// ```
+    ///
+    /// See `select` for the selector syntax.
+    pub fn select(&self, selector: &str) -> Result<Vec<RuleChain<'a>>> {
+        let selector: select::Selector = selector.parse()?;
+
+        Ok(selector
+            .select(self.pair.clone())
+            .into_iter()
+            .map(RuleChain::new)
+            .collect())
+    }
+
+    /// the first rule which matches the selector
+    pub fn select_first(&self, selector: &str) -> Result<Option<RuleChain<'a>>> {
+        Ok(self.select(selector)?.into_iter().next())
+    }
+
+    /// the number of rules which match the selector
+    pub fn count(&self, selector: &str) -> Result<usize> {
+        Ok(self.select(selector)?.len())
     }
 
     /// clones the node and returns the child nodes as owned
@@ -469,6 +542,16 @@ impl<'a> Display for RuleChain<'a> {
 mod tests {
     use super::*;
 
+    /// the text of each rule which matches the selector
+    fn texts(chain: &RuleChain, selector: &str) -> Vec<String> {
+        chain
+            .select(selector)
+            .unwrap()
+            .iter()
+            .map(|r| r.get_text())
+            .collect()
+    }
+
     #[test]
     fn is_attrs() {
         initialize();
@@ -477,35 +560,36 @@ mod tests {
             "   class=\"foo bar baz\" data-flag=\"false\"  ",
         );
 
-        assert!(matches!(p, Ok(_)))
+        assert!(p.is_ok())
     }
 
     #[test]
     fn self_closing_tag() {
         initialize();
-        let p = RuleChain::parse(
+        let tag = RuleChain::parse(
             Rule::tag, //
             "<test class=\"foo bar\" style=\"color: red\" />",
-        );
+        )
+        .unwrap();
 
-        if let Ok(tag) = p {
-            println!("{}", tag.describe());
-        }
+        assert_eq!(tag.how_many("self_closing_tag").unwrap(), 1);
+        assert_eq!(texts(&tag, "tagname"), vec!["test"]);
+        assert_eq!(texts(&tag, "attr_name"), vec!["class", "style"]);
+        assert_eq!(texts(&tag, "attr > value"), vec!["foo bar", "color: red"]);
     }
     #[test]
     fn block_tag() {
         initialize();
-        let p1 = RuleChain::parse(
+        let tag = RuleChain::parse(
             Rule::tag,
             r#"<foo-bar class="foo bar">hello world</foo-bar>"#,
-        );
+        )
+        .unwrap();
 
-        match p1 {
-            Ok(tag) => println!("{}", tag.describe()),
-            Err(err) => {
-                panic!("{}", err);
-            }
-        }
+        assert_eq!(tag.how_many("block_tag").unwrap(), 1);
+        assert_eq!(texts(&tag, "tagname"), vec!["foo-bar"]);
+        assert_eq!(texts(&tag, "attr > value"), vec!["foo bar"]);
+        assert_eq!(texts(&tag, "inner"), vec!["hello world"]);
 
         // let p2 = Markdown::parse(
         //     Rule::tag,
@@ -521,7 +605,7 @@ mod tests {
             "# Foobar\n",
         );
 
-        assert!(matches!(p, Ok(_)));
+        assert!(p.is_ok());
     }
     #[test]
     fn h6_with_two_space_indent() {
@@ -531,7 +615,7 @@ mod tests {
             "  ###### Foobar\n",
         );
 
-        assert!(matches!(p, Ok(_)));
+        assert!(p.is_ok());
     }
     #[test]
     fn h4_from_heading() {
@@ -539,7 +623,7 @@ mod tests {
             Rule::heading, //
             " #### Foobar\n",
         );
-        assert!(matches!(p, Ok(_)));
+        assert!(p.is_ok());
     }
 
     #[test]
@@ -548,13 +632,13 @@ mod tests {
         let mut dict = RuleChain::parse(
             Rule::fence_defn,
             r#"```ts { foo: "bar", bar: "baz" } bad-juju"#,
-        );
-        if let Ok(tag) = dict {
-            println!("{}", &tag.describe());
+        )
+        .unwrap();
 
-            let lang = tag.get_rule_text("lang");
-            assert!(matches!(lang.as_str(), "ts"));
-        }
+        assert_eq!(dict.get_rule_text("fence_lang"), "ts");
+        assert_eq!(texts(&dict, "prop_name"), vec!["foo", "bar"]);
+        assert_eq!(texts(&dict, "prop > value"), vec!["bar", "baz"]);
+        assert_eq!(texts(&dict, "fence_ignore"), vec!["bad-juju"]);
 
         // let csv = parse_rule(
         //     Rule::fence_defn,
@@ -563,6 +647,49 @@ mod tests {
         // assert!(matches!(csv, Ok(_)));
     }
 
+    #[test]
+    fn bullet_list() {
+        initialize();
+        let mut list = RuleChain::parse(
+            Rule::list, //
+            "- foo\n+ bar\n\n* baz\n  continued\n",
+        )
+        .unwrap();
+
+        assert_eq!(list.get_rules("list_item").map(|i| i.len()), Some(3));
+    }
+
+    #[test]
+    fn select_rules_from_the_whole_chain() {
+        initialize();
+        let md = "# Foo\n\n- a\n- b\n- c\n\n## Bar\n";
+        let chain = RuleChain::parse(Rule::file, md).unwrap();
+
+        assert_eq!(chain.how_many("list_item").unwrap(), 3);
+        assert_eq!(chain.how_many("h1").unwrap(), 1);
+        assert_eq!(chain.how_many("block_quote").unwrap(), 0);
+        assert!(chain.how_many("blockquote").is_err());
+        assert_eq!(
+            chain
+                .select_first("heading h2")
+                .unwrap()
+                .map(|h| h.get_text()),
+            Some("## Bar\n".to_string())
+        );
+        assert!(chain.select("heading >").is_err());
+    }
+
+    #[test]
+    fn image() {
+        initialize();
+        let p = RuleChain::parse(
+            Rule::image, //
+            "![a sunset](./sunset.png)",
+        );
+
+        assert!(p.is_ok());
+    }
+
     #[test]
     fn thematic_break() {
         initialize();
@@ -578,6 +705,102 @@ something else
             md,
         );
 
-        assert!(matches!(p, Ok(_)))
+        assert!(p.is_ok())
+    }
+
+    /// the length of the content matched by the rule
+    fn matched(rule: Rule, content: &str) -> Option<usize> {
+        Markdown::parse(rule, content)
+            .ok()
+            .map(|pairs| pairs.as_str().len())
+    }
+
+    #[test]
+    fn comment() {
+        initialize();
+        for md in ["<!---->", "<!-- a -- b -->", "<!-->", "<!--->"] {
+            assert_eq!(matched(Rule::comment, md), Some(md.len()), "{}", md);
+        }
+        assert_eq!(matched(Rule::comment, "<!-- a --> b -->"), Some(10));
+    }
+
+    #[test]
+    fn attribute_forms() {
+        initialize();
+        let md = "<input type='checkbox' checked value=on data-x = \"1\" />";
+        let tag = Markdown::parse(Rule::self_closing_tag, md).unwrap();
+        let attrs: Vec<&str> = tag
+            .flatten()
+            .filter(|p| p.as_rule() == Rule::attr)
+            .map(|p| p.as_str())
+            .collect();
+
+        assert_eq!(
+            attrs,
+            vec!["type='checkbox'", "checked", "value=on", "data-x = \"1\""]
+        );
+    }
+
+    #[test]
+    fn inline_raw_html() {
+        initialize();
+        for md in [
+            "<a href=\"x\">",
+            "</a >",
+            "<br/>",
+            "<?php echo 1; ?>",
+            "<!DOCTYPE html>",
+            "<![CDATA[ x < y ]]>",
+            "<!-- comment -->",
+        ] {
+            assert_eq!(matched(Rule::raw_html, md), Some(md.len()), "{}", md);
+        }
+        assert_eq!(matched(Rule::raw_html, "<a href=\"x\"y>"), None);
+    }
+
+    #[test]
+    fn html_block_start_conditions() {
+        initialize();
+        let blocks = [
+            // 1. raw text tags end at their end tag
+            "<pre class=\"x\">\n\n# not a heading\n</pre> trailing\n",
+            // 2. comments
+            "<!--\n\ncomment\n-->\n",
+            // 3. processing instructions
+            "<?xml\n\nversion=\"1.0\" ?>\n",
+            // 4. declarations
+            "<!DOCTYPE\n\nhtml>\n",
+            // 5. CDATA
+            "<![CDATA[\n\nx < y\n]]>\n",
+            // 6. known block tags end at an empty line
+            "<DIV\nclass=\"x\">\n<p>foo\n",
+            "</table>\n",
+            // 7. any other complete tag on a line of its own
+            "<my-tag a=b>\n*foo*\n",
+        ];
+        for md in blocks {
+            assert_eq!(matched(Rule::html_block, md), Some(md.len()), "{}", md);
+        }
+
+        assert_eq!(matched(Rule::html_block, "<div>\nfoo\n\nbar\n"), Some(10));
+        assert_eq!(matched(Rule::html_block, "<my-tag> foo\n"), None);
+        assert_eq!(matched(Rule::html_block, "<prefix>\n\n"), Some(9));
+    }
+
+    #[test]
+    fn character_references() {
+        initialize();
+        for md in [
+            "&amp;",
+            "&CounterClockwiseContourIntegral;",
+            "&#35;",
+            "&#X22;",
+            "&#xcab;",
+        ] {
+            assert_eq!(matched(Rule::char_ref, md), Some(md.len()), "{}", md);
+        }
+        for md in ["&#;", "&#x;", "&#87654321;", "&copy", "& amp;"] {
+            assert_eq!(matched(Rule::char_ref, md), None, "{}", md);
+        }
     }
 }
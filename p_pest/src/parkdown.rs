@@ -1,9 +1,38 @@
 pub struct Init;
 pub struct Parsed;
+/// parsed and then transformed by a `Pipeline`'s plugins
+pub struct Transformed;
 pub struct Html;
 
-use crate::{Markdown, Rule};
-use pest::iterators::{Pair, Pairs};
+/// The states in which the content has been parsed into a tree (which
+/// can be rendered).
+pub trait HasTree {}
+impl HasTree for Parsed {}
+impl HasTree for Transformed {}
+
+use crate::{
+    ast::{map_spans, to_nodes},
+    cst,
+    export::{Tree, TreeNode, VERSION},
+    Markdown, Rule,
+};
+use color_eyre::Result;
+use parser::{
+    ast::{Node, NodeKind, Span},
+    component::ComponentRegistry,
+    cst::CstNode,
+    diagnostic::Diagnostic,
+    event::{events, Events},
+    graph::{render, GraphNode, GraphOptions},
+    html::{to_html, to_html_with, HtmlOptions},
+    lint::Linter,
+    markdown::{to_markdown, MarkdownOptions},
+    mdast::{to_mdast, MdastNode},
+    pipeline::Pipeline,
+    sanitize::{sanitize, SanitizePolicy},
+    slug::assign_ids,
+};
+use pest::{iterators::Pairs, Parser};
 
 use std::{
     borrow::Cow, //
@@ -20,14 +49,190 @@ pub struct Parkdown<'a, TState = Init> {
     file: Option<String>,
     /// rule used to parse
     pub rule: Rule,
-    /// "pairs" produced by the rule
+    /// "pairs" produced by the rule; only available when
+    /// the _whole_ content parsed without error
     pairs: Option<Pairs<'a, Rule>>,
+    /// the tree produced by parsing
+    tree: Option<Node>,
+    /// problems found while parsing
+    diagnostics: Vec<Diagnostic>,
 
     state: PhantomData<TState>,
 }
 
+/// the characters which the `file` rule silently skips
+/// between blocks
+pub(crate) fn skip_whitespace(content: &str, pos: usize) -> usize {
+    match content[pos..].find(|c: char| !matches!(c, ' ' | '\t' | '\r' | '\n')) {
+        Some(idx) => pos + idx,
+        None => content.len(),
+    }
+}
+
+/// the end of the region starting at `pos` which is terminated
+/// by an empty line (or the end of the content)
+fn region_end(content: &str, pos: usize) -> usize {
+    let mut end = pos;
+
+    for line in content[pos..].split_inclusive('\n') {
+        if end > pos && line.trim().is_empty() {
+            break;
+        }
+        end += line.len();
+    }
+
+    end
+}
+
+/// wraps the nodes in a `Document` unless there's
+/// already a single root node
+fn to_root(mut nodes: Vec<Node>, len: usize) -> Node {
+    if nodes.len() == 1 {
+        nodes.remove(0)
+    } else {
+        Node::with_children(NodeKind::Document, Span::new(0, len), nodes)
+    }
+}
+
+fn pest_message(err: &pest::error::Error<Rule>) -> String {
+    format!(
+        "unable to parse content, it has been kept as literal text ({})",
+        err.variant.message()
+    )
+}
+
+/// A region of the content found while parsing it block by block.
+pub(crate) enum Region<'a> {
+    /// a block which parsed; the position is where the block
+    /// starts in the content
+    Block(Pairs<'a, Rule>, usize),
+    /// a region which failed to parse along with the reason
+    Failed(Span, String),
+}
+
+/// Parses the region of the content which starts at `pos` as a single
+/// block; a region which fails to parse runs until the next empty line
+/// so that parsing can resume after it. Returns the region along with
+/// the position where the next region can start.
+pub(crate) fn next_region(content: &str, pos: usize) -> (Region<'_>, usize) {
+    let parsed = Markdown::parse(Rule::block, &content[pos..]);
+
+    match parsed {
+        Ok(pairs) if !pairs.as_str().is_empty() => {
+            let consumed = pairs.as_str().len();
+            (
+                Region::Block(pairs, pos),
+                skip_whitespace(content, pos + consumed),
+            )
+        }
+        _ => {
+            let end = region_end(content, pos);
+            let text = content[pos..end].trim_end();
+            let message = match parsed {
+                Err(err) => pest_message(&err),
+                Ok(_) => "unable to parse content, it has been kept as literal text".to_string(),
+            };
+            let span = Span::new(pos, pos + text.len());
+
+            (Region::Failed(span, message), skip_whitespace(content, end))
+        }
+    }
+}
+
+/// Breaks the content up into the regions found by parsing it one
+/// block at a time.
+pub(crate) fn regions(content: &str) -> Vec<Region<'_>> {
+    let mut regions = vec![];
+    let mut pos = skip_whitespace(content, 0);
+
+    while pos < content.len() {
+        let (region, next) = next_region(content, pos);
+        regions.push(region);
+        pos = next;
+    }
+
+    regions
+}
+
+/// Parses the content as a sequence of blocks. When the content can't
+/// be parsed as a whole it is parsed block by block so that a region
+/// which fails to parse can be converted to literal text without
+/// impacting the rest of the document.
+pub(crate) fn parse_blocks(content: &str) -> (Vec<Node>, Vec<Diagnostic>) {
+    if let Ok(pairs) = Markdown::parse(Rule::file, content) {
+        let nodes = pairs
+            .flat_map(|p| to_nodes(p, 0))
+            .flat_map(|doc| doc.children)
+            .collect();
+        return (nodes, vec![]);
+    }
+
+    let mut children = vec![];
+    let mut diagnostics = vec![];
+
+    for region in regions(content) {
+        match region {
+            Region::Block(pairs, pos) => {
+                for pair in pairs {
+                    children.extend(to_nodes(pair, pos));
+                }
+            }
+            Region::Failed(span, message) => {
+                diagnostics.push(Diagnostic::warning(&message, span));
+                children.push(Node::text(&content[span.start..span.end], span));
+            }
+        }
+    }
+
+    (children, diagnostics)
+}
+
+/// Parses the raw inner content of every tag which is registered as a
+/// component as Markdown blocks so the component receives a tree
+/// rather than raw HTML; components nested within are parsed too.
+fn parse_components(node: &mut Node, components: &ComponentRegistry) {
+    let registered = matches!(&node.kind, NodeKind::Tag { name, .. } if components.contains(name));
+    if let (true, [inner]) = (registered, node.children.as_slice()) {
+        if let NodeKind::Html(text) | NodeKind::Text(text) = &inner.kind {
+            let start = inner.span.start;
+            let (mut nodes, _) = parse_blocks(text);
+            for node in nodes.iter_mut() {
+                map_spans(node, &|pos| pos + start);
+            }
+            node.children = nodes;
+        }
+    }
+
+    for child in node.children.iter_mut() {
+        parse_components(child, components);
+    }
+}
+
+/// Produces a tree for content which failed to parse with `rule`.
+fn recover(rule: Rule, content: &str) -> (Node, Vec<Diagnostic>) {
+    let span = Span::new(0, content.len());
+
+    if rule == Rule::file {
+        let (children, diagnostics) = parse_blocks(content);
+        return (
+            Node::with_children(NodeKind::Document, span, children),
+            diagnostics,
+        );
+    }
+
+    let mut diagnostics = vec![];
+    if let Err(err) = Markdown::parse(rule, content) {
+        diagnostics.push(Diagnostic::warning(&pest_message(&err), span));
+    }
+
+    (
+        to_root(vec![Node::text(content, span)], content.len()),
+        diagnostics,
+    )
+}
+
 // initializer implementation
-impl<'a, Init> Parkdown<'a, Init> {
+impl<'a> Parkdown<'a, Init> {
     /// create a new Parkdown parser with a reference
     /// to the underlying raw markdown content.
     pub fn new(content: &'a str) -> Parkdown<'a, Init> {
@@ -36,6 +241,8 @@ impl<'a, Init> Parkdown<'a, Init> {
             rule: Rule::file,
             file: None,
             pairs: None,
+            tree: None,
+            diagnostics: vec![],
             state: PhantomData::<Init>,
         }
     }
@@ -45,6 +252,8 @@ impl<'a, Init> Parkdown<'a, Init> {
             rule: Rule::file,
             file: None,
             pairs: None,
+            tree: None,
+            diagnostics: vec![],
             state: PhantomData::<Init>,
         }
     }
@@ -57,6 +266,8 @@ impl<'a, Init> Parkdown<'a, Init> {
             rule,
             file: None,
             pairs: None,
+            tree: None,
+            diagnostics: vec![],
             state: PhantomData::<Init>,
         }
     }
@@ -70,29 +281,755 @@ impl<'a, Init> Parkdown<'a, Init> {
         Ok(p)
     }
 
-    pub fn parse<'b>(&'b self) -> Result<Parkdown<'b, Parsed>> {
-        let pairs: Pairs<'b, Rule> = Markdown::parse(
-            self.rule, //
-            &self.content,
-        )?;
+    fn parsed<'b>(
+        &'b self,
+        pairs: Option<Pairs<'b, Rule>>,
+        mut tree: Node,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Parkdown<'b, Parsed> {
+        assign_ids(&mut tree);
 
-        let p: Parkdown<'b, Parsed> = Parkdown {
+        Parkdown {
             state: PhantomData::<Parsed>,
-            pairs: Some(pairs),
+            pairs,
+            tree: Some(tree),
+            diagnostics,
             content: Cow::Borrowed(&self.content),
             rule: self.rule,
             file: self.file.clone(),
-        };
+        }
+    }
 
-        Ok(p)
+    /// Parses the content and _always_ produces a tree.
+    ///
+    /// Any region of the content which can't be parsed is kept in
+    /// the tree as literal text and a _warning_ is added to the
+    /// diagnostics explaining why.
+    pub fn parse<'b>(&'b self) -> Parkdown<'b, Parsed> {
+        match Markdown::parse(self.rule, &self.content) {
+            Ok(pairs) => {
+                let nodes = pairs.clone().flat_map(|p| to_nodes(p, 0)).collect();
+                let tree = to_root(nodes, self.content.len());
+
+                self.parsed(Some(pairs), tree, vec![])
+            }
+            Err(_) => {
+                let (tree, diagnostics) = recover(self.rule, &self.content);
+
+                self.parsed(None, tree, diagnostics)
+            }
+        }
+    }
+
+    /// Parses the content but -- unlike `parse` -- fails if _any_
+    /// part of the content can't be parsed.
+    pub fn try_parse<'b>(&'b self) -> Result<Parkdown<'b, Parsed>> {
+        let pairs: Pairs<'b, Rule> = Markdown::parse(
+            self.rule, //
+            &self.content,
+        )?;
+        let nodes = pairs.clone().flat_map(|p| to_nodes(p, 0)).collect();
+        let tree = to_root(nodes, self.content.len());
+
+        Ok(self.parsed(Some(pairs), tree, vec![]))
     }
 }
 
-impl<'a, Parsed> Parkdown<'a, Parsed> {
+impl<'a> Parkdown<'a, Parsed> {
+    /// runs the pipeline's plugins over the tree; everything rendered
+    /// from the `Transformed` parser (e.g., `to_html`) reflects their
+    /// changes
+    pub fn transform(mut self, pipeline: &mut Pipeline) -> Result<Parkdown<'a, Transformed>> {
+        match &mut self.tree {
+            Some(tree) => pipeline.run(tree)?,
+            None => panic!("transform() called in invalid state!"),
+        }
+
+        Ok(Parkdown {
+            state: PhantomData::<Transformed>,
+            pairs: self.pairs,
+            tree: self.tree,
+            diagnostics: self.diagnostics,
+            content: self.content,
+            rule: self.rule,
+            file: self.file,
+        })
+    }
+}
+
+impl<'a, TState: HasTree> Parkdown<'a, TState> {
     pub fn pairs(&self) -> &Pairs<'a, Rule> {
         match &self.pairs {
             Some(pairs) => pairs,
             None => panic!("pairs() called in invalid state!"),
         }
     }
+
+    /// the tree produced by parsing the content
+    pub fn tree(&self) -> &Node {
+        match &self.tree {
+            Some(tree) => tree,
+            None => panic!("tree() called in invalid state!"),
+        }
+    }
+
+    /// the tree as a stream of events; see `crate::events::Events` to
+    /// stream the content without parsing it as a whole; rendered with
+    /// `parser::event::push_html`, a `Toc` only lists the headings which
+    /// come before it
+    pub fn events(&self) -> Events<'_> {
+        events(self.tree())
+    }
+
+    /// the pairs in the stable format of `export::Tree`; as with
+    /// `parse`, a region which fails to parse is kept (as a `failed`
+    /// node holding its text)
+    pub fn export(&self) -> Tree {
+        match &self.pairs {
+            Some(pairs) => Tree::from_pairs(pairs.clone()),
+            None if self.rule == Rule::file => Tree::from_content(&self.content),
+            None => Tree {
+                version: VERSION,
+                nodes: vec![TreeNode::failed(
+                    &self.content,
+                    Span::new(0, self.content.len()),
+                )],
+            },
+        }
+    }
+
+    /// the problems found while parsing; an empty list
+    /// means the content parsed cleanly
+    pub fn diagnostics(&self) -> &Vec<Diagnostic> {
+        &self.diagnostics
+    }
+
+    /// runs the linter's rules against the parsed content
+    pub fn lint(&self, linter: &Linter) -> Vec<Diagnostic> {
+        linter.lint(&self.content, self.tree())
+    }
+
+    /// renders the tree as normalized Markdown
+    pub fn to_markdown(&self, options: &MarkdownOptions) -> String {
+        to_markdown(self.tree(), options)
+    }
+
+    /// the tree as mdast for use with the remark ecosystem
+    pub fn to_mdast(&self) -> MdastNode {
+        to_mdast(self.tree(), &self.content)
+    }
+
+    /// renders the tree as Graphviz DOT or Mermaid text
+    pub fn to_graph(&self, options: &GraphOptions) -> String {
+        render(&GraphNode::from(self.tree()), options)
+    }
+
+    /// renders the tree as HTML
+    pub fn to_html(&self) -> String {
+        to_html(self.tree())
+    }
+
+    /// renders the tree as HTML using the options; the inner content
+    /// of any component is parsed as Markdown before it's rendered (and
+    /// the heading ids are assigned again to include its headings)
+    pub fn to_html_with(&self, options: &HtmlOptions) -> String {
+        match options.components {
+            Some(components) => {
+                let mut tree = self.tree().clone();
+                parse_components(&mut tree, components);
+                assign_ids(&mut tree);
+                to_html_with(&tree, options)
+            }
+            None => to_html_with(self.tree(), options),
+        }
+    }
+
+    /// renders the tree as HTML after removing any tags, attributes
+    /// and URLs the policy doesn't allow; use this for untrusted content
+    pub fn to_safe_html(&self, policy: &SanitizePolicy) -> String {
+        let mut tree = self.tree().clone();
+        sanitize(&mut tree, policy);
+
+        to_html(&tree)
+    }
+
+    /// a _lossless_ syntax tree of the content; unlike the `tree`,
+    /// all whitespace and markers are retained
+    pub fn cst(&self) -> CstNode {
+        match &self.pairs {
+            Some(pairs) => cst::from_pairs(pairs.clone(), &self.content),
+            None => cst::parse(self.rule, &self.content),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::initialize;
+
+    #[test]
+    fn resilient_parse_keeps_bad_regions_as_text() {
+        initialize();
+        let md = "# Foobar\n\n```stray\n\n## Baz\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let tree = parsed.tree();
+
+        assert_eq!(tree.kind, NodeKind::Document);
+        assert_eq!(tree.children.len(), 3);
+        assert!(matches!(
+            tree.children[0].kind,
+            NodeKind::Heading { level: 1, .. }
+        ));
+        assert_eq!(
+            tree.children[1].kind,
+            NodeKind::Text("```stray".to_string())
+        );
+        assert!(matches!(
+            tree.children[2].kind,
+            NodeKind::Heading { level: 2, .. }
+        ));
+
+        assert_eq!(parsed.diagnostics().len(), 1);
+        assert_eq!(parsed.diagnostics()[0].span, Span::new(10, 18));
+    }
+
+    #[test]
+    fn resilient_parse_exports_bad_regions_as_failed() {
+        initialize();
+        let md = "# Foobar\n\n```stray\n\n## Baz\n";
+        let exported = Parkdown::new(md).parse().export();
+
+        assert_eq!(exported, Tree::from_content(md));
+        assert_eq!(exported.nodes[0].children[1].kind, crate::export::FAILED);
+
+        let p = Parkdown::with_rule(Rule::heading, "Foo");
+        let exported = p.parse().export();
+        assert_eq!(exported.nodes[0].kind, crate::export::FAILED);
+        assert_eq!(exported.nodes[0].text.as_deref(), Some("Foo"));
+    }
+
+    #[test]
+    fn strict_parse_fails_on_bad_regions() {
+        initialize();
+        let p = Parkdown::new("# Foobar\n\n```stray\n");
+
+        assert!(p.try_parse().is_err());
+    }
+
+    #[test]
+    fn clean_document_parses_through_to_eoi() {
+        initialize();
+        for md in ["", "# Foobar\n", "\n\n# Foobar\n---\n"] {
+            let p = Parkdown::new(md);
+
+            assert!(p.try_parse().is_ok());
+        }
+    }
+
+    #[test]
+    fn list_item_continuation_is_parsed_as_blocks() {
+        initialize();
+        let md = "- foo\n  - bar\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let list = &parsed.tree().children[0];
+        let item = &list.children[0];
+
+        assert_eq!(list.kind, NodeKind::List { ordered: false });
+        assert_eq!(item.children.len(), 2);
+        assert_eq!(item.children[1].kind, NodeKind::List { ordered: false });
+        // spans of the nested list point back into the original source
+        assert_eq!(item.children[1].span.start, 8);
+        assert_eq!(item.children[1].text_content(), "bar");
+    }
+
+    #[test]
+    fn lint_parsed_content() {
+        initialize();
+        let p = Parkdown::new("# Foo\n\n### Bar\n");
+        let diagnostics = p.parse().lint(&Linter::default());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("MD001"));
+    }
+
+    #[test]
+    fn clean_parse_has_no_diagnostics() {
+        initialize();
+        let p = Parkdown::with_rule(Rule::heading, "## Foobar\n");
+        let parsed = p.parse();
+
+        assert!(parsed.diagnostics().is_empty());
+        assert_eq!(
+            parsed.tree().kind,
+            NodeKind::Heading {
+                level: 2,
+                id: Some("foobar".to_string())
+            }
+        );
+        assert_eq!(parsed.tree().text_content(), "Foobar");
+    }
+
+    #[test]
+    fn cst_preserves_formatting() {
+        initialize();
+        let md = "##   Foo  \n\n*  bar\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+
+        assert_eq!(parsed.cst().text(), md);
+        assert_eq!(parsed.cst().find_all("h2").len(), 1);
+    }
+
+    #[test]
+    fn only_a_closing_sequence_is_removed_from_headings() {
+        initialize();
+        let md = "# C#\n\n# #1 rule\n\n## Foo ##\n\n### ###\n";
+        let p = Parkdown::new(md);
+
+        assert_eq!(
+            p.parse().to_html(),
+            concat!(
+                "<h1 id=\"c\">C#</h1>\n",
+                "<h1 id=\"1-rule\">#1 rule</h1>\n",
+                "<h2 id=\"foo\">Foo</h2>\n",
+                "<h3 id=\"\"></h3>",
+            )
+        );
+    }
+
+    #[test]
+    fn toc_placeholder_renders_as_nested_list() {
+        initialize();
+        let md = "# Test Markdown\n\n<!-- toc -->\n\n## Topic 1\n\n## Topic 1\n";
+        let p = Parkdown::new(md);
+        let html = p.parse().to_html();
+
+        assert!(html.contains("<h2 id=\"topic-1-1\">Topic 1</h2>"));
+        assert!(html.contains(
+            "<li><a href=\"#test-markdown\">Test Markdown</a>\n<ul>\n<li><a href=\"#topic-1\">"
+        ));
+    }
+
+    #[test]
+    fn safe_html_strips_unsafe_attributes() {
+        initialize();
+        let p = Parkdown::with_rule(
+            Rule::tag,
+            "<a onclick=\"alert(1)\" href=\"javascript:alert(1)\" title=\"bar\">baz</a>",
+        );
+        let parsed = p.parse();
+
+        assert!(parsed.to_html().contains("onclick"));
+        assert_eq!(
+            parsed.to_safe_html(&SanitizePolicy::default()),
+            "<a title=\"bar\">baz</a>"
+        );
+    }
+
+    #[test]
+    fn components_receive_parsed_inner_markdown() {
+        initialize();
+        let mut components = ComponentRegistry::new();
+        components.register("block-component", |c| {
+            let headings = c
+                .children
+                .iter()
+                .filter(|n| matches!(n.kind, NodeKind::Heading { .. }))
+                .count();
+            format!(
+                "<section data-headings=\"{}\">\n{}\n</section>",
+                headings, c.inner
+            )
+        });
+        let p = Parkdown::with_rule(
+            Rule::tag,
+            "<block-component size=\"2\">\n## Inner\n\n::smile\n</block-component>",
+        );
+        let parsed = p.parse();
+        let html = parsed.to_html_with(&HtmlOptions {
+            components: Some(&components),
+            ..HtmlOptions::default()
+        });
+
+        assert_eq!(
+            html,
+            "<section data-headings=\"1\">\n<h2 id=\"inner\">Inner</h2>\n<span class=\"emoji block\">😀</span>\n</section>"
+        );
+    }
+
+    #[test]
+    fn markdown_inside_block_tags_is_parsed() {
+        initialize();
+        let md = "<block-component>\n\n  ## Inner\n\n  ::smile\n\n</block-component>\n\n<my-inline>\n## raw\n</my-inline>\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let tree = parsed.tree();
+
+        assert!(parsed.diagnostics().is_empty());
+        assert_eq!(tree.children.len(), 2);
+        let component = &tree.children[0];
+        assert!(component.is_block());
+        assert!(matches!(
+            component.children[0].kind,
+            NodeKind::Heading { level: 2, .. }
+        ));
+        assert_eq!(component.children[0].span, Span::new(21, 30));
+        assert!(matches!(
+            component.children[1].kind,
+            NodeKind::BlockEmoji { .. }
+        ));
+        // without an empty line the inner content is kept as raw HTML
+        assert_eq!(
+            tree.children[1].children,
+            vec![Node::new(
+                NodeKind::Html("\n## raw\n".to_string()),
+                Span::new(73, 81)
+            )]
+        );
+        assert_eq!(
+            parsed.to_html(),
+            "<block-component>\n<h2 id=\"inner\">Inner</h2>\n<span class=\"emoji block\">😀</span>\n</block-component>\n<my-inline>\n## raw\n</my-inline>"
+        );
+    }
+
+    #[test]
+    fn raw_html_blocks_are_passed_through() {
+        initialize();
+        let md = "<!DOCTYPE html>\n\n<pre>\n# not a heading\n\n</pre>\n\n<div>\n<span>*x*</span>\n\n# Heading\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let tree = parsed.tree();
+
+        assert!(parsed.diagnostics().is_empty());
+        assert_eq!(
+            tree.children[0].kind,
+            NodeKind::Html("<!DOCTYPE html>".to_string())
+        );
+        assert_eq!(tree.children[2].span, Span::new(48, 70));
+        assert_eq!(
+            parsed.to_html(),
+            "<!DOCTYPE html>\n<pre>\n# not a heading\n\n</pre>\n<div>\n<span>*x*</span>\n<h1 id=\"heading\">Heading</h1>"
+        );
+    }
+
+    #[test]
+    fn character_references_are_decoded() {
+        initialize();
+        let md = "# Fish &amp; Chips &#35;1 &bogus;\n\n```\n&amp;\n```\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let heading = &parsed.tree().children[0];
+
+        assert_eq!(heading.text_content(), "Fish & Chips #1 &bogus;");
+        assert_eq!(heading.children[0].span, Span::new(2, 33));
+        assert_eq!(
+            parsed.to_html(),
+            "<h1 id=\"fish--chips-1-bogus\">Fish &amp; Chips #1 &amp;bogus;</h1>\n<pre><code>&amp;amp;\n</code></pre>"
+        );
+        assert_eq!(
+            parsed.to_markdown(&MarkdownOptions::default()),
+            "# Fish & Chips #1 &bogus;\n\n```\n&amp;\n```\n"
+        );
+    }
+
+    #[test]
+    fn line_breaks_within_paragraphs() {
+        initialize();
+        let md = "- foo  \n  bar\\\n  baz &amp;\n  qux\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let paragraph = &parsed.tree().children[0].children[0].children[0];
+        let kinds: Vec<&NodeKind> = paragraph.children.iter().map(|c| &c.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                &NodeKind::Text("foo".to_string()),
+                &NodeKind::HardBreak,
+                &NodeKind::Text("bar".to_string()),
+                &NodeKind::HardBreak,
+                &NodeKind::Text("baz &".to_string()),
+                &NodeKind::SoftBreak,
+                &NodeKind::Text("qux".to_string()),
+            ]
+        );
+        assert_eq!(paragraph.children[1].span, Span::new(5, 10));
+        assert_eq!(paragraph.children[4].span, Span::new(17, 26));
+        assert_eq!(
+            parsed.to_html(),
+            "<ul>\n<li>foo<br />\nbar<br />\nbaz &amp;\nqux</li>\n</ul>"
+        );
+    }
+
+    #[test]
+    fn paragraphs_end_at_empty_lines_and_interrupting_blocks() {
+        initialize();
+        let md = "foo [bar](/bar) ::smile::\nbaz\n\nqux\n## Heading\nquux\n- item\n> quote\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let tree = parsed.tree();
+        let kinds: Vec<&NodeKind> = tree.children.iter().map(|c| &c.kind).collect();
+
+        assert!(parsed.diagnostics().is_empty());
+        assert_eq!(
+            kinds,
+            vec![
+                &NodeKind::Paragraph,
+                &NodeKind::Paragraph,
+                &NodeKind::Heading {
+                    level: 2,
+                    id: Some("heading".to_string())
+                },
+                &NodeKind::Paragraph,
+                &NodeKind::List { ordered: false },
+                &NodeKind::BlockQuote,
+            ]
+        );
+
+        let first = &tree.children[0];
+        assert_eq!(first.span, Span::new(0, 29));
+        assert_eq!(
+            first.children.iter().map(|c| &c.kind).collect::<Vec<_>>(),
+            vec![
+                &NodeKind::Text("foo ".to_string()),
+                &NodeKind::Link {
+                    dest: "/bar".to_string()
+                },
+                &NodeKind::Text(" ".to_string()),
+                &NodeKind::Emoji {
+                    name: "smile".to_string()
+                },
+                &NodeKind::SoftBreak,
+                &NodeKind::Text("baz".to_string()),
+            ]
+        );
+        assert_eq!(first.children[1].span, Span::new(4, 15));
+
+        let quote = &tree.children[5];
+        assert_eq!(quote.children[0].kind, NodeKind::Paragraph);
+        assert_eq!(quote.children[0].span, Span::new(60, 65));
+        assert_eq!(parsed.to_html().lines().last(), Some("</blockquote>"));
+    }
+
+    #[test]
+    fn test_document_parses_without_diagnostics() {
+        initialize();
+        let md = include_str!("../../cli/test.md");
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+
+        assert!(parsed.diagnostics().is_empty());
+        assert!(parsed
+            .tree()
+            .children
+            .iter()
+            .all(|c| !matches!(c.kind, NodeKind::Text(_))));
+    }
+
+    #[test]
+    fn pipelines_transform_the_parsed_tree() {
+        initialize();
+        let config = parser::pipeline::PipelineConfig {
+            plugins: vec![parser::pipeline::PluginConfig {
+                name: "rewrite-links".to_string(),
+                options: serde_json::json!({ "base": "https://docs.com" }),
+            }],
+        };
+        let mut pipeline = parser::pipeline::PluginRegistry::builtin()
+            .pipeline(&config)
+            .unwrap();
+        let p = Parkdown::new("[guide](guide.md)\n");
+        let transformed: Parkdown<Transformed> = p.parse().transform(&mut pipeline).unwrap();
+
+        assert_eq!(
+            transformed.to_html(),
+            "<p><a href=\"https://docs.com/guide.md\">guide</a></p>"
+        );
+    }
+
+    #[test]
+    fn same_named_tags_nest() {
+        initialize();
+        let md = "<div class=\"outer\">\n\n<div>\n\n# Inner\n\n</div>\n\n</div>\n\n---\n";
+        let p = Parkdown::new(md);
+        let parsed = p.parse();
+        let tree = parsed.tree();
+
+        assert!(parsed.diagnostics().is_empty());
+        let outer = &tree.children[0];
+        assert_eq!(outer.span, Span::new(0, 51));
+        assert!(matches!(&outer.children[0].kind, NodeKind::Tag { name, .. } if name == "div"));
+        assert!(matches!(
+            outer.children[0].children[0].kind,
+            NodeKind::Heading { level: 1, .. }
+        ));
+        assert_eq!(tree.children[1].kind, NodeKind::ThematicBreak);
+    }
+
+    mod round_trip {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn words() -> impl Strategy<Value = String> {
+            let word = prop_oneof![
+                4 => "[a-z]{1,8}",
+                1 => "[a-z]{0,3}&(amp|copy|foo|#35|#x22|#0);?[a-z]{0,3}",
+            ];
+            prop::collection::vec(word, 1..6).prop_map(|w| w.join(" "))
+        }
+
+        fn heading() -> impl Strategy<Value = String> {
+            (1..=6usize, 0..=3usize, words()).prop_map(|(level, pad, title)| {
+                format!("{}{} {}\n", " ".repeat(pad), "#".repeat(level), title)
+            })
+        }
+
+        fn thematic_break() -> impl Strategy<Value = String> {
+            ("[-*_]", 3..6usize).prop_map(|(c, n)| format!("{}\n", c.repeat(n)))
+        }
+
+        /// a fenced code block whose code may hold lines which would
+        /// close a shorter fence
+        fn code_block() -> impl Strategy<Value = String> {
+            let line = prop_oneof![4 => "[a-z =;]{0,20}", 1 => "`{3,5}"];
+            ("[a-z]{0,4}", prop::collection::vec(line, 0..4)).prop_map(|(lang, lines)| {
+                let code: Vec<String> = lines.iter().map(|l| format!("{}\n", l)).collect();
+                let longest = lines
+                    .iter()
+                    .filter(|l| l.starts_with("```"))
+                    .map(|l| l.len())
+                    .max()
+                    .unwrap_or(2);
+                let fence = "`".repeat(longest + 1);
+                format!("{}{}\n{}{}\n", fence, lang, code.concat(), fence)
+            })
+        }
+
+        /// the text of a list item which may continue over several
+        /// lines with soft or hard breaks between them
+        fn item_text() -> impl Strategy<Value = String> {
+            (
+                words(),
+                prop::collection::vec((prop_oneof!["\n", "  \n", "\\\n"], words()), 0..3),
+            )
+                .prop_map(|(first, rest)| {
+                    let lines: Vec<String> = rest
+                        .iter()
+                        .map(|(line_break, text)| format!("{}  {}", line_break, text))
+                        .collect();
+                    format!("{}{}", first, lines.concat())
+                })
+        }
+
+        fn paragraph() -> impl Strategy<Value = String> {
+            let inline = prop_oneof![
+                Just("".to_string()),
+                " \\[[a-z]{1,5}\\]\\(/[a-z]{1,5}\\)",
+                Just(" ::smile::".to_string()),
+            ];
+            (item_text(), inline).prop_map(|(text, inline)| format!("{}{}\n", text, inline))
+        }
+
+        fn block_quote() -> impl Strategy<Value = String> {
+            prop::collection::vec(prop_oneof![heading(), paragraph()], 1..3).prop_map(|blocks| {
+                blocks
+                    .join("\n")
+                    .lines()
+                    .map(|line| format!("> {}\n", line))
+                    .collect()
+            })
+        }
+
+        fn list() -> impl Strategy<Value = String> {
+            prop::collection::vec(("[-+*]", item_text()), 1..5).prop_map(|items| {
+                items
+                    .iter()
+                    .map(|(marker, text)| format!("{} {}\n", marker, text))
+                    .collect::<Vec<String>>()
+                    .concat()
+            })
+        }
+
+        fn block_emoji() -> impl Strategy<Value = String> {
+            (
+                "[a-z][a-z_]{0,8}",
+                prop::collection::vec(("[a-z]{1,8}", "[a-z0-9 ]{0,8}"), 0..3),
+            )
+                .prop_map(|(name, attrs)| {
+                    if attrs.is_empty() {
+                        format!("::{}\n", name)
+                    } else {
+                        let props: Vec<String> = attrs
+                            .iter()
+                            .map(|(k, v)| format!("{}:\"{}\"", k, v))
+                            .collect();
+                        format!("::{} {{{}}}\n", name, props.join(",  "))
+                    }
+                })
+        }
+
+        fn block_tag() -> impl Strategy<Value = String> {
+            (
+                "[a-z]{1,6}(-[a-z]{1,6})?",
+                prop::collection::vec(prop_oneof![heading(), block_emoji()], 1..3),
+            )
+                .prop_map(|(name, blocks)| {
+                    format!("<{}>\n\n{}\n</{}>\n", name, blocks.join("\n"), name)
+                })
+        }
+
+        fn raw_html() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "<!--[a-z ]{0,10}-->\n",
+                "<div>\n[a-z]{1,8}\n",
+                "<pre>\n\n[a-z ]{0,10}\n</pre>\n",
+            ]
+        }
+
+        fn document() -> impl Strategy<Value = String> {
+            let block = prop_oneof![
+                heading(),
+                thematic_break(),
+                code_block(),
+                list(),
+                paragraph(),
+                block_quote(),
+                block_emoji(),
+                block_tag(),
+                raw_html(),
+                prop_oneof![
+                    Just("[[toc]]\n".to_string()),
+                    Just("<!-- toc -->\n".to_string())
+                ]
+            ];
+            prop::collection::vec(block, 0..8).prop_map(|blocks| blocks.join("\n"))
+        }
+
+        proptest! {
+            #[test]
+            fn format_preserves_the_tree(md in document(), width in 0..40usize) {
+                let options = MarkdownOptions { width, ..MarkdownOptions::default() };
+                let original = Parkdown::new(&md);
+                let original = original.try_parse().unwrap();
+                let formatted = original.to_markdown(&options);
+
+                let reparsed = Parkdown::new(&formatted);
+                let reparsed = reparsed.try_parse();
+                prop_assert!(reparsed.is_ok(), "formatted output failed to parse:\n{}", formatted);
+                let reparsed = reparsed.unwrap();
+
+                prop_assert!(
+                    original.tree().is_equivalent(reparsed.tree()),
+                    "trees differ\n{:#?}\n{:#?}",
+                    original.tree(),
+                    reparsed.tree()
+                );
+                // formatting is idempotent
+                prop_assert_eq!(reparsed.to_markdown(&options), formatted);
+            }
+        }
+    }
 }
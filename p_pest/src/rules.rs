@@ -0,0 +1,173 @@
+//! Rules looked up by their name (e.g., from the command line) rather
+//! than referenced as `Rule` variants.
+//!
+//! ```
+//! use p_pest::{rules::try_rule, Rule};
+//!
+//! let rule: Rule = "heading".parse().unwrap();
+//! let found = try_rule(rule, "# Foo\nbar").unwrap();
+//!
+//! assert_eq!(found.consumed.unwrap().end, 6);
+//! assert_eq!(found.remainder, Some("bar"));
+//! ```
+
+use color_eyre::{eyre::eyre, Report};
+use parser::ast::Span;
+use pest::{error::Error, iterators::Pairs, Parser};
+use std::str::FromStr;
+
+use crate::{Markdown, Rule};
+
+macro_rules! rules {
+    ($($rule:ident),* $(,)?) => {
+        /// every rule in the grammar (including silent rules) in the
+        /// order they're defined
+        pub const RULES: &[Rule] = &[$(Rule::$rule),*];
+
+        /// the name of the rule as written in the grammar; as the
+        /// match is exhaustive, a rule which is added to the grammar
+        /// without being added to `RULES` doesn't compile
+        pub fn name(rule: Rule) -> &'static str {
+            match rule {
+                $(Rule::$rule => stringify!($rule),)*
+            }
+        }
+    };
+}
+
+rules! {
+    EOI, lf, cr, tab, space, pad, opt3_space, ff, WHITESPACE, trim, not_newline,
+    rest_of_line, text, title, title_with_attrs, entity_ref, dec_num_ref, hex_num_ref,
+    char_ref, open_bracket, close_bracket, dash, underscore, asterisk, backtick, fence,
+    lang_fence, lowercase, uppercase, number, camel_case, pascal_case, kebab_case,
+    snake_case, cased_var, double_quote, single_quote, open_double_quote,
+    close_double_quote, esc_open_double_quote, esc_close_double_quote, var, line_ending,
+    content, line, eol, to_eol, empty_line, sol, indent, h_indent, sentence_terminators,
+    sentence_sep_char, special_char, math_char, numeric_char, str_char, comment,
+    processing_instruction, declaration, cdata, open_tag, closing_tag, raw_html,
+    hard_break, soft_break, inline_start, inline_text, inline, inlines, non_whitepace,
+    punctuation, colon, equals, assignment_op, assignment, attr_value, key, prop_name,
+    value, tagname, attr_name, single_quoted_value, unquoted_value, attr, attrs, prop,
+    dict, csv, end_tag, nested_tag, inner, block_tag, self_closing_tag, tag,
+    space_collapse, maybe_two_space, maybe_three_space, greater_than, less_than,
+    html_gt, html_lt, link_desc, link_uri, protocol, protocol_delim, external_uri, uri,
+    link, image, italic_marker, bold_marker, list_marker, ordered_marker, list_text,
+    list_continuation, list_item, list, emoji_name, emoji, block_emoji, h_prefix, h1,
+    h2, h3, h4, h5, h6, heading, paragraph_interrupt, paragraph_line, paragraph,
+    thematic_char, thematic_characters, thematic_break, code_block_precondition,
+    code_line, indented_code_block, fence_lang, fence_ignore, fence_info, fence_defn,
+    fence_close, closes_fence, fenced_code_line, fenced_code_block, toc, html_element,
+    raw_text_tagname, raw_text_end, html_block_tagname, html_literal, html_lines,
+    html_block, quote_line, block_quote, leaf_block, container_block, block, file,
+}
+
+impl FromStr for Rule {
+    type Err = Report;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match RULES.iter().find(|rule| self::name(**rule) == name) {
+            Some(rule) => Ok(*rule),
+            None => {
+                // the rules are snake_case so `blockquote` is `block_quote`
+                let plain = |name: &str| name.replace('_', "");
+                let similar: Vec<&str> = RULES
+                    .iter()
+                    .map(|rule| self::name(*rule))
+                    .filter(|rule| {
+                        rule.contains(name) || name.contains(rule) || plain(rule) == plain(name)
+                    })
+                    .collect();
+                match similar.is_empty() {
+                    true => Err(eyre!("there's no rule called '{}'", name)),
+                    false => Err(eyre!(
+                        "there's no rule called '{}'; did you mean {}?",
+                        name,
+                        similar.join(", ")
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// The result of parsing content against a single rule.
+#[derive(Debug)]
+pub struct RuleMatch<'a> {
+    pub rule: Rule,
+    pub pairs: Pairs<'a, Rule>,
+    /// the span of the content the rule matched; `None` for a silent
+    /// rule as it has no pair of its own (only those of its children)
+    /// so what it consumed isn't known
+    pub consumed: Option<Span>,
+    /// the content after the match which the rule didn't consume (when
+    /// that's known)
+    pub remainder: Option<&'a str>,
+}
+
+/// Parses the content against the rule; unlike `file`, most rules
+/// match a _prefix_ of the content so whatever's left is returned as
+/// the `remainder`.
+pub fn try_rule(rule: Rule, content: &str) -> Result<RuleMatch<'_>, Box<Error<Rule>>> {
+    let pairs = Markdown::parse(rule, content).map_err(Box::new)?;
+    // a rule which isn't silent is a single pair spanning its match
+    let mut own = pairs.clone().filter(|p| p.as_rule() == rule);
+    let consumed = match (own.next(), own.next()) {
+        (Some(pair), None) => Some(Span::new(pair.as_span().start(), pair.as_span().end())),
+        _ => None,
+    };
+
+    Ok(RuleMatch {
+        rule,
+        pairs,
+        consumed,
+        remainder: consumed.map(|span| &content[span.end..]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_are_found_by_name() {
+        crate::initialize();
+        assert_eq!("heading".parse::<Rule>().unwrap(), Rule::heading);
+        assert_eq!("EOI".parse::<Rule>().unwrap(), Rule::EOI);
+        assert_eq!(name(Rule::fenced_code_block), "fenced_code_block");
+
+        let error = "headin".parse::<Rule>().unwrap_err().to_string();
+        assert!(error.contains("did you mean heading?"), "{}", error);
+    }
+
+    #[test]
+    fn every_rule_in_the_grammar_is_listed() {
+        let grammar = include_str!("markdown.pest");
+        let defined = grammar
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, _)| name.trim())
+            .filter(|name| {
+                !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            })
+            .count();
+
+        // every defined rule along with `EOI`
+        assert_eq!(RULES.len(), defined + 1);
+    }
+
+    #[test]
+    fn rules_report_what_they_consumed() {
+        let found = try_rule(Rule::list_item, "- a\n- b\n").unwrap();
+        assert_eq!(found.consumed, Some(Span::new(0, 4)));
+        assert_eq!(found.remainder, Some("- b\n"));
+        assert_eq!(found.pairs.count(), 1);
+
+        // the spaces are consumed but there's no pair to say so
+        let silent = try_rule(Rule::opt3_space, "  x").unwrap();
+        assert_eq!(silent.consumed, None);
+        assert_eq!(silent.remainder, None);
+        assert_eq!(silent.pairs.count(), 0);
+
+        assert!(try_rule(Rule::heading, "foo").is_err());
+    }
+}
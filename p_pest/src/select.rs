@@ -0,0 +1,496 @@
+//! A CSS-selector-like query language for the pairs of a parse.
+//!
+//! A selector is made up of the names of rules (or `*` for any rule)
+//! and the combinators between them:
+//!
+//! - `heading h2` -- an `h2` anywhere within a `heading`
+//! - `block > heading` -- a `heading` which is a direct child of a `block`
+//! - `heading, list` -- either a `heading` or a `list`
+//!
+//! A rule can be narrowed further with _attributes_ which compare the
+//! text of a descendant rule -- named either exactly or by the part after
+//! its last `_` (e.g., `lang` for `fence_lang`) -- or of the rule itself
+//! with `text`:
+//!
+//! - `[lang]` -- has a (non-empty) descendant `fence_lang` rule
+//! - `[lang=ts]`, `[text^=foo]`, `[text$=bar]`, `[text*=baz]` -- the
+//!   text is equal to, starts with, ends with or contains the value;
+//!   values may be quoted
+//!
+//! and _pseudo-classes_ which check a rule's position among its
+//! siblings: `:first-child`, `:last-child` and `:nth-child(n)` where
+//! `n` is 1-based or `odd`/`even`.
+//!
+//! A rule or attribute which isn't in the grammar is an error (rather
+//! than a selector which never matches).
+
+use std::str::FromStr;
+
+use color_eyre::{eyre::eyre, Report, Result};
+use pest::iterators::Pair;
+
+use crate::{
+    rules::{name, RULES},
+    Rule,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// the rule is anywhere within the previous rule
+    Descendant,
+    /// the rule is a direct child of the previous rule
+    Child,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operator {
+    Exists,
+    Equals(String),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Attribute {
+    name: String,
+    operator: Operator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NthChild {
+    Index(usize),
+    Odd,
+    Even,
+    Last,
+}
+
+/// a rule (`None` for `*`) along with its attributes and
+/// pseudo-classes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Compound {
+    rule: Option<Rule>,
+    attributes: Vec<Attribute>,
+    nth_child: Vec<NthChild>,
+}
+
+/// A parsed selector; it's a list of alternatives, each of which is a
+/// chain of compound selectors along with the combinator which
+/// precedes each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    alternatives: Vec<Vec<(Combinator, Compound)>>,
+}
+
+/// a pair in the flattened tree along with where it is in the tree
+struct Entry<'a> {
+    pair: Pair<'a, Rule>,
+    parent: Option<usize>,
+    /// the 1-based position among its siblings
+    position: usize,
+    siblings: usize,
+}
+
+fn flatten<'a>(
+    pair: Pair<'a, Rule>,
+    parent: Option<usize>,
+    position: usize,
+    siblings: usize,
+    entries: &mut Vec<Entry<'a>>,
+) {
+    let idx = entries.len();
+    let children: Vec<Pair<'a, Rule>> = pair.clone().into_inner().collect();
+    entries.push(Entry {
+        pair,
+        parent,
+        position,
+        siblings,
+    });
+
+    let count = children.len();
+    for (i, child) in children.into_iter().enumerate() {
+        flatten(child, Some(idx), i + 1, count, entries);
+    }
+}
+
+/// the text of a descendant rule which is `name` (or ends with `_name`)
+fn descendant_text<'a>(pair: &Pair<'a, Rule>, name: &str) -> Option<&'a str> {
+    let suffix = format!("_{}", name);
+    pair.clone().into_inner().flatten().find_map(|p| {
+        let rule = self::name(p.as_rule());
+        (rule == name || rule.ends_with(&suffix)).then(|| p.as_str())
+    })
+}
+
+impl Attribute {
+    fn matches(&self, pair: &Pair<Rule>) -> bool {
+        let text = match self.name.as_str() {
+            "text" => Some(pair.as_str().trim()),
+            name => descendant_text(pair, name).map(|t| t.trim()),
+        };
+
+        match (&self.operator, text) {
+            (_, None) => false,
+            (Operator::Exists, Some(text)) => !text.is_empty(),
+            (Operator::Equals(v), Some(text)) => text == v,
+            (Operator::StartsWith(v), Some(text)) => text.starts_with(v.as_str()),
+            (Operator::EndsWith(v), Some(text)) => text.ends_with(v.as_str()),
+            (Operator::Contains(v), Some(text)) => text.contains(v.as_str()),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.rule.is_none_or(|r| r == entry.pair.as_rule())
+            && self.attributes.iter().all(|a| a.matches(&entry.pair))
+            && self.nth_child.iter().all(|n| match n {
+                NthChild::Index(idx) => entry.position == *idx,
+                NthChild::Odd => !entry.position.is_multiple_of(2),
+                NthChild::Even => entry.position.is_multiple_of(2),
+                NthChild::Last => entry.position == entry.siblings,
+            })
+    }
+}
+
+impl Selector {
+    /// The pairs in the tree under (and including) `root` which match,
+    /// in document order.
+    pub fn select<'a>(&self, root: Pair<'a, Rule>) -> Vec<Pair<'a, Rule>> {
+        let mut entries = vec![];
+        flatten(root, None, 1, 1, &mut entries);
+
+        (0..entries.len())
+            .filter(|idx| {
+                self.alternatives
+                    .iter()
+                    .any(|chain| matches(&entries, *idx, chain))
+            })
+            .map(|idx| entries[idx].pair.clone())
+            .collect()
+    }
+}
+
+/// whether the entry matches the last compound in the chain (and its
+/// ancestors match the rest of the chain)
+fn matches(entries: &[Entry], idx: usize, chain: &[(Combinator, Compound)]) -> bool {
+    let Some(((combinator, compound), rest)) = chain.split_last() else {
+        return true;
+    };
+    if !compound.matches(&entries[idx]) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+
+    match combinator {
+        Combinator::Child => entries[idx]
+            .parent
+            .is_some_and(|parent| matches(entries, parent, rest)),
+        Combinator::Descendant => {
+            let mut ancestor = entries[idx].parent;
+            while let Some(parent) = ancestor {
+                if matches(entries, parent, rest) {
+                    return true;
+                }
+                ancestor = entries[parent].parent;
+            }
+            false
+        }
+    }
+}
+
+/// A cursor over the selector's text.
+struct Tokens<'s> {
+    text: &'s str,
+    pos: usize,
+}
+
+impl<'s> Tokens<'s> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        self.take_while(char::is_whitespace);
+        self.pos > start
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'s str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += self.peek().map_or(0, char::len_utf8);
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn identifier(&mut self) -> Result<&'s str> {
+        match self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            "" => Err(self.error("expected a name")),
+            name => Ok(name),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", c)))
+        }
+    }
+
+    fn error(&self, message: &str) -> Report {
+        eyre!(
+            "invalid selector '{}': {} at {}",
+            self.text,
+            message,
+            self.pos
+        )
+    }
+
+    /// an attribute's value which may be quoted
+    fn value(&mut self) -> Result<String> {
+        for quote in ['"', '\''] {
+            if self.eat(quote) {
+                let value = self.take_while(|c| c != quote).to_string();
+                self.expect(quote)?;
+                return Ok(value);
+            }
+        }
+        Ok(self.take_while(|c| c != ']').trim().to_string())
+    }
+
+    /// the rule with the name
+    fn rule(&mut self) -> Result<Rule> {
+        let start = self.pos;
+        let rule = self.identifier()?;
+        rule.parse().map_err(|err: Report| {
+            self.pos = start;
+            self.error(&err.to_string())
+        })
+    }
+
+    fn attribute(&mut self) -> Result<Attribute> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let name = self.identifier()?.to_string();
+        let suffix = format!("_{}", name);
+        let known = name == "text"
+            || RULES
+                .iter()
+                .any(|r| self::name(*r) == name || self::name(*r).ends_with(&suffix));
+        if !known {
+            self.pos = start;
+            return Err(self.error(&format!("there's no rule for the attribute '{}'", name)));
+        }
+        self.skip_whitespace();
+
+        let prefix = ['^', '$', '*'].into_iter().find(|c| self.eat(*c));
+        let operator = if prefix.is_some() || self.peek() == Some('=') {
+            self.expect('=')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            match prefix {
+                Some('^') => Operator::StartsWith(value),
+                Some('$') => Operator::EndsWith(value),
+                Some(_) => Operator::Contains(value),
+                None => Operator::Equals(value),
+            }
+        } else {
+            Operator::Exists
+        };
+        self.skip_whitespace();
+        self.expect(']')?;
+
+        Ok(Attribute { name, operator })
+    }
+
+    fn pseudo_class(&mut self) -> Result<NthChild> {
+        match self.identifier()? {
+            "first-child" => Ok(NthChild::Index(1)),
+            "last-child" => Ok(NthChild::Last),
+            "nth-child" => {
+                self.expect('(')?;
+                let arg = self.take_while(|c| c != ')').trim().to_string();
+                self.expect(')')?;
+                match arg.as_str() {
+                    "odd" => Ok(NthChild::Odd),
+                    "even" => Ok(NthChild::Even),
+                    n => match n.parse::<usize>() {
+                        Ok(n) if n > 0 => Ok(NthChild::Index(n)),
+                        _ => Err(self.error("expected a position, 'odd' or 'even'")),
+                    },
+                }
+            }
+            other => Err(self.error(&format!("unknown pseudo-class ':{}'", other))),
+        }
+    }
+
+    fn compound(&mut self) -> Result<Compound> {
+        let rule = if self.eat('*') {
+            None
+        } else {
+            Some(self.rule()?)
+        };
+        let mut compound = Compound {
+            rule,
+            attributes: vec![],
+            nth_child: vec![],
+        };
+
+        loop {
+            if self.eat('[') {
+                compound.attributes.push(self.attribute()?);
+            } else if self.eat(':') {
+                compound.nth_child.push(self.pseudo_class()?);
+            } else {
+                return Ok(compound);
+            }
+        }
+    }
+
+    fn chain(&mut self) -> Result<Vec<(Combinator, Compound)>> {
+        let mut chain = vec![];
+        let mut combinator = Combinator::Descendant;
+
+        self.skip_whitespace();
+        loop {
+            chain.push((combinator, self.compound()?));
+
+            let spaced = self.skip_whitespace();
+            combinator = if self.eat('>') {
+                self.skip_whitespace();
+                Combinator::Child
+            } else if spaced && !matches!(self.peek(), None | Some(',')) {
+                Combinator::Descendant
+            } else {
+                return Ok(chain);
+            };
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Report;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut tokens = Tokens { text, pos: 0 };
+        let mut alternatives = vec![tokens.chain()?];
+
+        while tokens.eat(',') {
+            alternatives.push(tokens.chain()?);
+        }
+        if tokens.pos < text.len() {
+            return Err(tokens.error("unexpected character"));
+        }
+
+        Ok(Self { alternatives })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Markdown;
+    use pest::Parser;
+
+    const MD: &str =
+        "# Foo\n\n## Bar\n\n```ts\nlet x = 1;\n```\n\n```rust\nlet y = 2;\n```\n\n- a\n- b\n- c\n";
+
+    fn select<'a>(selector: &str, md: &'a str) -> Vec<(String, &'a str)> {
+        let root = Markdown::parse(Rule::file, md).unwrap().next().unwrap();
+        selector
+            .parse::<Selector>()
+            .unwrap()
+            .select(root)
+            .into_iter()
+            .map(|p| (format!("{:?}", p.as_rule()), p.as_str().trim()))
+            .collect()
+    }
+
+    #[test]
+    fn descendant_and_child_combinators() {
+        assert_eq!(
+            select("block > leaf_block heading h2", MD),
+            vec![("h2".to_string(), "## Bar")]
+        );
+        assert!(select("block > heading", MD).is_empty());
+        assert_eq!(select("file heading, list", MD).len(), 3);
+        assert_eq!(
+            select("*", "# Foo\n")
+                .iter()
+                .map(|(rule, _)| rule.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "file",
+                "block",
+                "leaf_block",
+                "heading",
+                "h1",
+                "title",
+                "EOI"
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes_compare_descendant_text() {
+        let ts = select("fenced_code_block[lang=ts]", MD);
+        assert_eq!(ts.len(), 1);
+        assert!(ts[0].1.contains("let x"));
+
+        assert_eq!(select("fenced_code_block[lang]", MD).len(), 2);
+        assert_eq!(select("fenced_code_block[lang^='ru']", MD).len(), 1);
+        assert_eq!(select("heading[text*=Ba]", MD).len(), 1);
+        assert_eq!(select("heading[text$=\"Foo\"]", MD).len(), 1);
+    }
+
+    #[test]
+    fn nth_child() {
+        let items = |s| {
+            select(s, MD)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(items("list_item:first-child"), vec!["- a"]);
+        assert_eq!(items("list > list_item:nth-child(2)"), vec!["- b"]);
+        assert_eq!(items("list_item:last-child"), vec!["- c"]);
+        assert_eq!(items("list_item:nth-child(odd)"), vec!["- a", "- c"]);
+    }
+
+    #[test]
+    fn invalid_selectors_are_an_error() {
+        for selector in [
+            "",
+            "heading[",
+            "heading:nth-child(0)",
+            "list >",
+            ":foo",
+            "list ]",
+        ] {
+            assert!(selector.parse::<Selector>().is_err(), "{}", selector);
+        }
+    }
+
+    #[test]
+    fn unknown_rules_are_an_error() {
+        let err = "blockquote > list".parse::<Selector>().unwrap_err();
+        assert!(err.to_string().contains("block_quote"), "{}", err);
+
+        assert!("fenced_code_block[lang]".parse::<Selector>().is_ok());
+        assert!("fenced_code_block[langauge]".parse::<Selector>().is_err());
+    }
+}
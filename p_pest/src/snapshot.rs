@@ -0,0 +1,287 @@
+//! Snapshot testing of the grammar.
+//!
+//! Each directory in `tests/fixtures` is named after a rule and holds
+//! Markdown fixtures (`*.md`) which are parsed with that rule. The
+//! result -- the tree of rules, what was consumed and the HTML -- is
+//! compared against the snapshot committed alongside the fixture
+//! (`<fixture>.snap`).
+//!
+//! When a snapshot doesn't match (or doesn't exist yet) the new result
+//! is written next to it as `<fixture>.snap.new` and the test fails;
+//! `cli snapshots` then shows each pending snapshot's diff so it can be
+//! accepted or rejected. Setting `UPDATE_SNAPSHOTS=1` accepts every new
+//! snapshot as the tests run.
+
+use color_eyre::{eyre::eyre, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{export::Tree, parkdown::Parkdown, rules::try_rule, Rule};
+
+/// A fixture along with the rule it's parsed with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub rule: Rule,
+    pub path: PathBuf,
+}
+
+impl Fixture {
+    /// where the fixture's snapshot is committed
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.path.with_extension("md.snap")
+    }
+
+    /// where a snapshot which doesn't match is written for review
+    pub fn pending_path(&self) -> PathBuf {
+        self.path.with_extension("md.snap.new")
+    }
+}
+
+/// What checking a fixture against its snapshot found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Matched,
+    /// the snapshot was written as there wasn't one (or it didn't match)
+    /// and updates were accepted
+    Updated,
+    /// the snapshot didn't match (or didn't exist) so the new one was
+    /// written to its pending path
+    Pending,
+}
+
+/// The snapshot of parsing the content with the rule; a rule which
+/// doesn't match has the parse error as its snapshot.
+pub fn render(rule: Rule, content: &str) -> String {
+    let found = match try_rule(rule, content) {
+        Ok(found) => found,
+        Err(error) => return format!("error:\n{}\n", error),
+    };
+    let mut snapshot = "tree:\n".to_string();
+    for node in Tree::from_pairs(found.pairs).nodes {
+        snapshot.push_str(&node.outline());
+    }
+    match (found.consumed, found.remainder) {
+        (Some(consumed), Some(remainder)) => snapshot.push_str(&format!(
+            "\nconsumed: {}..{}\nremainder: {:?}\n",
+            consumed.start, consumed.end, remainder
+        )),
+        _ => snapshot.push_str("\nconsumed: unknown\n"),
+    }
+
+    let md = Parkdown::with_rule(rule, content);
+    if let Ok(parsed) = md.try_parse() {
+        snapshot.push_str(&format!("\nhtml:\n{}\n", parsed.to_html()));
+    }
+
+    snapshot
+}
+
+/// the snapshot along with a header naming the rule and fixture
+fn document(fixture: &Fixture, snapshot: &str) -> String {
+    let name = fixture
+        .path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    format!(
+        "---\nrule: {}\nfixture: {}\n---\n{}",
+        crate::rules::name(fixture.rule),
+        name,
+        snapshot
+    )
+}
+
+/// Every fixture in the directory; each sub-directory must be named
+/// after a rule.
+pub fn fixtures(dir: &Path) -> Result<Vec<Fixture>> {
+    let mut fixtures = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let rule_dir = entry?.path();
+        if !rule_dir.is_dir() {
+            continue;
+        }
+        let name = rule_dir.file_name().unwrap_or_default().to_string_lossy();
+        let rule: Rule = name
+            .parse()
+            .map_err(|e| eyre!("the fixtures in {:?} can't be parsed: {}", rule_dir, e))?;
+
+        for entry in fs::read_dir(&rule_dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "md") {
+                fixtures.push(Fixture { rule, path });
+            }
+        }
+    }
+    fixtures.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(fixtures)
+}
+
+/// Checks the fixture against its snapshot; when `update` is set a
+/// snapshot which doesn't match is replaced rather than left pending.
+pub fn check(fixture: &Fixture, update: bool) -> Result<Outcome> {
+    let content = fs::read_to_string(&fixture.path)?;
+    let snapshot = document(fixture, &render(fixture.rule, &content));
+    let pending = fixture.pending_path();
+
+    if fs::read_to_string(fixture.snapshot_path()).ok().as_deref() == Some(snapshot.as_str()) {
+        if pending.exists() {
+            fs::remove_file(pending)?;
+        }
+        return Ok(Outcome::Matched);
+    }
+
+    if update {
+        fs::write(fixture.snapshot_path(), snapshot)?;
+        if pending.exists() {
+            fs::remove_file(pending)?;
+        }
+        Ok(Outcome::Updated)
+    } else {
+        fs::write(pending, snapshot)?;
+        Ok(Outcome::Pending)
+    }
+}
+
+/// A new snapshot waiting to be reviewed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pending {
+    /// the committed snapshot (if there is one)
+    pub snapshot: PathBuf,
+    pub pending: PathBuf,
+}
+
+impl Pending {
+    /// replaces the committed snapshot with the new one
+    pub fn accept(&self) -> Result<()> {
+        Ok(fs::rename(&self.pending, &self.snapshot)?)
+    }
+
+    /// discards the new snapshot
+    pub fn reject(&self) -> Result<()> {
+        Ok(fs::remove_file(&self.pending)?)
+    }
+}
+
+/// Every snapshot waiting to be reviewed in the fixtures directory.
+pub fn pending(dir: &Path) -> Result<Vec<Pending>> {
+    let mut pending = vec![];
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            pending.extend(self::pending(&path)?);
+        } else if path.to_string_lossy().ends_with(".snap.new") {
+            pending.push(Pending {
+                snapshot: path.with_extension(""),
+                pending: path,
+            });
+        }
+    }
+    pending.sort_by(|a, b| a.pending.cmp(&b.pending));
+
+    Ok(pending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{name, RULES};
+
+    /// the lexical helpers (single characters, character classes and the
+    /// rest of a line) which have no fixtures of their own as the fixtures
+    /// of the rules using them cover them; `end_tag` only matches the end
+    /// tag of an enclosing tag so it can't be parsed on its own
+    #[rustfmt::skip]
+    const COVERED_BY_PARENT: &[Rule] = &[
+        Rule::EOI, Rule::ff, Rule::not_newline, Rule::rest_of_line, Rule::text, Rule::open_bracket,
+        Rule::close_bracket, Rule::dash, Rule::underscore, Rule::asterisk, Rule::backtick,
+        Rule::fence, Rule::lowercase, Rule::uppercase, Rule::number, Rule::double_quote,
+        Rule::single_quote, Rule::line_ending, Rule::to_eol, Rule::empty_line, Rule::indent,
+        Rule::sentence_terminators, Rule::sentence_sep_char, Rule::special_char, Rule::math_char,
+        Rule::numeric_char, Rule::str_char, Rule::non_whitepace, Rule::punctuation, Rule::colon,
+        Rule::equals, Rule::attr_value, Rule::value, Rule::end_tag, Rule::space_collapse,
+        Rule::greater_than, Rule::less_than, Rule::html_gt, Rule::html_lt, Rule::italic_marker,
+        Rule::bold_marker, Rule::list_marker, Rule::list_text, Rule::thematic_char,
+    ];
+
+    /// the names of the silent rules (i.e., `rule = _{ ... }`)
+    fn silent() -> Vec<&'static str> {
+        include_str!("markdown.pest")
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(_, definition)| definition.trim_start().starts_with("_{"))
+            .map(|(name, _)| name.trim())
+            .collect()
+    }
+
+    #[test]
+    fn every_rule_has_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let silent = silent();
+
+        for rule in RULES {
+            let name = name(*rule);
+            let has_fixtures = dir.join(name).is_dir();
+            let covered = COVERED_BY_PARENT.contains(rule);
+
+            assert!(
+                has_fixtures || covered || silent.contains(&name),
+                "there are no fixtures in tests/fixtures/{}",
+                name
+            );
+            assert!(
+                !(has_fixtures && covered),
+                "{} has fixtures so doesn't need to be in COVERED_BY_PARENT",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn snapshots_include_the_tree_and_html() {
+        assert_eq!(
+            render(Rule::heading, "# Foo\nbar"),
+            concat!(
+                "tree:\n",
+                "heading 0..6\n",
+                "  h1 0..6\n",
+                "    title 2..5 \"Foo\"\n",
+                "\n",
+                "consumed: 0..6\n",
+                "remainder: \"bar\"\n",
+                "\n",
+                "html:\n",
+                "<h1 id=\"foo\">Foo</h1>\n",
+            )
+        );
+        assert!(render(Rule::heading, "bar").starts_with("error:\n"));
+    }
+
+    #[test]
+    fn mismatched_snapshots_are_left_for_review() {
+        let dir = std::env::temp_dir().join(format!("p_pest_snapshots_{}", std::process::id()));
+        fs::create_dir_all(dir.join("heading")).unwrap();
+        fs::write(dir.join("heading/atx.md"), "# Foo\n").unwrap();
+
+        let fixture = &fixtures(&dir).unwrap()[0];
+        assert_eq!(fixture.rule, Rule::heading);
+        assert_eq!(check(fixture, false).unwrap(), Outcome::Pending);
+
+        let review = pending(&dir).unwrap();
+        assert_eq!(review.len(), 1);
+        assert_eq!(review[0].snapshot, fixture.snapshot_path());
+        review[0].accept().unwrap();
+        assert_eq!(check(fixture, false).unwrap(), Outcome::Matched);
+
+        fs::write(dir.join("heading/atx.md"), "## Foo\n").unwrap();
+        assert_eq!(check(fixture, true).unwrap(), Outcome::Updated);
+        assert!(pending(&dir).unwrap().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+//! Strongly typed views of the `Pairs` produced by the grammar.
+//!
+//! Each struct is generated by the `typed!` macro from the rule it's
+//! converted from and the rules each of its fields are found under;
+//! as the rules are referenced as `Rule` variants, renaming (or
+//! removing) a rule in the grammar without updating the structs is a
+//! compile-time error rather than a field which is silently empty.
+//!
+//! ```
+//! use p_pest::{typed::{FromPair, Link}, Markdown, Rule};
+//! use pest::Parser;
+//!
+//! let pair = Markdown::parse(Rule::link, "[home](https://home.com)")
+//!     .unwrap()
+//!     .next()
+//!     .unwrap();
+//! let link = Link::from_pair(pair).unwrap();
+//!
+//! assert_eq!(link.text, "home");
+//! assert_eq!(link.dest, "https://home.com");
+//! ```
+
+use color_eyre::{eyre::eyre, Result};
+use pest::iterators::{Pair, Pairs};
+
+use crate::{ast::heading_title, Rule};
+
+/// A type which is converted from a pair of a specific rule.
+pub trait FromPair: Sized {
+    /// the rule whose pairs convert to this type
+    const RULE: Rule;
+
+    fn from_pair(pair: Pair<'_, Rule>) -> Result<Self>;
+}
+
+/// How a single matching pair converts to a field's value.
+pub trait FromMatch: Sized {
+    fn from_match(pair: Pair<'_, Rule>) -> Result<Self>;
+}
+
+impl FromMatch for String {
+    fn from_match(pair: Pair<'_, Rule>) -> Result<Self> {
+        Ok(pair.as_str().to_string())
+    }
+}
+
+impl<T: FromPair> FromMatch for T {
+    fn from_match(pair: Pair<'_, Rule>) -> Result<Self> {
+        T::from_pair(pair)
+    }
+}
+
+/// How the values of all the pairs found for a field are collected
+/// into the field; a field which isn't an `Option` or `Vec` is
+/// required.
+pub trait Field: Sized {
+    type Item;
+
+    fn from_items(field: &str, items: Vec<Self::Item>) -> Result<Self>;
+}
+
+impl<T> Field for Vec<T> {
+    type Item = T;
+
+    fn from_items(_field: &str, items: Vec<T>) -> Result<Self> {
+        Ok(items)
+    }
+}
+
+impl<T> Field for Option<T> {
+    type Item = T;
+
+    fn from_items(_field: &str, items: Vec<T>) -> Result<Self> {
+        Ok(items.into_iter().next())
+    }
+}
+
+macro_rules! required_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for $ty {
+                type Item = $ty;
+
+                fn from_items(field: &str, items: Vec<$ty>) -> Result<Self> {
+                    items
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| eyre!("the required field '{}' wasn't found", field))
+                }
+            }
+        )*
+    };
+}
+
+required_field!(String, u8);
+
+/// The descendants of `pair` whose rule is one of `rules`; the search
+/// doesn't descend into a pair whose rule is in `fields` (i.e., the
+/// rules of the struct's other fields) so that nested content (e.g.,
+/// the attributes of a tag _within_ a tag) isn't mistaken for the
+/// struct's own.
+#[doc(hidden)]
+pub fn matching<'a>(pair: &Pair<'a, Rule>, rules: &[Rule], fields: &[Rule]) -> Vec<Pair<'a, Rule>> {
+    let mut found = vec![];
+
+    for child in pair.clone().into_inner() {
+        if rules.contains(&child.as_rule()) {
+            found.push(child);
+        } else if !fields.contains(&child.as_rule()) {
+            found.extend(matching(&child, rules, fields));
+        }
+    }
+
+    found
+}
+
+/// Every pair in the tree which converts to `T`, in document order;
+/// pairs within a match (e.g., a link within a tag) are found too.
+pub fn find_all<T: FromPair>(pairs: Pairs<'_, Rule>) -> Result<Vec<T>> {
+    let mut found = vec![];
+
+    for pair in pairs {
+        if pair.as_rule() == T::RULE {
+            found.push(T::from_pair(pair.clone())?);
+        }
+        found.extend(find_all::<T>(pair.into_inner())?);
+    }
+
+    Ok(found)
+}
+
+/// Generates a struct along with its `FromPair` conversion.
+///
+/// The struct is converted from a pair of the rule given after `<-`
+/// and each field from the descendant pairs of the rules after `=`.
+/// A field's values are either converted with `FromMatch` or -- when
+/// given after `=>` -- a function which returns `None` for pairs which
+/// should be skipped. Every struct also has the `span` of its pair.
+///
+/// Rules are referenced as `Rule` variants so a struct which refers to
+/// a rule which isn't in the grammar doesn't compile:
+///
+/// ```compile_fail
+/// p_pest::typed! {
+///     pub struct Quote <- block_quote {
+///         pub lines: Vec<String> = quoted_line,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! typed {
+    ($(
+        $(#[$meta:meta])*
+        pub struct $name:ident <- $rule:ident {
+            $(
+                $(#[$field_meta:meta])*
+                pub $field:ident: $ty:ty = $($field_rule:ident)|+ $(=> $convert:path)?
+            ),* $(,)?
+        }
+    )*) => {
+        $(
+            $(#[$meta])*
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            pub struct $name {
+                $(
+                    $(#[$field_meta])*
+                    pub $field: $ty,
+                )*
+                /// where the pair was found in the source
+                pub span: $crate::typed::__private::Span,
+            }
+
+            impl $crate::typed::FromPair for $name {
+                const RULE: $crate::Rule = $crate::Rule::$rule;
+
+                fn from_pair(
+                    pair: $crate::typed::__private::Pair<'_, $crate::Rule>,
+                ) -> $crate::typed::__private::Result<Self> {
+                    if pair.as_rule() != Self::RULE {
+                        return Err($crate::typed::__private::eyre!(
+                            "a {} is converted from the '{:?}' rule rather than '{:?}'",
+                            stringify!($name),
+                            Self::RULE,
+                            pair.as_rule()
+                        ));
+                    }
+                    let fields: &[$crate::Rule] = &[$($($crate::Rule::$field_rule),+),*];
+                    let span = pair.as_span();
+
+                    Ok(Self {
+                        $(
+                            $field: {
+                                let found = $crate::typed::matching(
+                                    &pair,
+                                    &[$($crate::Rule::$field_rule),+],
+                                    fields,
+                                );
+                                let items = $crate::typed!(@items found $(, $convert)?);
+                                <$ty as $crate::typed::Field>::from_items(
+                                    stringify!($field),
+                                    items,
+                                )?
+                            },
+                        )*
+                        span: $crate::typed::__private::Span::new(span.start(), span.end()),
+                    })
+                }
+            }
+        )*
+    };
+    (@items $found:ident) => {
+        $found
+            .into_iter()
+            .map($crate::typed::FromMatch::from_match)
+            .collect::<$crate::typed::__private::Result<Vec<_>>>()?
+    };
+    (@items $found:ident, $convert:path) => {
+        $found.into_iter().filter_map($convert).collect::<Vec<_>>()
+    };
+}
+
+/// what the `typed!` macro refers to so that crates using it don't
+/// need dependencies of their own
+#[doc(hidden)]
+pub mod __private {
+    pub use color_eyre::{eyre::eyre, Result};
+    pub use parser::ast::Span;
+    pub use pest::iterators::Pair;
+}
+
+/// the level of a heading from which of `h1`-`h6` matched
+fn level(pair: Pair<'_, Rule>) -> Option<u8> {
+    match pair.as_rule() {
+        Rule::h1 => Some(1),
+        Rule::h2 => Some(2),
+        Rule::h3 => Some(3),
+        Rule::h4 => Some(4),
+        Rule::h5 => Some(5),
+        Rule::h6 => Some(6),
+        _ => None,
+    }
+}
+
+/// the title of a heading without its markers (or a closing sequence)
+fn title(pair: Pair<'_, Rule>) -> Option<String> {
+    Some(heading_title(pair.as_str()).1.to_string())
+}
+
+/// the content between the brackets of a link's description or
+/// destination
+fn bracketed(pair: Pair<'_, Rule>) -> Option<String> {
+    let text = pair.as_str();
+    Some(text[1..text.len() - 1].to_string())
+}
+
+fn non_empty(pair: Pair<'_, Rule>) -> Option<String> {
+    Some(pair.as_str().trim().to_string()).filter(|s| !s.is_empty())
+}
+
+typed! {
+    /// an ATX heading
+    pub struct Heading <- heading {
+        pub level: u8 = h1 | h2 | h3 | h4 | h5 | h6 => level,
+        pub text: String = h1 | h2 | h3 | h4 | h5 | h6 => title,
+    }
+
+    /// a fenced code block; the lines don't include their line endings
+    pub struct FencedCode <- fenced_code_block {
+        pub lang: Option<String> = fence_lang => non_empty,
+        pub info: Option<String> = fence_info => non_empty,
+        pub lines: Vec<String> = fenced_code_line,
+    }
+
+    /// an attribute of a tag; _boolean_ attributes have no value
+    pub struct Attr <- attr {
+        pub name: String = attr_name,
+        pub value: Option<String> = value | single_quoted_value | unquoted_value,
+    }
+
+    /// an HTML-like tag; self-closing tags have no inner content
+    pub struct Tag <- tag {
+        pub name: String = tagname,
+        pub attrs: Vec<Attr> = attr,
+        pub inner: Option<String> = inner,
+    }
+
+    /// a Markdown link
+    pub struct Link <- link {
+        pub text: String = link_desc => bracketed,
+        pub dest: String = link_uri => bracketed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Markdown;
+    use parser::ast::Span;
+    use pest::Parser;
+
+    fn parse<T: FromPair>(content: &str) -> T {
+        let pair = Markdown::parse(T::RULE, content).unwrap().next().unwrap();
+        T::from_pair(pair).unwrap()
+    }
+
+    #[test]
+    fn pairs_convert_to_typed_structs() {
+        let heading: Heading = parse("### Foo bar ##\n");
+        assert_eq!((heading.level, heading.text.as_str()), (3, "Foo bar"));
+        let heading: Heading = parse("# C# #\n");
+        assert_eq!(heading.text, "C#");
+
+        let code: FencedCode = parse("```rust { class: \"big\" }\nlet x = 1;\n```\n");
+        assert_eq!(code.lang.as_deref(), Some("rust"));
+        assert_eq!(code.info.as_deref(), Some("{ class: \"big\" }"));
+        assert_eq!(code.lines, vec!["let x = 1;"]);
+
+        let plain: FencedCode = parse("```\n```\n");
+        assert_eq!((plain.lang, plain.info), (None, None));
+
+        let tag: Tag = parse("<my-tag a=\"1\" b='2' c><my-tag d=\"x\"></my-tag></my-tag>");
+        assert_eq!(tag.name, "my-tag");
+        assert_eq!(
+            tag.attrs
+                .iter()
+                .map(|a| (a.name.as_str(), a.value.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![("a", Some("1")), ("b", Some("2")), ("c", None)]
+        );
+        assert_eq!(tag.inner.as_deref(), Some("<my-tag d=\"x\"></my-tag>"));
+        assert_eq!(tag.span, Span::new(0, 54));
+
+        let closed: Tag = parse("<br />");
+        assert_eq!((closed.name.as_str(), closed.inner), ("br", None));
+    }
+
+    #[test]
+    fn pairs_of_other_rules_are_an_error() {
+        let pair = Markdown::parse(Rule::heading, "# Foo\n")
+            .unwrap()
+            .next()
+            .unwrap();
+
+        assert!(Link::from_pair(pair).is_err());
+    }
+
+    #[test]
+    fn rule_chains_convert_to_typed_structs() {
+        let chain = crate::RuleChain::parse(Rule::heading, "## Foo\n").unwrap();
+        let heading: Heading = chain.typed().unwrap();
+
+        assert_eq!((heading.level, heading.text.as_str()), (2, "Foo"));
+    }
+
+    #[test]
+    fn all_matches_are_found() {
+        let pairs = Markdown::parse(Rule::inlines, "[a](/a) and [b](/b)").unwrap();
+        let links: Vec<Link> = find_all(pairs).unwrap();
+
+        assert_eq!(
+            links.iter().map(|l| l.dest.as_str()).collect::<Vec<_>>(),
+            vec!["/a", "/b"]
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use p_pest::snapshot::{check, fixtures, Outcome};
+
+/// Parses every fixture in `tests/fixtures/<rule>` with its rule and
+/// compares the result with its committed snapshot; review the
+/// snapshots which don't match with `cargo run -p cli -- snapshots`.
+#[test]
+fn fixtures_match_their_snapshots() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let update = std::env::var("UPDATE_SNAPSHOTS").is_ok_and(|v| v == "1");
+
+    let mut pending = vec![];
+    for fixture in fixtures(&dir).unwrap() {
+        if check(&fixture, update).unwrap() == Outcome::Pending {
+            pending.push(fixture.pending_path());
+        }
+    }
+
+    assert!(
+        pending.is_empty(),
+        "these snapshots don't match and are waiting for review:\n{:#?}",
+        pending
+    );
+}
@@ -1,14 +1,322 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+//! Markdown parsed by **pulldown-cmark** as the events of
+//! `parser::event`, so its output goes through the same renderers and
+//! transforms as the other backends.
+//!
+//! ```
+//! use p_pulldown::Events;
+//! use parser::event::to_html;
+//!
+//! let html = to_html(Events::new("# Foo\n\n*bar*\n")).unwrap();
+//!
+//! assert_eq!(html, "<h1>Foo</h1>\n<p><em>bar</em></p>");
+//! ```
+
+use std::{collections::VecDeque, ops::Range};
+
+use parser::{
+    ast::Span,
+    event::{Event, Tag},
+    html::escape,
+};
+use pulldown_cmark::{
+    CodeBlockKind, Event as CmarkEvent, OffsetIter, Options, Parser, Tag as CmarkTag,
+};
+
+fn element(name: &str) -> Tag {
+    Tag::Element {
+        name: name.to_string(),
+        attrs: vec![],
+    }
+}
+
+/// the tag for one of pulldown-cmark's; those the tree has no node for
+/// (e.g., emphasis) become the HTML element they're rendered as; a
+/// table's cells are headers when they're `in_head`
+fn tag(tag: CmarkTag, content: &str, range: Range<usize>, in_head: bool) -> Tag {
+    match tag {
+        CmarkTag::Paragraph => Tag::Paragraph,
+        CmarkTag::Heading(level, id, _) => Tag::Heading {
+            level: level as u8,
+            id: id.map(String::from),
+        },
+        CmarkTag::BlockQuote => Tag::BlockQuote,
+        CmarkTag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            let (lang, info) = info.trim().split_once(' ').unwrap_or((&info, ""));
+            let some = |s: &str| (!s.trim().is_empty()).then(|| s.trim().to_string());
+            Tag::CodeBlock {
+                lang: some(lang),
+                info: some(info),
+            }
+        }
+        CmarkTag::CodeBlock(CodeBlockKind::Indented) => Tag::CodeBlock {
+            lang: None,
+            info: None,
+        },
+        CmarkTag::List(start) => Tag::List {
+            ordered: start.is_some(),
+        },
+        // pulldown-cmark doesn't keep the marker but an item starts with it
+        CmarkTag::Item => Tag::ListItem {
+            marker: content[range]
+                .split_whitespace()
+                .next()
+                .unwrap_or("-")
+                .to_string(),
+        },
+        CmarkTag::FootnoteDefinition(label) => Tag::Element {
+            name: "div".to_string(),
+            attrs: vec![
+                ("class".to_string(), "footnote-definition".to_string()),
+                ("id".to_string(), label.to_string()),
+            ],
+        },
+        CmarkTag::Table(_) => element("table"),
+        CmarkTag::TableHead => element("thead"),
+        CmarkTag::TableRow => element("tr"),
+        CmarkTag::TableCell if in_head => element("th"),
+        CmarkTag::TableCell => element("td"),
+        CmarkTag::Emphasis => element("em"),
+        CmarkTag::Strong => element("strong"),
+        CmarkTag::Strikethrough => element("del"),
+        CmarkTag::Link(_, dest, _) => Tag::Link {
+            dest: dest.to_string(),
+        },
+        CmarkTag::Image(_, src, _) => Tag::Image {
+            src: src.to_string(),
+        },
+    }
+}
+
+/// whether the event is inline content (rather than a block)
+fn is_inline(event: &CmarkEvent) -> bool {
+    match event {
+        CmarkEvent::Start(tag) | CmarkEvent::End(tag) => matches!(
+            tag,
+            CmarkTag::Emphasis
+                | CmarkTag::Strong
+                | CmarkTag::Strikethrough
+                | CmarkTag::Link(..)
+                | CmarkTag::Image(..)
+        ),
+        CmarkEvent::Rule => false,
+        _ => true,
+    }
+}
+
+/// The events pulldown-cmark produces for the content; the blocks are
+/// within a `Document` just as they are in the tree.
+pub struct Events<'a> {
+    content: &'a str,
+    inner: OffsetIter<'a, 'a>,
+    /// the events which are ready to be read
+    queue: VecDeque<(Event, Span)>,
+    /// whether each of the tags which are open is a list item
+    items: Vec<bool>,
+    /// the inline content of a "tight" list item; pulldown-cmark doesn't
+    /// put it in a paragraph but the tree does
+    tight: Option<Vec<(Event, Span)>>,
+    /// the code of the code block being read; pulldown-cmark splits it
+    /// into a `Text` per line but the tree has a single one
+    code: Option<(String, Option<Span>)>,
+    /// whether the events are within a table's head
+    in_head: bool,
+    started: bool,
+    ended: bool,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(content: &'a str) -> Self {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_HEADING_ATTRIBUTES;
+
+        Self {
+            content,
+            inner: Parser::new_ext(content, options).into_offset_iter(),
+            queue: VecDeque::new(),
+            items: vec![],
+            tight: None,
+            code: None,
+            in_head: false,
+            started: false,
+            ended: false,
+        }
+    }
+
+    fn emit(&mut self, event: Event, span: Span) {
+        match &mut self.tight {
+            Some(tight) => tight.push((event, span)),
+            None => self.queue.push_back((event, span)),
+        }
+    }
+
+    /// wraps the inline content of a tight list item in a paragraph
+    fn close_tight(&mut self) {
+        let Some(tight) = self.tight.take() else {
+            return;
+        };
+        if let (Some((_, first)), Some((_, last))) = (tight.first(), tight.last()) {
+            let span = Span::new(first.start, last.end);
+            self.queue.push_back((Event::Start(Tag::Paragraph), span));
+            self.queue.extend(tight);
+            self.queue.push_back((Event::End(Tag::Paragraph), span));
+        }
+    }
+
+    fn read(&mut self, event: CmarkEvent, range: Range<usize>) {
+        let span = Span::new(range.start, range.end);
+        match is_inline(&event) {
+            true if self.tight.is_none() && self.items.last() == Some(&true) => {
+                self.tight = Some(vec![])
+            }
+            true => (),
+            false => self.close_tight(),
+        }
+
+        let event = match event {
+            CmarkEvent::Start(t) => {
+                self.in_head |= matches!(t, CmarkTag::TableHead);
+                let t = tag(t, self.content, range, self.in_head);
+                self.items.push(matches!(t, Tag::ListItem { .. }));
+                if let Tag::CodeBlock { .. } = t {
+                    self.code = Some((String::new(), None));
+                }
+                Event::Start(t)
+            }
+            CmarkEvent::End(t) => {
+                self.items.pop();
+                if let Some((code, code_span)) = self.code.take() {
+                    let text = code.strip_suffix('\n').unwrap_or(&code);
+                    let code_span = match code_span {
+                        Some(s) => Span::new(s.start, s.end - (code.len() - text.len())),
+                        None => Span::new(span.end, span.end),
+                    };
+                    self.emit(Event::Text(text.to_string()), code_span);
+                }
+                self.in_head &= !matches!(t, CmarkTag::TableHead);
+                Event::End(tag(t, self.content, range, self.in_head))
+            }
+            CmarkEvent::Text(text) => match &mut self.code {
+                Some((code, code_span)) => {
+                    code.push_str(&text);
+                    let start = code_span.map_or(span.start, |s| s.start);
+                    *code_span = Some(Span::new(start, span.end));
+                    return;
+                }
+                None => Event::Text(text.to_string()),
+            },
+            CmarkEvent::Code(code) => Event::Code(code.to_string()),
+            CmarkEvent::Html(html) => Event::Html(html.to_string()),
+            CmarkEvent::FootnoteReference(label) => Event::Html(format!(
+                "<sup class=\"footnote-reference\"><a href=\"#{}\">{}</a></sup>",
+                escape(&label),
+                escape(&label)
+            )),
+            CmarkEvent::SoftBreak => Event::SoftBreak,
+            CmarkEvent::HardBreak => Event::HardBreak,
+            CmarkEvent::Rule => Event::Rule,
+            CmarkEvent::TaskListMarker(checked) => Event::Html(match checked {
+                true => "<input type=\"checkbox\" disabled checked /> ".to_string(),
+                false => "<input type=\"checkbox\" disabled /> ".to_string(),
+            }),
+        };
+
+        self.emit(event, span);
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = (Event, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let document = Span::new(0, self.content.len());
+        if !self.started {
+            self.started = true;
+            return Some((Event::Start(Tag::Document), document));
+        }
+
+        while self.queue.is_empty() {
+            match self.inner.next() {
+                Some((event, range)) => self.read(event, range),
+                None => break,
+            }
+        }
+        if let Some(event) = self.queue.pop_front() {
+            return Some(event);
+        }
+
+        match self.ended {
+            true => None,
+            false => {
+                self.ended = true;
+                Some((Event::End(Tag::Document), document))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parser::{
+        ast::{Node, NodeKind},
+        event::{to_html, to_tree},
+    };
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn events_build_the_shared_tree() {
+        let md = "# Foo {#top}\n\n3. a\n4. b\n\n```rs\nlet x = 1;\nlet y = 2;\n```\n";
+        let tree = to_tree(Events::new(md)).unwrap();
+        let kinds: Vec<&NodeKind> = tree.children.iter().map(|c| &c.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                &NodeKind::Heading {
+                    level: 1,
+                    id: Some("top".to_string())
+                },
+                &NodeKind::List { ordered: true },
+                &NodeKind::CodeBlock {
+                    lang: Some("rs".to_string()),
+                    info: None
+                },
+            ]
+        );
+        assert_eq!(
+            tree.children[1].children[1].kind,
+            NodeKind::ListItem {
+                marker: "4.".to_string()
+            }
+        );
+        assert_eq!(
+            tree.children[2].children,
+            vec![Node::text("let x = 1;\nlet y = 2;", Span::new(31, 52))]
+        );
+    }
+
+    #[test]
+    fn table_head_cells_are_headers() {
+        let html = to_html(Events::new("| a | b |\n|---|---|\n| c | d |\n")).unwrap();
+
+        assert!(html.contains("<th>a</th>"), "{}", html);
+        assert!(html.contains("<td>c</td>"), "{}", html);
+        assert!(!html.contains("<th>c</th>"), "{}", html);
+    }
+
+    #[test]
+    fn events_render_with_the_shared_renderer() {
+        let md = "3. `a` and ~~b~~\n4. [c](https://c.com)\n";
+
+        assert_eq!(
+            to_html(Events::new(md)).unwrap(),
+            concat!(
+                "<ol start=\"3\">\n",
+                "<li><code>a</code> and <del>b</del></li>\n",
+                "<li><a href=\"https://c.com\">c</a></li>\n",
+                "</ol>"
+            )
+        );
     }
 }
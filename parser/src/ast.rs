@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+/// A byte range into the source content which a node
+/// (or diagnostic) was derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    /// byte index where the span starts (inclusive)
+    pub start: usize,
+    /// byte index where the span ends (exclusive)
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// moves the span _forward_ by the given offset; useful
+    /// when a sub-section of a document was parsed on its own
+    pub fn offset(&self, offset: usize) -> Self {
+        Self {
+            start: self.start + offset,
+            end: self.end + offset,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// The _kind_ of node found in the tree along with any
+/// data which is specific to that kind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    /// the root of a parsed Markdown document
+    Document,
+    /// a heading with a level of 1-6; the `id` is the heading's
+    /// anchor which is assigned once the whole document is known
+    /// (see `slug::assign_ids`)
+    Heading {
+        level: u8,
+        id: Option<String>,
+    },
+    Paragraph,
+    ThematicBreak,
+    /// an indented or fenced code block; the code itself
+    /// is held as a `Text` child
+    CodeBlock {
+        lang: Option<String>,
+        info: Option<String>,
+    },
+    /// an HTML-like tag; for non-self-closing tags the
+    /// children represent the tag's _inner_ content
+    Tag {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    /// a bullet or ordered list whose children are `ListItem`s
+    List {
+        ordered: bool,
+    },
+    /// an item in a list along with the marker used (e.g., `-`, `1.`)
+    ListItem {
+        marker: String,
+    },
+    /// a block quote whose children are the quoted blocks
+    BlockQuote,
+    /// a link; the children are the link's description
+    Link {
+        dest: String,
+    },
+    /// an image; the children are the image's _alt text_
+    Image {
+        src: String,
+    },
+    /// an inline emoji (e.g., `::smile::`)
+    Emoji {
+        name: String,
+    },
+    /// an emoji which is a block element and may have
+    /// HTML attributes (e.g., `::smile { class: "big" }`)
+    BlockEmoji {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    /// a placeholder (`[[toc]]` or `<!-- toc -->`) for the
+    /// document's table of contents
+    Toc,
+    /// a line ending within inline content which is rendered as
+    /// whitespace (or a newline)
+    SoftBreak,
+    /// a line ending preceded by two or more spaces (or a backslash)
+    /// which is rendered as a line break
+    HardBreak,
+    /// raw HTML (e.g., a comment or an HTML block) which is
+    /// passed through as is
+    Html(String),
+    /// literal text
+    Text(String),
+}
+
+/// A node in the tree produced by a parser.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Self {
+            kind,
+            span,
+            children: vec![],
+        }
+    }
+
+    pub fn with_children(kind: NodeKind, span: Span, children: Vec<Node>) -> Self {
+        Self {
+            kind,
+            span,
+            children,
+        }
+    }
+
+    /// a leaf node of literal text
+    pub fn text(text: &str, span: Span) -> Self {
+        Self::new(NodeKind::Text(text.to_string()), span)
+    }
+
+    /// Whether the node is block-level content; a tag is block-level
+    /// when its inner content contains blocks (e.g., the Markdown
+    /// parsed from within a block tag).
+    pub fn is_block(&self) -> bool {
+        match self.kind {
+            NodeKind::Text(_)
+            | NodeKind::SoftBreak
+            | NodeKind::HardBreak
+            | NodeKind::Html(_)
+            | NodeKind::Link { .. }
+            | NodeKind::Image { .. }
+            | NodeKind::Emoji { .. } => false,
+            NodeKind::Tag { .. } => self.children.iter().any(|c| c.is_block()),
+            _ => true,
+        }
+    }
+
+    /// the concatenated text of all `Text` nodes in this
+    /// node's subtree
+    pub fn text_content(&self) -> String {
+        match &self.kind {
+            NodeKind::Text(text) => text.clone(),
+            NodeKind::SoftBreak | NodeKind::HardBreak => "\n".to_string(),
+            _ => self
+                .children
+                .iter()
+                .map(|c| c.text_content())
+                .collect::<Vec<String>>()
+                .concat(),
+        }
+    }
+
+    /// Whether two trees are _equivalent_; this ignores the spans of
+    /// the nodes, treats any run of whitespace in text (including soft
+    /// breaks) as a single space and only compares the _number_ of a list item's marker
+    /// (e.g., `-` is equivalent to `*` and `2.` to `2)`).
+    pub fn is_equivalent(&self, other: &Node) -> bool {
+        let kinds_match = match (&self.kind, &other.kind) {
+            (NodeKind::Text(a), NodeKind::Text(b)) => {
+                a.split_whitespace().eq(b.split_whitespace())
+            }
+            (NodeKind::ListItem { marker: a }, NodeKind::ListItem { marker: b }) => {
+                a.trim_end_matches(['.', ')', '-', '+', '*'])
+                    == b.trim_end_matches(['.', ')', '-', '+', '*'])
+            }
+            (a, b) => a == b,
+        };
+
+        let (children, other_children) = (merged(&self.children), merged(&other.children));
+
+        kinds_match
+            && children.len() == other_children.len()
+            && children
+                .iter()
+                .zip(other_children.iter())
+                .all(|(a, b)| a.is_equivalent(b))
+    }
+}
+
+/// The nodes with any adjacent `Text` and `SoftBreak` nodes merged into
+/// a single `Text` node; a soft break is only whitespace so wrapping
+/// text differently doesn't change its meaning.
+fn merged(nodes: &[Node]) -> Vec<Node> {
+    let mut merged: Vec<Node> = vec![];
+
+    for node in nodes {
+        let text = match &node.kind {
+            NodeKind::Text(text) => Some(text.as_str()),
+            NodeKind::SoftBreak => Some("\n"),
+            _ => None,
+        };
+        match (text, merged.last_mut()) {
+            (
+                Some(text),
+                Some(Node {
+                    kind: NodeKind::Text(last),
+                    ..
+                }),
+            ) => last.push_str(text),
+            (Some(text), _) => merged.push(Node::text(text, node.span)),
+            (None, _) => merged.push(node.clone()),
+        }
+    }
+
+    merged
+}
+
+/// Builders for the trees used by the tests; the nodes have a default
+/// span unless one is set with `at`.
+#[cfg(test)]
+pub(crate) mod build {
+    use super::{Node, NodeKind, Span};
+
+    pub(crate) fn node(kind: NodeKind, children: Vec<Node>) -> Node {
+        Node::with_children(kind, Span::default(), children)
+    }
+
+    pub(crate) fn text(text: &str) -> Node {
+        Node::text(text, Span::default())
+    }
+
+    /// a link whose text is its destination
+    pub(crate) fn link(dest: &str) -> Node {
+        node(
+            NodeKind::Link {
+                dest: dest.to_string(),
+            },
+            vec![text(dest)],
+        )
+    }
+
+    impl Node {
+        /// the node with its span set to `start..end`
+        pub(crate) fn at(mut self, start: usize, end: usize) -> Self {
+            self.span = Span::new(start, end);
+            self
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Custom components: HTML-like tags (e.g., `<block-component>`)
+//! which are rendered by Rust callbacks rather than passed through.
+
+use std::collections::HashMap;
+
+use crate::ast::Node;
+
+/// A custom tag found in the tree which is about to be rendered.
+pub struct Component<'a> {
+    /// the tag's name
+    pub name: &'a str,
+    /// the tag's attributes, in the order they appear
+    pub attrs: &'a [(String, String)],
+    /// the tag's _parsed_ inner content
+    pub children: &'a [Node],
+    /// the inner content already rendered as HTML
+    pub inner: String,
+}
+
+impl<'a> Component<'a> {
+    /// the value of the named attribute
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+type Render = dyn Fn(&Component) -> String + Send + Sync;
+
+/// The components which can be used in a document, keyed by tag name.
+///
+/// ```
+/// use parser::component::ComponentRegistry;
+///
+/// let mut registry = ComponentRegistry::new();
+/// registry.register("callout", |c| {
+///     let kind = c.attr("kind").unwrap_or("note");
+///     format!("<aside class=\"{}\">{}</aside>", kind, c.inner)
+/// });
+/// assert!(registry.contains("callout"));
+/// ```
+#[derive(Default)]
+pub struct ComponentRegistry {
+    components: HashMap<String, Box<Render>>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registers the callback which renders the tag `name`; any
+    /// existing callback for the tag is replaced
+    pub fn register<F>(&mut self, name: &str, render: F) -> &mut Self
+    where
+        F: Fn(&Component) -> String + Send + Sync + 'static,
+    {
+        self.components.insert(name.to_string(), Box::new(render));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.components.contains_key(name)
+    }
+
+    /// the names of the registered components
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.components.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// renders the component, or `None` when there's no callback
+    /// registered for it
+    pub fn render(&self, component: &Component) -> Option<String> {
+        self.components
+            .get(component.name)
+            .map(|render| render(component))
+    }
+}
+
+impl std::fmt::Debug for ComponentRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentRegistry")
+            .field("components", &self.names())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_are_rendered_by_name() {
+        let mut registry = ComponentRegistry::new();
+        registry
+            .register("my-inline", |c| format!("<b>{}</b>", c.inner))
+            .register("BlockComponent", |c| {
+                format!(
+                    "<div data-size=\"{}\"></div>",
+                    c.attr("size").unwrap_or("0")
+                )
+            });
+        let attrs = vec![("size".to_string(), "2".to_string())];
+        let component = |name| Component {
+            name,
+            attrs: &attrs,
+            children: &[],
+            inner: "foo".to_string(),
+        };
+
+        assert_eq!(registry.names(), vec!["BlockComponent", "my-inline"]);
+        assert_eq!(
+            registry.render(&component("my-inline")).as_deref(),
+            Some("<b>foo</b>")
+        );
+        assert_eq!(
+            registry.render(&component("BlockComponent")).as_deref(),
+            Some("<div data-size=\"2\"></div>")
+        );
+        assert_eq!(registry.render(&component("other")), None);
+    }
+}
@@ -0,0 +1,227 @@
+//! A _lossless_ concrete syntax tree.
+//!
+//! Unlike the `ast`, every byte of the source content is attributable
+//! to a token in the tree; this includes the whitespace, line endings
+//! and markers which a grammar typically throws away. Concatenating the
+//! text of the tokens -- in order -- reproduces the source exactly.
+//!
+//! Whitespace and markers are only separate tokens where the grammar
+//! leaves them out of a rule's match (or, for headings, where they're
+//! split out explicitly); the content of other atomic rules -- such as
+//! a `thematic_break` -- is a single `Text` token, markers included.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::Span;
+
+/// The _kind_ of a token in the syntax tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenKind {
+    /// content matched by a (non-silent) rule in the grammar
+    Text,
+    /// a run of spaces and/or tabs
+    Whitespace,
+    /// a line ending (`\n` or `\r\n`)
+    Newline,
+    /// any other trivia, typically syntax markers like the `#`
+    /// of a heading
+    Marker,
+    /// content which the parser was unable to parse
+    Unparsed,
+}
+
+impl TokenKind {
+    /// whether the token is _trivia_ which the grammar would
+    /// normally discard
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Marker
+        )
+    }
+}
+
+/// A leaf in the syntax tree which owns a slice of the source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub text: String,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, text: &str, start: usize) -> Self {
+        Self {
+            kind,
+            span: Span::new(start, start + text.len()),
+            text: text.to_string(),
+        }
+    }
+
+    /// Splits a section of content -- which no rule claimed -- into
+    /// whitespace, newline and marker tokens; `start` is where the
+    /// content starts in the source.
+    pub fn trivia(content: &str, start: usize) -> Vec<Token> {
+        let kind_of = |c: char| match c {
+            ' ' | '\t' => TokenKind::Whitespace,
+            '\r' | '\n' => TokenKind::Newline,
+            _ => TokenKind::Marker,
+        };
+        let mut tokens: Vec<Token> = vec![];
+        let mut run_start = 0;
+
+        for (idx, c) in content.char_indices() {
+            let kind = kind_of(c);
+            let prior = content[run_start..idx].chars().last();
+            let continues = match prior {
+                // a newline token is a single line ending
+                Some('\r') => c == '\n',
+                Some('\n') => false,
+                Some(p) => kind_of(p) == kind,
+                None => true,
+            };
+            if !continues {
+                let text = &content[run_start..idx];
+                tokens.push(Token::new(kind_of(prior.unwrap()), text, start + run_start));
+                run_start = idx;
+            }
+        }
+        if let Some(last) = content[run_start..].chars().last() {
+            tokens.push(Token::new(
+                kind_of(last),
+                &content[run_start..],
+                start + run_start,
+            ));
+        }
+
+        tokens
+    }
+}
+
+/// An element of the syntax tree; either a node for a rule
+/// in the grammar or a token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(Token),
+}
+
+impl CstElement {
+    pub fn span(&self) -> Span {
+        match self {
+            CstElement::Node(node) => node.span,
+            CstElement::Token(token) => token.span,
+        }
+    }
+}
+
+/// A node in the syntax tree representing a rule matched by
+/// the grammar; its children cover its span without gaps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CstNode {
+    /// the name of the grammar's rule
+    pub rule: String,
+    pub span: Span,
+    pub children: Vec<CstElement>,
+}
+
+impl CstNode {
+    pub fn new(rule: &str, span: Span, children: Vec<CstElement>) -> Self {
+        Self {
+            rule: rule.to_string(),
+            span,
+            children,
+        }
+    }
+
+    /// all tokens in the node's subtree, in source order
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = vec![];
+        for child in &self.children {
+            match child {
+                CstElement::Node(node) => tokens.extend(node.tokens()),
+                CstElement::Token(token) => tokens.push(token),
+            }
+        }
+
+        tokens
+    }
+
+    /// the source text of the node, including all trivia
+    pub fn text(&self) -> String {
+        self.tokens()
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect::<Vec<&str>>()
+            .concat()
+    }
+
+    /// all nodes in the subtree (including this one) which
+    /// were produced by the given rule
+    pub fn find_all(&self, rule: &str) -> Vec<&CstNode> {
+        let mut found = vec![];
+        if self.rule == rule {
+            found.push(self);
+        }
+        for child in &self.children {
+            if let CstElement::Node(node) = child {
+                found.extend(node.find_all(rule));
+            }
+        }
+
+        found
+    }
+
+    /// the token which contains the given byte offset
+    pub fn token_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens()
+            .into_iter()
+            .find(|t| t.span.start <= offset && offset < t.span.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trivia_is_split_by_kind() {
+        let tokens = Token::trivia("## \t\r\n\n", 4);
+        let kinds: Vec<TokenKind> = tokens.iter().map(|t| t.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Marker,
+                TokenKind::Whitespace,
+                TokenKind::Newline,
+                TokenKind::Newline
+            ]
+        );
+        assert_eq!(tokens[0].span, Span::new(4, 6));
+        assert_eq!(tokens[2].text, "\r\n");
+        assert_eq!(tokens[3].span, Span::new(10, 11));
+    }
+
+    #[test]
+    fn node_text_is_the_concatenated_tokens() {
+        let node = CstNode::new(
+            "h1",
+            Span::new(0, 6),
+            vec![
+                CstElement::Token(Token::new(TokenKind::Marker, "#", 0)),
+                CstElement::Token(Token::new(TokenKind::Whitespace, " ", 1)),
+                CstElement::Node(CstNode::new(
+                    "title",
+                    Span::new(2, 5),
+                    vec![CstElement::Token(Token::new(TokenKind::Text, "Foo", 2))],
+                )),
+                CstElement::Token(Token::new(TokenKind::Newline, "\n", 5)),
+            ],
+        );
+
+        assert_eq!(node.text(), "# Foo\n");
+        assert_eq!(node.find_all("title").len(), 1);
+        assert_eq!(node.token_at(3).map(|t| t.kind), Some(TokenKind::Text));
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// A problem found while processing a document which
+/// did not prevent processing from completing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// the section of the source content the diagnostic
+    /// refers to
+    pub span: Span,
+    /// an identifier for the kind of problem found (e.g., a
+    /// lint rule's ID)
+    pub code: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn warning(message: &str, span: Span) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.to_string(),
+            span,
+            code: None,
+        }
+    }
+
+    pub fn error(message: &str, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.to_string(),
+            span,
+            code: None,
+        }
+    }
+
+    /// sets the diagnostic's `code`
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(
+                f,
+                "{} {} [{}..{}]: {}",
+                self.severity, code, self.span.start, self.span.end, self.message
+            ),
+            None => write!(
+                f,
+                "{} [{}..{}]: {}",
+                self.severity, self.span.start, self.span.end, self.message
+            ),
+        }
+    }
+}
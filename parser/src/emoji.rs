@@ -0,0 +1,37 @@
+//! The emoji which can be referenced by name (e.g., `::smile::`).
+
+/// The supported emoji; each entry is a shortcode along with the
+/// emoji character it maps to. A single emoji may have several
+/// shortcodes to allow for different casing conventions.
+pub const EMOJI: &[(&str, &str)] = &[
+    ("smile", "😀"),
+    ("cry", "😢"),
+    ("thumbs_up", "👍"),
+    ("thumbs-up", "👍"),
+    ("thumbsUp", "👍"),
+    ("ThumbsUp", "👍"),
+    ("thumbs_down", "👎"),
+    ("thumbs-down", "👎"),
+];
+
+/// the emoji character for a shortcode (if it's a known emoji)
+pub fn lookup(name: &str) -> Option<&'static str> {
+    EMOJI.iter().find(|(n, _)| *n == name).map(|(_, e)| *e)
+}
+
+/// all shortcodes which start with the given prefix
+pub fn matching(prefix: &str) -> impl Iterator<Item = &'static (&'static str, &'static str)> + '_ {
+    EMOJI.iter().filter(move |(n, _)| n.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_map_to_the_same_emoji() {
+        assert_eq!(lookup("thumbs_up"), lookup("ThumbsUp"));
+        assert_eq!(lookup("frown"), None);
+        assert_eq!(matching("thumbs_").count(), 2);
+    }
+}
@@ -0,0 +1,156 @@
+//! Decodes [entity](https://spec.commonmark.org/0.31.2/#entity-and-numeric-character-references)
+//! and numeric character references (e.g., `&amp;`, `&#35;`, `&#x22;`).
+
+use std::{borrow::Cow, collections::HashMap, sync::OnceLock};
+
+/// the longest named reference is `&CounterClockwiseContourIntegral;`
+const MAX_REFERENCE_LEN: usize = 33;
+
+/// the HTML5 named references (including the `&` and `;`) and the
+/// characters they decode to; the legacy names which can be used
+/// without a trailing `;` aren't valid in Markdown
+fn named() -> &'static HashMap<&'static str, &'static str> {
+    static NAMED: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+
+    NAMED.get_or_init(|| {
+        entities::ENTITIES
+            .iter()
+            .filter(|e| e.entity.ends_with(';'))
+            .map(|e| (e.entity, e.characters))
+            .collect()
+    })
+}
+
+/// a numeric reference's code point as a character; `0` and
+/// invalid code points are replaced with U+FFFD
+fn code_point(digits: &str, radix: u32) -> char {
+    u32::from_str_radix(digits, radix)
+        .ok()
+        .filter(|c| *c != 0)
+        .and_then(char::from_u32)
+        .unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The characters which a single reference (e.g., `&amp;`) decodes
+/// to or `None` when it isn't a valid reference.
+pub fn decode_reference(reference: &str) -> Option<Cow<'static, str>> {
+    let body = reference.strip_prefix('&')?.strip_suffix(';')?;
+    let numeric = |digits: &str, max: usize, radix: u32| {
+        let valid = (1..=max).contains(&digits.len()) && digits.chars().all(|c| c.is_digit(radix));
+        valid.then(|| Cow::Owned(code_point(digits, radix).to_string()))
+    };
+
+    match body.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => numeric(&hex[1..], 6, 16),
+        Some(dec) => numeric(dec, 7, 10),
+        None => named().get(reference).map(|c| Cow::Borrowed(*c)),
+    }
+}
+
+/// the reference which starts at the `&` found at `idx` along with
+/// what it decodes to
+fn reference_at(text: &str, idx: usize) -> Option<(&str, Cow<'static, str>)> {
+    let rest = &text[idx..];
+    let end = rest
+        .char_indices()
+        .take(MAX_REFERENCE_LEN)
+        .find(|(_, c)| *c == ';')?
+        .0;
+    let reference = &rest[..=end];
+
+    decode_reference(reference).map(|decoded| (reference, decoded))
+}
+
+/// Replaces every valid reference in the text with the characters it
+/// refers to; anything which only _looks_ like a reference (e.g.,
+/// `&foo;`) is kept as is.
+pub fn decode(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find('&') {
+        let idx = pos + found;
+        decoded.push_str(&text[pos..idx]);
+        match reference_at(text, idx) {
+            Some((reference, characters)) => {
+                decoded.push_str(&characters);
+                pos = idx + reference.len();
+            }
+            None => {
+                decoded.push('&');
+                pos = idx + 1;
+            }
+        }
+    }
+    decoded.push_str(&text[pos..]);
+
+    Cow::Owned(decoded)
+}
+
+/// The inverse of `decode` for text which is written back out as
+/// Markdown; an `&` which would start a valid reference is escaped
+/// as `&amp;` so the text decodes back to itself.
+pub fn escape_references(text: &str) -> Cow<'_, str> {
+    let references: Vec<usize> = text
+        .match_indices('&')
+        .map(|(idx, _)| idx)
+        .filter(|idx| reference_at(text, *idx).is_some())
+        .collect();
+    if references.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() + references.len() * 4);
+    let mut pos = 0;
+    for idx in references {
+        escaped.push_str(&text[pos..idx]);
+        escaped.push_str("&amp;");
+        pos = idx + 1;
+    }
+    escaped.push_str(&text[pos..]);
+
+    Cow::Owned(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_are_decoded() {
+        assert_eq!(
+            decode("&nbsp; &amp; &copy; &AElig; &Dcaron; &frac34; &ngE;"),
+            "\u{a0} & © Æ Ď ¾ \u{2267}\u{338}"
+        );
+        assert_eq!(decode("&#35; &#1234; &#992; &#0;"), "# Ӓ Ϡ \u{fffd}");
+        assert_eq!(decode("&#X22; &#XD06; &#xcab;"), "\" ആ ಫ");
+        assert_eq!(decode("&#xD800; &#x110000;"), "\u{fffd} \u{fffd}");
+    }
+
+    #[test]
+    fn invalid_references_are_kept() {
+        for text in [
+            "&nbsp &x; &#; &#x;",
+            "&#87654321;",
+            "&#abcdef0;",
+            "&ThisIsNotDefined; &hi?;",
+            "&copy",
+            "&MadeUpEntity;",
+        ] {
+            assert_eq!(decode(text), text);
+        }
+        assert!(matches!(decode("no references"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn escaped_references_decode_to_the_original() {
+        let text = "AT&T &amp; &copy &copy; &#35;";
+        let escaped = escape_references(text);
+
+        assert_eq!(escaped, "AT&T &amp;amp; &copy &amp;copy; &amp;#35;");
+        assert_eq!(decode(&escaped), text);
+    }
+}
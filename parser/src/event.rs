@@ -0,0 +1,500 @@
+//! The tree as a flat stream of events -- the start and end of every
+//! container with the leaves in between -- in the style of
+//! pulldown-cmark's `Iterator<Item = Event>`.
+//!
+//! Every backend -- pest (`p_pest`), pulldown-cmark (`p_pulldown`) and
+//! nom (`p_nom`, which parses a minimal set of blocks) -- produces the
+//! same events so a renderer or transform written against them works
+//! with all of them;
+//! and as the stream can be consumed a block at a time (see `blocks`) a
+//! large document never needs to be held as a whole tree.
+//!
+//! ```
+//! use parser::{
+//!     ast::{Node, NodeKind, Span},
+//!     event::{events, to_html, Event},
+//! };
+//!
+//! let tree = Node::with_children(
+//!     NodeKind::Paragraph,
+//!     Span::new(0, 3),
+//!     vec![Node::text("Foo", Span::new(0, 3))],
+//! );
+//! let shouting = events(&tree).map(|(event, span)| match event {
+//!     Event::Text(text) => (Event::Text(text.to_uppercase()), span),
+//!     event => (event, span),
+//! });
+//!
+//! assert_eq!(to_html(shouting).unwrap(), "<p>FOO</p>");
+//! ```
+
+use std::{fmt, mem::discriminant};
+
+use crate::{
+    ast::{Node, NodeKind, Span},
+    html::{to_html_within, HtmlOptions},
+};
+
+/// A node which has children; it's started and ended in the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Document,
+    Heading {
+        level: u8,
+        id: Option<String>,
+    },
+    Paragraph,
+    CodeBlock {
+        lang: Option<String>,
+        info: Option<String>,
+    },
+    /// an HTML-like tag (`NodeKind::Tag`)
+    Element {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    List {
+        ordered: bool,
+    },
+    ListItem {
+        marker: String,
+    },
+    BlockQuote,
+    Link {
+        dest: String,
+    },
+    Image {
+        src: String,
+    },
+}
+
+impl Tag {
+    /// the tag for a node of the kind; `None` when the kind is a leaf
+    pub fn from_kind(kind: &NodeKind) -> Option<Tag> {
+        match Event::for_kind(kind) {
+            Event::Start(tag) => Some(tag),
+            _ => None,
+        }
+    }
+
+    pub fn into_kind(self) -> NodeKind {
+        match self {
+            Tag::Document => NodeKind::Document,
+            Tag::Heading { level, id } => NodeKind::Heading { level, id },
+            Tag::Paragraph => NodeKind::Paragraph,
+            Tag::CodeBlock { lang, info } => NodeKind::CodeBlock { lang, info },
+            Tag::Element { name, attrs } => NodeKind::Tag { name, attrs },
+            Tag::List { ordered } => NodeKind::List { ordered },
+            Tag::ListItem { marker } => NodeKind::ListItem { marker },
+            Tag::BlockQuote => NodeKind::BlockQuote,
+            Tag::Link { dest } => NodeKind::Link { dest },
+            Tag::Image { src } => NodeKind::Image { src },
+        }
+    }
+}
+
+/// An event in the stream; every `Start` is followed (eventually) by an
+/// `End` of the same tag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Start(Tag),
+    End(Tag),
+    Text(String),
+    /// inline code; the tree has no node of its own for it so it
+    /// becomes a `<code>` tag
+    Code(String),
+    Html(String),
+    SoftBreak,
+    HardBreak,
+    /// a thematic break
+    Rule,
+    Emoji {
+        name: String,
+    },
+    BlockEmoji {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    Toc,
+}
+
+impl Event {
+    /// the event for a node of the kind; a `Start` when the kind has
+    /// children
+    fn for_kind(kind: &NodeKind) -> Event {
+        match kind {
+            NodeKind::Document => Event::Start(Tag::Document),
+            NodeKind::Heading { level, id } => Event::Start(Tag::Heading {
+                level: *level,
+                id: id.clone(),
+            }),
+            NodeKind::Paragraph => Event::Start(Tag::Paragraph),
+            NodeKind::CodeBlock { lang, info } => Event::Start(Tag::CodeBlock {
+                lang: lang.clone(),
+                info: info.clone(),
+            }),
+            NodeKind::Tag { name, attrs } => Event::Start(Tag::Element {
+                name: name.clone(),
+                attrs: attrs.clone(),
+            }),
+            NodeKind::List { ordered } => Event::Start(Tag::List { ordered: *ordered }),
+            NodeKind::ListItem { marker } => Event::Start(Tag::ListItem {
+                marker: marker.clone(),
+            }),
+            NodeKind::BlockQuote => Event::Start(Tag::BlockQuote),
+            NodeKind::Link { dest } => Event::Start(Tag::Link { dest: dest.clone() }),
+            NodeKind::Image { src } => Event::Start(Tag::Image { src: src.clone() }),
+            NodeKind::Text(text) => Event::Text(text.clone()),
+            NodeKind::Html(html) => Event::Html(html.clone()),
+            NodeKind::SoftBreak => Event::SoftBreak,
+            NodeKind::HardBreak => Event::HardBreak,
+            NodeKind::ThematicBreak => Event::Rule,
+            NodeKind::Emoji { name } => Event::Emoji { name: name.clone() },
+            NodeKind::BlockEmoji { name, attrs } => Event::BlockEmoji {
+                name: name.clone(),
+                attrs: attrs.clone(),
+            },
+            NodeKind::Toc => Event::Toc,
+        }
+    }
+}
+
+/// The events of a tree in document order; each is paired with the
+/// span of the node it came from.
+#[derive(Debug)]
+pub struct Events<'a> {
+    root: Option<&'a Node>,
+    /// the containers which have been started along with the index of
+    /// the next child to visit
+    open: Vec<(&'a Node, usize)>,
+}
+
+/// the events of the tree
+pub fn events(tree: &Node) -> Events<'_> {
+    Events {
+        root: Some(tree),
+        open: vec![],
+    }
+}
+
+impl<'a> Events<'a> {
+    fn enter(&mut self, node: &'a Node) -> (Event, Span) {
+        let event = Event::for_kind(&node.kind);
+        // a leaf's children (if it had any) wouldn't be rendered
+        if let Event::Start(_) = event {
+            self.open.push((node, 0));
+        }
+
+        (event, node.span)
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = (Event, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            return Some(self.enter(root));
+        }
+
+        let (node, next) = self.open.last_mut()?;
+        let node: &'a Node = node;
+        match node.children.get(*next) {
+            Some(child) => {
+                *next += 1;
+                Some(self.enter(child))
+            }
+            None => {
+                self.open.pop();
+                let tag = Tag::from_kind(&node.kind)?;
+                Some((Event::End(tag), node.span))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventError {
+    /// an `End` which doesn't match the last `Start`
+    Unexpected(Event),
+    /// tags which were started but never ended
+    Unclosed(Vec<Tag>),
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unexpected(event) => {
+                write!(f, "{:?} doesn't end the last tag which was started", event)
+            }
+            Self::Unclosed(tags) => write!(f, "the tags {:?} were never ended", tags),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+/// Builds nodes from a stream of events.
+#[derive(Debug, Default)]
+pub struct TreeBuilder {
+    /// the containers which have been started along with their
+    /// children so far
+    open: Vec<(Tag, Span, Vec<Node>)>,
+}
+
+impl TreeBuilder {
+    /// Adds the event; once it completes a node which isn't within a
+    /// container (i.e., a leaf or the `End` of a container) the node
+    /// is returned.
+    ///
+    /// An `End` only needs to be the same _kind_ of tag as its `Start`;
+    /// the node is built from the `Start` so a transform which changes
+    /// a tag (e.g., a link's `dest`) needn't change both.
+    pub fn push(&mut self, event: Event, span: Span) -> Result<Option<Node>, EventError> {
+        let node = match event {
+            Event::Start(tag) => {
+                self.open.push((tag, span, vec![]));
+                return Ok(None);
+            }
+            Event::End(tag) => match self.open.pop() {
+                Some((start, span, children)) if discriminant(&start) == discriminant(&tag) => {
+                    Node::with_children(start.into_kind(), span, children)
+                }
+                Some(open) => {
+                    self.open.push(open);
+                    return Err(EventError::Unexpected(Event::End(tag)));
+                }
+                None => return Err(EventError::Unexpected(Event::End(tag))),
+            },
+            Event::Code(code) => Node::with_children(
+                NodeKind::Tag {
+                    name: "code".to_string(),
+                    attrs: vec![],
+                },
+                span,
+                vec![Node::text(&code, span)],
+            ),
+            Event::Text(text) => Node::text(&text, span),
+            Event::Html(html) => Node::new(NodeKind::Html(html), span),
+            Event::SoftBreak => Node::new(NodeKind::SoftBreak, span),
+            Event::HardBreak => Node::new(NodeKind::HardBreak, span),
+            Event::Rule => Node::new(NodeKind::ThematicBreak, span),
+            Event::Emoji { name } => Node::new(NodeKind::Emoji { name }, span),
+            Event::BlockEmoji { name, attrs } => {
+                Node::new(NodeKind::BlockEmoji { name, attrs }, span)
+            }
+            Event::Toc => Node::new(NodeKind::Toc, span),
+        };
+
+        match self.open.last_mut() {
+            Some((_, _, children)) => {
+                children.push(node);
+                Ok(None)
+            }
+            None => Ok(Some(node)),
+        }
+    }
+
+    /// fails when there are tags which were never ended
+    pub fn finish(self) -> Result<(), EventError> {
+        match self.open.is_empty() {
+            true => Ok(()),
+            false => Err(EventError::Unclosed(
+                self.open.into_iter().map(|(tag, _, _)| tag).collect(),
+            )),
+        }
+    }
+}
+
+/// The tree the events describe; the events of several nodes (rather
+/// than a single root) are wrapped in a `Document`.
+pub fn to_tree<I: IntoIterator<Item = (Event, Span)>>(events: I) -> Result<Node, EventError> {
+    let mut builder = TreeBuilder::default();
+    let mut nodes = vec![];
+
+    for (event, span) in events {
+        nodes.extend(builder.push(event, span)?);
+    }
+    builder.finish()?;
+
+    if nodes.len() == 1 {
+        return Ok(nodes.remove(0));
+    }
+    let span = match (nodes.first(), nodes.last()) {
+        (Some(first), Some(last)) => Span::new(first.span.start, last.span.end),
+        _ => Span::default(),
+    };
+
+    Ok(Node::with_children(NodeKind::Document, span, nodes))
+}
+
+/// The top-level blocks of a stream of events, built one at a time as
+/// the stream is read; a `Document` the blocks are within is skipped.
+pub struct Blocks<I> {
+    events: I,
+    builder: Option<TreeBuilder>,
+}
+
+/// the top-level blocks of the events
+pub fn blocks<I: IntoIterator<Item = (Event, Span)>>(events: I) -> Blocks<I::IntoIter> {
+    Blocks {
+        events: events.into_iter(),
+        builder: Some(TreeBuilder::default()),
+    }
+}
+
+impl<I: Iterator<Item = (Event, Span)>> Iterator for Blocks<I> {
+    type Item = Result<Node, EventError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let builder = self.builder.as_mut()?;
+
+        for (event, span) in self.events.by_ref() {
+            let top_level = builder.open.is_empty();
+            match event {
+                Event::Start(Tag::Document) | Event::End(Tag::Document) if top_level => (),
+                event => match builder.push(event, span) {
+                    Ok(Some(node)) => return Some(Ok(node)),
+                    Ok(None) => (),
+                    Err(err) => {
+                        self.builder = None;
+                        return Some(Err(err));
+                    }
+                },
+            }
+        }
+
+        self.builder.take()?.finish().err().map(Err)
+    }
+}
+
+/// Appends the HTML for the events to `out` a block at a time.
+///
+/// The output is the same as rendering the tree the events describe
+/// except that -- as the rest of the document hasn't been read yet --
+/// a `Toc` only lists the headings which come before it.
+pub fn push_html<I: IntoIterator<Item = (Event, Span)>>(
+    out: &mut String,
+    events: I,
+    options: &HtmlOptions,
+) -> Result<(), EventError> {
+    fn headings(node: &Node, found: &mut Vec<Node>) {
+        match node.kind {
+            NodeKind::Heading { .. } => found.push(node.clone()),
+            _ => node.children.iter().for_each(|c| headings(c, found)),
+        }
+    }
+    // just the headings seen so far; enough for a table of contents
+    let mut document = Node::new(NodeKind::Document, Span::default());
+    let mut first = true;
+
+    for block in blocks(events) {
+        let block = block?;
+        headings(&block, &mut document.children);
+
+        let html = to_html_within(&block, &document, options);
+        if html.is_empty() {
+            continue;
+        }
+        if !first {
+            out.push('\n');
+        }
+        out.push_str(&html);
+        first = false;
+    }
+
+    Ok(())
+}
+
+/// the HTML for the events; see `push_html`
+pub fn to_html<I: IntoIterator<Item = (Event, Span)>>(events: I) -> Result<String, EventError> {
+    let mut html = String::new();
+    push_html(&mut html, events, &HtmlOptions::default())?;
+
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::build::{node, text},
+        html,
+        slug::assign_ids,
+    };
+
+    /// `# Foo\n\n- a\n- b\n\n---\n`
+    fn tree() -> Node {
+        let item = |start: usize, content: &str| {
+            node(
+                NodeKind::ListItem {
+                    marker: "-".to_string(),
+                },
+                vec![node(
+                    NodeKind::Paragraph,
+                    vec![text(content).at(start + 2, start + 3)],
+                )
+                .at(start + 2, start + 3)],
+            )
+            .at(start, start + 4)
+        };
+        let mut tree = node(
+            NodeKind::Document,
+            vec![
+                node(
+                    NodeKind::Heading { level: 1, id: None },
+                    vec![text("Foo").at(2, 5)],
+                )
+                .at(0, 6),
+                node(
+                    NodeKind::List { ordered: false },
+                    vec![item(7, "a"), item(11, "b")],
+                )
+                .at(7, 15),
+                node(NodeKind::ThematicBreak, vec![]).at(16, 20),
+            ],
+        )
+        .at(0, 20);
+        assign_ids(&mut tree);
+
+        tree
+    }
+
+    #[test]
+    fn trees_round_trip_through_events() {
+        let tree = tree();
+        let stream: Vec<(Event, Span)> = events(&tree).collect();
+
+        assert_eq!(stream.len(), 18);
+        assert_eq!(stream[0], (Event::Start(Tag::Document), Span::new(0, 20)));
+        assert_eq!(stream[16], (Event::Rule, Span::new(16, 20)));
+        assert_eq!(to_tree(stream.clone()).unwrap(), tree);
+
+        let blocks: Vec<Node> = blocks(stream).map(Result::unwrap).collect();
+        assert_eq!(blocks, tree.children);
+    }
+
+    #[test]
+    fn events_render_the_same_html_as_the_tree() {
+        let tree = tree();
+
+        assert_eq!(to_html(events(&tree)).unwrap(), html::to_html(&tree));
+    }
+
+    #[test]
+    fn unbalanced_events_are_errors() {
+        let span = Span::default();
+        let start = (Event::Start(Tag::Paragraph), span);
+        let end = (Event::End(Tag::BlockQuote), span);
+
+        assert_eq!(
+            to_tree(vec![start.clone(), end.clone()]),
+            Err(EventError::Unexpected(end.0))
+        );
+        assert_eq!(
+            to_tree(vec![start.clone()]),
+            Err(EventError::Unclosed(vec![Tag::Paragraph]))
+        );
+        assert!(blocks(vec![start]).next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! Renders a tree as Graphviz DOT or Mermaid (mindmap or flowchart)
+//! text to visualize -- and debug -- what a parser produced.
+//!
+//! Any tree can be rendered once it's converted to a `GraphNode`; the
+//! AST converts with `From<&Node>` while the `p_pest` crate converts
+//! its `Pairs`.
+//!
+//! ```
+//! use parser::{
+//!     ast::{Node, NodeKind, Span},
+//!     graph::{render, GraphFormat, GraphNode, GraphOptions},
+//! };
+//!
+//! let tree = Node::with_children(
+//!     NodeKind::Paragraph,
+//!     Span::new(0, 3),
+//!     vec![Node::text("Foo", Span::new(0, 3))],
+//! );
+//! let options = GraphOptions {
+//!     format: GraphFormat::MermaidMindmap,
+//!     ..Default::default()
+//! };
+//!
+//! assert_eq!(
+//!     render(&GraphNode::from(&tree), &options),
+//!     "mindmap\n  n0[\"paragraph\"]\n    n1[\"text: Foo\"]\n"
+//! );
+//! ```
+
+use crate::ast::{Node, NodeKind, Span};
+
+/// A node of the tree being rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphNode {
+    /// what the node is (e.g., the name of a rule)
+    pub label: String,
+    /// the text the node represents (if any)
+    pub text: Option<String>,
+    pub span: Span,
+    pub children: Vec<GraphNode>,
+}
+
+impl GraphNode {
+    pub fn new(label: &str, text: Option<&str>, span: Span, children: Vec<GraphNode>) -> Self {
+        Self {
+            label: label.to_string(),
+            text: text.map(String::from),
+            span,
+            children,
+        }
+    }
+}
+
+impl From<&Node> for GraphNode {
+    fn from(node: &Node) -> Self {
+        let label = match &node.kind {
+            NodeKind::Document => "document".to_string(),
+            NodeKind::Heading { level, .. } => format!("h{}", level),
+            NodeKind::Paragraph => "paragraph".to_string(),
+            NodeKind::ThematicBreak => "thematic_break".to_string(),
+            NodeKind::CodeBlock {
+                lang: Some(lang), ..
+            } => format!("code_block ({})", lang),
+            NodeKind::CodeBlock { .. } => "code_block".to_string(),
+            NodeKind::Tag { name, .. } => format!("<{}>", name),
+            NodeKind::List { ordered: true } => "ordered_list".to_string(),
+            NodeKind::List { ordered: false } => "list".to_string(),
+            NodeKind::ListItem { marker } => format!("item ({})", marker),
+            NodeKind::BlockQuote => "block_quote".to_string(),
+            NodeKind::Link { dest } => format!("link ({})", dest),
+            NodeKind::Image { src } => format!("image ({})", src),
+            NodeKind::Emoji { name } => format!("emoji ({})", name),
+            NodeKind::BlockEmoji { name, .. } => format!("block_emoji ({})", name),
+            NodeKind::Toc => "toc".to_string(),
+            NodeKind::SoftBreak => "soft_break".to_string(),
+            NodeKind::HardBreak => "hard_break".to_string(),
+            NodeKind::Html(_) => "html".to_string(),
+            NodeKind::Text(_) => "text".to_string(),
+        };
+        let text = match &node.kind {
+            NodeKind::Text(text) | NodeKind::Html(text) => Some(text.as_str()),
+            _ => None,
+        };
+
+        Self::new(
+            &label,
+            text,
+            node.span,
+            node.children.iter().map(GraphNode::from).collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GraphFormat {
+    /// a Graphviz `digraph`
+    #[default]
+    Dot,
+    /// a Mermaid `mindmap`
+    MermaidMindmap,
+    /// a Mermaid `flowchart` drawn top-down
+    MermaidFlowchart,
+}
+
+/// Options which control what's rendered
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GraphOptions {
+    pub format: GraphFormat,
+    /// merges nodes which only wrap a single child covering the same
+    /// span (e.g., `block > leaf_block > heading`) into one node
+    pub collapse_silent: bool,
+    /// folds leaf nodes into their parent which shows their text
+    pub collapse_leaves: bool,
+    /// the number of characters of text shown before it's truncated
+    pub max_text: Option<usize>,
+}
+
+/// the node with its wrappers (and/or leaves) collapsed
+fn collapse(mut node: GraphNode, options: &GraphOptions) -> GraphNode {
+    if options.collapse_silent {
+        while node.text.is_none() && node.children.len() == 1 && node.children[0].span == node.span
+        {
+            let child = node.children.remove(0);
+            node = GraphNode {
+                label: format!("{} > {}", node.label, child.label),
+                ..child
+            };
+        }
+    }
+    if options.collapse_leaves && !node.children.is_empty() {
+        let (leaves, children): (Vec<GraphNode>, Vec<GraphNode>) = node
+            .children
+            .into_iter()
+            .partition(|c| c.children.is_empty());
+        let text: String = leaves.iter().filter_map(|l| l.text.as_deref()).collect();
+        if !text.is_empty() {
+            node.text = Some(node.text.unwrap_or_default() + &text);
+        }
+        node.children = children;
+    }
+    node.children = node
+        .children
+        .into_iter()
+        .map(|c| collapse(c, options))
+        .collect();
+
+    node
+}
+
+/// the node's label along with its (truncated) text; line endings are
+/// shown as `⏎` so the text stays on a single line
+fn caption(node: &GraphNode, options: &GraphOptions) -> String {
+    match &node.text {
+        Some(text) => {
+            let mut text = text.replace('\n', "⏎");
+            if let Some(max) = options.max_text {
+                if text.chars().count() > max {
+                    text = text.chars().take(max).collect::<String>() + "…";
+                }
+            }
+            format!("{}: {}", node.label, text)
+        }
+        None => node.label.clone(),
+    }
+}
+
+/// every node paired with its parent (by index) in pre-order
+fn flatten<'a>(
+    node: &'a GraphNode,
+    parent: Option<usize>,
+    nodes: &mut Vec<(Option<usize>, &'a GraphNode)>,
+) {
+    let idx = nodes.len();
+    nodes.push((parent, node));
+    for child in &node.children {
+        flatten(child, Some(idx), nodes);
+    }
+}
+
+fn dot(nodes: &[(Option<usize>, &GraphNode)], options: &GraphOptions) -> String {
+    let escape = |s: String| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut dot = "digraph tree {\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+
+    for (idx, (_, node)) in nodes.iter().enumerate() {
+        dot.push_str(&format!(
+            "    n{} [label=\"{}\"];\n",
+            idx,
+            escape(caption(node, options))
+        ));
+    }
+    for (idx, (parent, _)) in nodes.iter().enumerate() {
+        if let Some(parent) = parent {
+            dot.push_str(&format!("    n{} -> n{};\n", parent, idx));
+        }
+    }
+    dot.push_str("}\n");
+
+    dot
+}
+
+/// text which can be used within a quoted Mermaid label
+fn mermaid_label(text: String) -> String {
+    text.replace('"', "#quot;")
+}
+
+fn mindmap(node: &GraphNode, options: &GraphOptions) -> String {
+    fn lines(
+        node: &GraphNode,
+        depth: usize,
+        next: &mut usize,
+        options: &GraphOptions,
+        out: &mut String,
+    ) {
+        out.push_str(&format!(
+            "{}n{}[\"{}\"]\n",
+            "  ".repeat(depth),
+            next,
+            mermaid_label(caption(node, options))
+        ));
+        *next += 1;
+        for child in &node.children {
+            lines(child, depth + 1, next, options, out);
+        }
+    }
+    let mut mindmap = "mindmap\n".to_string();
+    lines(node, 1, &mut 0, options, &mut mindmap);
+
+    mindmap
+}
+
+fn flowchart(nodes: &[(Option<usize>, &GraphNode)], options: &GraphOptions) -> String {
+    let mut flowchart = "flowchart TD\n".to_string();
+
+    for (idx, (parent, node)) in nodes.iter().enumerate() {
+        flowchart.push_str(&format!(
+            "    n{}[\"{}\"]\n",
+            idx,
+            mermaid_label(caption(node, options))
+        ));
+        if let Some(parent) = parent {
+            flowchart.push_str(&format!("    n{} --> n{}\n", parent, idx));
+        }
+    }
+
+    flowchart
+}
+
+/// Renders the tree in the format given by the options.
+pub fn render(tree: &GraphNode, options: &GraphOptions) -> String {
+    let tree = collapse(tree.clone(), options);
+    let mut nodes = vec![];
+    flatten(&tree, None, &mut nodes);
+
+    match options.format {
+        GraphFormat::Dot => dot(&nodes, options),
+        GraphFormat::MermaidMindmap => mindmap(&tree, options),
+        GraphFormat::MermaidFlowchart => flowchart(&nodes, options),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a `block > leaf_block > heading` chain as produced by the grammar
+    fn tree() -> GraphNode {
+        let title = GraphNode::new("title", Some("Say \"hi\""), Span::new(2, 10), vec![]);
+        let heading = GraphNode::new("heading", None, Span::new(0, 11), vec![title]);
+        let leaf = GraphNode::new("leaf_block", None, Span::new(0, 11), vec![heading]);
+        let block = GraphNode::new("block", None, Span::new(0, 11), vec![leaf]);
+
+        GraphNode::new("file", None, Span::new(0, 12), vec![block])
+    }
+
+    #[test]
+    fn trees_render_as_dot() {
+        let dot = render(&tree(), &GraphOptions::default());
+
+        assert!(dot.starts_with("digraph tree {\n"));
+        assert!(dot.contains("    n4 [label=\"title: Say \\\"hi\\\"\"];\n"));
+        assert!(dot.contains("    n3 -> n4;\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn silent_and_leaf_nodes_collapse() {
+        let options = GraphOptions {
+            format: GraphFormat::MermaidFlowchart,
+            collapse_silent: true,
+            collapse_leaves: true,
+            max_text: Some(6),
+        };
+
+        assert_eq!(
+            render(&tree(), &options),
+            concat!(
+                "flowchart TD\n",
+                "    n0[\"file\"]\n",
+                "    n1[\"block > leaf_block > heading: Say #quot;h…\"]\n",
+                "    n0 --> n1\n",
+            )
+        );
+    }
+
+    #[test]
+    fn ast_renders_as_a_mindmap() {
+        let tree = Node::with_children(
+            NodeKind::Document,
+            Span::new(0, 8),
+            vec![Node::with_children(
+                NodeKind::Heading { level: 1, id: None },
+                Span::new(0, 7),
+                vec![Node::text("Foo\nbar", Span::new(2, 7))],
+            )],
+        );
+        let options = GraphOptions {
+            format: GraphFormat::MermaidMindmap,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&GraphNode::from(&tree), &options),
+            "mindmap\n  n0[\"document\"]\n    n1[\"h1\"]\n      n2[\"text: Foo⏎bar\"]\n"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Renders the tree as HTML.
+
+use crate::{
+    ast::{Node, NodeKind, Span},
+    component::{Component, ComponentRegistry},
+    emoji, toc,
+};
+
+/// escapes the characters which have a special meaning in HTML
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn attributes(attrs: &[(String, String)]) -> String {
+    attrs
+        .iter()
+        .map(|(k, v)| format!(" {}=\"{}\"", k, escape(v)))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+/// How a soft break (a line ending within a paragraph) is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoftBreak {
+    /// as a newline, which browsers display as a space
+    #[default]
+    Newline,
+    Space,
+    /// as a `<br />`, so line endings in the source are kept
+    LineBreak,
+}
+
+/// Options which change how the tree is rendered as HTML.
+#[derive(Debug, Default)]
+pub struct HtmlOptions<'a> {
+    /// the components which render custom tags; tags without a
+    /// component are passed through as is
+    pub components: Option<&'a ComponentRegistry>,
+    pub soft_break: SoftBreak,
+}
+
+/// Renders the node (and its children) as HTML.
+///
+/// Headings are given an `id` so they can be linked to and any
+/// `Toc` placeholder is rendered as the document's table of contents.
+pub fn to_html(node: &Node) -> String {
+    to_html_with(node, &HtmlOptions::default())
+}
+
+/// Renders the node (and its children) as HTML using the options.
+pub fn to_html_with(node: &Node, options: &HtmlOptions) -> String {
+    to_html_within(node, node, options)
+}
+
+/// Renders the node as HTML using the options where the node is part of
+/// a larger `document`; any `Toc` placeholder lists the document's
+/// headings rather than just the node's.
+pub fn to_html_within(node: &Node, document: &Node, options: &HtmlOptions) -> String {
+    let renderer = Renderer {
+        toc: toc::table_of_contents(document, Span::default()),
+        components: options.components,
+        soft_break: options.soft_break,
+    };
+
+    renderer.render(node)
+}
+
+struct Renderer<'a> {
+    /// the table of contents for the whole tree
+    toc: Node,
+    components: Option<&'a ComponentRegistry>,
+    soft_break: SoftBreak,
+}
+
+impl<'a> Renderer<'a> {
+    fn children(&self, node: &Node) -> String {
+        node.children
+            .iter()
+            .map(|c| self.render(c))
+            .collect::<Vec<String>>()
+            .concat()
+    }
+
+    fn blocks(&self, nodes: &[Node]) -> String {
+        nodes
+            .iter()
+            .map(|c| self.render(c))
+            .filter(|b| !b.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn render(&self, node: &Node) -> String {
+        match &node.kind {
+            NodeKind::Document => self.blocks(&node.children),
+            NodeKind::Heading { level, id } => match id {
+                Some(id) => format!(
+                    "<h{} id=\"{}\">{}</h{}>",
+                    level,
+                    escape(id),
+                    self.children(node).trim(),
+                    level
+                ),
+                None => format!("<h{}>{}</h{}>", level, self.children(node).trim(), level),
+            },
+            NodeKind::Paragraph => format!("<p>{}</p>", self.children(node)),
+            NodeKind::ThematicBreak => "<hr />".to_string(),
+            NodeKind::CodeBlock { lang, .. } => {
+                let code = escape(&node.text_content());
+                match lang {
+                    Some(lang) if !lang.is_empty() => format!(
+                        "<pre><code class=\"language-{}\">{}\n</code></pre>",
+                        escape(lang),
+                        code
+                    ),
+                    _ => format!("<pre><code>{}\n</code></pre>", code),
+                }
+            }
+            NodeKind::Tag { name, attrs } => {
+                let registered = self.components.filter(|c| c.contains(name));
+                if let Some(components) = registered {
+                    let component = Component {
+                        name,
+                        attrs,
+                        children: &node.children,
+                        inner: self.blocks(&node.children),
+                    };
+                    components.render(&component).unwrap_or_default()
+                } else if node.children.is_empty() {
+                    format!("<{}{} />", name, attributes(attrs))
+                } else if node.is_block() {
+                    format!(
+                        "<{}{}>\n{}\n</{}>",
+                        name,
+                        attributes(attrs),
+                        self.blocks(&node.children),
+                        name
+                    )
+                } else {
+                    format!(
+                        "<{}{}>{}</{}>",
+                        name,
+                        attributes(attrs),
+                        self.children(node),
+                        name
+                    )
+                }
+            }
+            NodeKind::List { ordered } => {
+                let start = match node.children.first().map(|i| &i.kind) {
+                    Some(NodeKind::ListItem { marker }) if *ordered => marker
+                        .trim_end_matches(['.', ')'])
+                        .parse::<usize>()
+                        .unwrap_or(1),
+                    _ => 1,
+                };
+                let items = self.blocks(&node.children);
+                match (ordered, start) {
+                    (false, _) => format!("<ul>\n{}\n</ul>", items),
+                    (true, 1) => format!("<ol>\n{}\n</ol>", items),
+                    (true, start) => format!("<ol start=\"{}\">\n{}\n</ol>", start, items),
+                }
+            }
+            NodeKind::ListItem { .. } => {
+                // a single paragraph is rendered "tight" (without the `<p>`)
+                let content = match node.children.as_slice() {
+                    [p] if p.kind == NodeKind::Paragraph => self.children(p),
+                    [p, rest @ ..] if p.kind == NodeKind::Paragraph => {
+                        format!("{}\n{}", self.children(p), self.blocks(rest))
+                    }
+                    children => self.blocks(children),
+                };
+                format!("<li>{}</li>", content)
+            }
+            NodeKind::BlockQuote => {
+                format!(
+                    "<blockquote>\n{}\n</blockquote>",
+                    self.blocks(&node.children)
+                )
+            }
+            NodeKind::Link { dest } => {
+                format!("<a href=\"{}\">{}</a>", escape(dest), self.children(node))
+            }
+            NodeKind::Image { src } => format!(
+                "<img src=\"{}\" alt=\"{}\" />",
+                escape(src),
+                escape(&node.text_content())
+            ),
+            NodeKind::Emoji { name } => match emoji::lookup(name) {
+                Some(emoji) => format!("<span class=\"emoji\">{}</span>", emoji),
+                None => format!("::{}::", escape(name)),
+            },
+            NodeKind::BlockEmoji { name, attrs } => {
+                let emoji = emoji::lookup(name)
+                    .map(|e| e.to_string())
+                    .unwrap_or_else(|| format!("::{}", escape(name)));
+                let mut attrs = attrs.clone();
+                match attrs.iter_mut().find(|(k, _)| k == "class") {
+                    Some((_, class)) => *class = format!("emoji block {}", class),
+                    None => attrs.insert(0, ("class".to_string(), "emoji block".to_string())),
+                }
+                format!("<span{}>{}</span>", attributes(&attrs), emoji)
+            }
+            NodeKind::Toc => format!("<nav class=\"toc\">\n{}\n</nav>", self.render(&self.toc)),
+            NodeKind::SoftBreak => match self.soft_break {
+                SoftBreak::Newline => "\n".to_string(),
+                SoftBreak::Space => " ".to_string(),
+                SoftBreak::LineBreak => "<br />\n".to_string(),
+            },
+            NodeKind::HardBreak => "<br />\n".to_string(),
+            NodeKind::Html(html) => html.clone(),
+            NodeKind::Text(text) => escape(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::build::{node, text},
+        slug::assign_ids,
+    };
+
+    #[test]
+    fn headings_have_ids() {
+        let heading = |t: &str| node(NodeKind::Heading { level: 2, id: None }, vec![text(t)]);
+        let mut tree = node(
+            NodeKind::Document,
+            vec![heading("Topic 1"), heading("Topic 1")],
+        );
+        assign_ids(&mut tree);
+
+        assert_eq!(
+            to_html(&tree),
+            "<h2 id=\"topic-1\">Topic 1</h2>\n<h2 id=\"topic-1-1\">Topic 1</h2>"
+        );
+    }
+
+    #[test]
+    fn toc_placeholder_is_expanded() {
+        let mut tree = node(
+            NodeKind::Document,
+            vec![
+                node(NodeKind::Heading { level: 1, id: None }, vec![text("Foo")]),
+                node(NodeKind::Toc, vec![]),
+            ],
+        );
+        assign_ids(&mut tree);
+
+        assert_eq!(
+            to_html(&tree),
+            "<h1 id=\"foo\">Foo</h1>\n<nav class=\"toc\">\n<ul>\n<li><a href=\"#foo\">Foo</a></li>\n</ul>\n</nav>"
+        );
+    }
+
+    #[test]
+    fn components_render_custom_tags() {
+        let mut components = ComponentRegistry::new();
+        components.register("my-inline", |c| {
+            format!(
+                "<em title=\"{}\">{}</em>",
+                c.attr("title").unwrap_or(""),
+                c.inner
+            )
+        });
+        let tag = |name: &str| {
+            node(
+                NodeKind::Tag {
+                    name: name.to_string(),
+                    attrs: vec![("title".to_string(), "t".to_string())],
+                },
+                vec![node(NodeKind::Paragraph, vec![text("foo")])],
+            )
+        };
+        let tree = node(NodeKind::Document, vec![tag("my-inline"), tag("other")]);
+        let options = HtmlOptions {
+            components: Some(&components),
+            ..HtmlOptions::default()
+        };
+
+        assert_eq!(
+            to_html_with(&tree, &options),
+            "<em title=\"t\"><p>foo</p></em>\n<other title=\"t\">\n<p>foo</p>\n</other>"
+        );
+    }
+
+    #[test]
+    fn line_breaks() {
+        let p = node(
+            NodeKind::Paragraph,
+            vec![
+                text("foo"),
+                node(NodeKind::SoftBreak, vec![]),
+                text("bar"),
+                node(NodeKind::HardBreak, vec![]),
+                text("baz"),
+            ],
+        );
+        let html = |soft_break| {
+            let options = HtmlOptions {
+                soft_break,
+                ..HtmlOptions::default()
+            };
+            to_html_with(&p, &options)
+        };
+
+        assert_eq!(html(SoftBreak::Newline), "<p>foo\nbar<br />\nbaz</p>");
+        assert_eq!(html(SoftBreak::Space), "<p>foo bar<br />\nbaz</p>");
+        assert_eq!(
+            html(SoftBreak::LineBreak),
+            "<p>foo<br />\nbar<br />\nbaz</p>"
+        );
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let p = node(NodeKind::Paragraph, vec![text("a < b & \"c\"")]);
+
+        assert_eq!(to_html(&p), "<p>a &lt; b &amp; &quot;c&quot;</p>");
+    }
+}
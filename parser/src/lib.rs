@@ -1,5 +1,22 @@
+pub mod ast;
+pub mod component;
+pub mod cst;
+pub mod diagnostic;
+pub mod emoji;
+pub mod entity;
+pub mod event;
+pub mod graph;
+pub mod html;
+pub mod lint;
+pub mod markdown;
+pub mod mdast;
+pub mod pipeline;
+pub mod sanitize;
+pub mod slug;
+pub mod toc;
+pub mod visit;
+
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
 
 #[derive(Serialize, Deserialize)]
 pub enum ParserStage {
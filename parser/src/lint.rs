@@ -0,0 +1,716 @@
+//! A linter for Markdown documents which runs a set of _rules_
+//! against the parsed tree. Rules use the same IDs and aliases
+//! as [markdownlint](https://github.com/DavidAnson/markdownlint)
+//! so an existing `.markdownlint.json` can be used to configure it.
+
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fs::read_to_string, io};
+
+use crate::{
+    ast::{Node, NodeKind, Span},
+    diagnostic::Diagnostic,
+};
+
+/// The content which a lint rule is run against.
+pub struct LintContext<'a> {
+    /// the raw markdown content
+    pub source: &'a str,
+    /// the tree produced by parsing the `source`
+    pub tree: &'a Node,
+}
+
+/// A rule which can be registered with the `Linter`.
+pub trait LintRule {
+    /// the rule's ID (e.g., `MD001`)
+    fn id(&self) -> &'static str;
+    /// the human friendly alias for the rule (e.g., `heading-increment`)
+    fn alias(&self) -> &'static str;
+    /// a short description of what the rule checks
+    fn description(&self) -> &'static str;
+    /// checks the content and reports any problems found; `options` are
+    /// the rule's options from the configuration (or `Value::Null`)
+    fn check(&self, ctx: &LintContext, options: &Value) -> Vec<Diagnostic>;
+}
+
+/// The configuration for a single rule
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleSetting {
+    Enabled(bool),
+    /// the rule is enabled with the given options
+    Options(Map<String, Value>),
+}
+
+/// Linter configuration in the **markdownlint** format:
+///
+/// ```json
+/// { "default": true, "MD004": false, "MD024": { "siblings_only": true } }
+/// ```
+///
+/// Rules may be referred to by either ID or alias.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintConfig {
+    /// whether rules not mentioned in the config are enabled
+    pub default: bool,
+    pub rules: HashMap<String, RuleSetting>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            default: true,
+            rules: HashMap::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let values: Map<String, Value> = serde_json::from_str(json)?;
+        let mut config = LintConfig::default();
+
+        for (key, value) in values {
+            match (key.as_str(), value) {
+                ("default", Value::Bool(default)) => config.default = default,
+                (_, Value::Bool(enabled)) => {
+                    config.rules.insert(key, RuleSetting::Enabled(enabled));
+                }
+                (_, Value::Object(options)) => {
+                    config.rules.insert(key, RuleSetting::Options(options));
+                }
+                // markdownlint ignores settings it doesn't understand
+                _ => (),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// loads the configuration from a file such as `.markdownlint.json`
+    pub fn from_file(file: &str) -> io::Result<Self> {
+        let json = read_to_string(file)?;
+        LintConfig::from_json(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn setting(&self, rule: &dyn LintRule) -> Option<&RuleSetting> {
+        self.rules
+            .get(rule.id())
+            .or_else(|| self.rules.get(rule.alias()))
+    }
+
+    pub fn is_enabled(&self, rule: &dyn LintRule) -> bool {
+        match self.setting(rule) {
+            Some(RuleSetting::Enabled(enabled)) => *enabled,
+            Some(RuleSetting::Options(_)) => true,
+            None => self.default,
+        }
+    }
+
+    /// the options configured for the rule (or `Value::Null`)
+    pub fn options(&self, rule: &dyn LintRule) -> Value {
+        match self.setting(rule) {
+            Some(RuleSetting::Options(options)) => Value::Object(options.clone()),
+            _ => Value::Null,
+        }
+    }
+}
+
+/// Runs the registered rules against a parsed document.
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule>>,
+    pub config: LintConfig,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Linter::new(LintConfig::default())
+    }
+}
+
+impl Linter {
+    /// a linter with all of the built-in rules registered
+    pub fn new(config: LintConfig) -> Self {
+        Self {
+            rules: vec![
+                Box::new(HeadingIncrement),
+                Box::new(ListMarkerStyle),
+                Box::new(TrailingSpaces),
+                Box::new(DuplicateHeading),
+                Box::new(BareUrls),
+                Box::new(MissingAltText),
+                Box::new(LinkFragments),
+            ],
+            config,
+        }
+    }
+
+    /// a linter with no rules registered
+    pub fn empty(config: LintConfig) -> Self {
+        Self {
+            rules: vec![],
+            config,
+        }
+    }
+
+    /// adds a rule to the linter
+    pub fn register(&mut self, rule: Box<dyn LintRule>) -> &mut Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &Vec<Box<dyn LintRule>> {
+        &self.rules
+    }
+
+    /// lints the document, returning the problems found in the
+    /// order they appear in the source
+    pub fn lint(&self, source: &str, tree: &Node) -> Vec<Diagnostic> {
+        let ctx = LintContext { source, tree };
+        let mut diagnostics: Vec<Diagnostic> = self
+            .rules
+            .iter()
+            .filter(|rule| self.config.is_enabled(rule.as_ref()))
+            .flat_map(|rule| {
+                let options = self.config.options(rule.as_ref());
+                rule.check(&ctx, &options)
+                    .into_iter()
+                    .map(|d| d.with_code(rule.id()))
+            })
+            .collect();
+
+        diagnostics.sort_by_key(|d| d.span.start);
+        diagnostics
+    }
+}
+
+/// visits every node in the tree (depth first) along with
+/// the node's ancestors
+fn walk<'a>(
+    node: &'a Node,
+    ancestors: &mut Vec<&'a Node>,
+    f: &mut dyn FnMut(&'a Node, &[&'a Node]),
+) {
+    f(node, ancestors);
+    ancestors.push(node);
+    for child in node.children.iter() {
+        walk(child, ancestors, f);
+    }
+    ancestors.pop();
+}
+
+fn headings(tree: &Node) -> Vec<(u8, &Node)> {
+    let mut headings = vec![];
+    walk(tree, &mut vec![], &mut |node, _| {
+        if let NodeKind::Heading { level, .. } = node.kind {
+            headings.push((level, node));
+        }
+    });
+
+    headings
+}
+
+/// **MD001** - heading levels should only increment by one level at a time
+pub struct HeadingIncrement;
+
+impl LintRule for HeadingIncrement {
+    fn id(&self) -> &'static str {
+        "MD001"
+    }
+    fn alias(&self) -> &'static str {
+        "heading-increment"
+    }
+    fn description(&self) -> &'static str {
+        "Heading levels should only increment by one level at a time"
+    }
+
+    fn check(&self, ctx: &LintContext, _options: &Value) -> Vec<Diagnostic> {
+        let mut previous: Option<u8> = None;
+        let mut diagnostics = vec![];
+
+        for (level, node) in headings(ctx.tree) {
+            if let Some(prev) = previous {
+                if level > prev + 1 {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!("Expected: h{}; Actual: h{}", prev + 1, level),
+                        node.span,
+                    ));
+                }
+            }
+            previous = Some(level);
+        }
+
+        diagnostics
+    }
+}
+
+/// **MD004** - bullet lists should use a consistent marker
+///
+/// options:
+/// - `style`: `consistent` (default), `asterisk`, `dash` or `plus`
+pub struct ListMarkerStyle;
+
+impl LintRule for ListMarkerStyle {
+    fn id(&self) -> &'static str {
+        "MD004"
+    }
+    fn alias(&self) -> &'static str {
+        "ul-style"
+    }
+    fn description(&self) -> &'static str {
+        "Unordered list style"
+    }
+
+    fn check(&self, ctx: &LintContext, options: &Value) -> Vec<Diagnostic> {
+        let mut expected = match options.get("style").and_then(|s| s.as_str()) {
+            Some("asterisk") => Some("*"),
+            Some("dash") => Some("-"),
+            Some("plus") => Some("+"),
+            _ => None,
+        };
+        let mut diagnostics = vec![];
+
+        walk(ctx.tree, &mut vec![], &mut |node, _| {
+            if let NodeKind::ListItem { marker } = &node.kind {
+                if !matches!(marker.as_str(), "-" | "+" | "*") {
+                    return;
+                }
+                match expected {
+                    Some(style) if style != marker => diagnostics.push(Diagnostic::warning(
+                        &format!("Expected: {}; Actual: {}", style, marker),
+                        Span::new(node.span.start, node.span.start + marker.len()),
+                    )),
+                    Some(_) => (),
+                    None => expected = Some(marker_style(marker)),
+                }
+            }
+        });
+
+        diagnostics
+    }
+}
+
+fn marker_style(marker: &str) -> &'static str {
+    match marker {
+        "*" => "*",
+        "+" => "+",
+        _ => "-",
+    }
+}
+
+/// **MD009** - lines should not end in whitespace
+///
+/// options:
+/// - `br_spaces`: the number of trailing spaces allowed in order to
+///   create a hard line break (default `2`)
+pub struct TrailingSpaces;
+
+impl LintRule for TrailingSpaces {
+    fn id(&self) -> &'static str {
+        "MD009"
+    }
+    fn alias(&self) -> &'static str {
+        "no-trailing-spaces"
+    }
+    fn description(&self) -> &'static str {
+        "Trailing spaces"
+    }
+
+    fn check(&self, ctx: &LintContext, options: &Value) -> Vec<Diagnostic> {
+        let br_spaces = options
+            .get("br_spaces")
+            .and_then(|b| b.as_u64())
+            .unwrap_or(2) as usize;
+        let mut diagnostics = vec![];
+        let mut pos = 0;
+
+        for line in ctx.source.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_end_matches([' ', '\t']);
+            let trailing = content.len() - trimmed.len();
+            let is_break = br_spaces >= 2
+                && trailing == br_spaces
+                && !trimmed.is_empty()
+                && content.ends_with(&" ".repeat(br_spaces));
+
+            if trailing > 0 && !is_break {
+                diagnostics.push(Diagnostic::warning(
+                    &format!("Expected: 0 or {}; Actual: {}", br_spaces, trailing),
+                    Span::new(pos + trimmed.len(), pos + content.len()),
+                ));
+            }
+            pos += line.len();
+        }
+
+        diagnostics
+    }
+}
+
+/// **MD024** - multiple headings should not have the same content
+///
+/// options:
+/// - `siblings_only`: only headings which share the same parent
+///   heading are compared (default `false`)
+pub struct DuplicateHeading;
+
+impl LintRule for DuplicateHeading {
+    fn id(&self) -> &'static str {
+        "MD024"
+    }
+    fn alias(&self) -> &'static str {
+        "no-duplicate-heading"
+    }
+    fn description(&self) -> &'static str {
+        "Multiple headings with the same content"
+    }
+
+    fn check(&self, ctx: &LintContext, options: &Value) -> Vec<Diagnostic> {
+        let siblings_only = options
+            .get("siblings_only")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false);
+        // the headings seen at each level of the document outline
+        let mut seen: Vec<Vec<String>> = vec![vec![]; 7];
+        let mut diagnostics = vec![];
+
+        for (level, node) in headings(ctx.tree) {
+            let text = node.text_content();
+            let level = level as usize;
+            let scope = if siblings_only { level } else { 0 };
+
+            if seen[scope].contains(&text) {
+                diagnostics.push(Diagnostic::warning(
+                    &format!("Duplicate heading: \"{}\"", text),
+                    node.span,
+                ));
+            }
+            seen[scope].push(text);
+
+            if siblings_only {
+                // a new parent heading starts a new set of siblings
+                for deeper in seen.iter_mut().skip(level + 1) {
+                    deeper.clear();
+                }
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// **MD034** - URLs should be wrapped in a link rather than left bare
+pub struct BareUrls;
+
+impl LintRule for BareUrls {
+    fn id(&self) -> &'static str {
+        "MD034"
+    }
+    fn alias(&self) -> &'static str {
+        "no-bare-urls"
+    }
+    fn description(&self) -> &'static str {
+        "Bare URL used"
+    }
+
+    fn check(&self, ctx: &LintContext, _options: &Value) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        walk(ctx.tree, &mut vec![], &mut |node, ancestors| {
+            let text = match &node.kind {
+                NodeKind::Text(text) => text,
+                _ => return,
+            };
+            let in_link = ancestors.iter().any(|a| {
+                matches!(
+                    a.kind,
+                    NodeKind::Link { .. }
+                        | NodeKind::Image { .. }
+                        | NodeKind::CodeBlock { .. }
+                        | NodeKind::Tag { .. }
+                )
+            });
+            if in_link {
+                return;
+            }
+            // the text is decoded (e.g., `&amp;` is `&`) so its offsets
+            // only match the source's when the source is searched
+            let text = ctx
+                .source
+                .get(node.span.start..node.span.end)
+                .unwrap_or(text);
+
+            let mut pos = 0;
+            while let Some(idx) = find_url(&text[pos..]) {
+                let start = pos + idx;
+                let len = text[start..]
+                    .find(|c: char| c.is_whitespace() || matches!(c, ')' | '>' | '"'))
+                    .unwrap_or(text.len() - start);
+                let url = text[start..start + len].trim_end_matches(['.', ',', ';', '!', '?']);
+
+                diagnostics.push(Diagnostic::warning(
+                    &format!("Bare URL used: {}", url),
+                    Span::new(node.span.start + start, node.span.start + start + url.len()),
+                ));
+                pos = start + len;
+            }
+        });
+
+        diagnostics
+    }
+}
+
+fn find_url(text: &str) -> Option<usize> {
+    match (text.find("https://"), text.find("http://")) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// **MD045** - images should have alternate text
+pub struct MissingAltText;
+
+impl LintRule for MissingAltText {
+    fn id(&self) -> &'static str {
+        "MD045"
+    }
+    fn alias(&self) -> &'static str {
+        "no-alt-text"
+    }
+    fn description(&self) -> &'static str {
+        "Images should have alternate text (alt text)"
+    }
+
+    fn check(&self, ctx: &LintContext, _options: &Value) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        walk(ctx.tree, &mut vec![], &mut |node, _| {
+            if let NodeKind::Image { src } = &node.kind {
+                if node.text_content().trim().is_empty() {
+                    diagnostics.push(Diagnostic::warning(
+                        &format!("Image has no alt text: {}", src),
+                        node.span,
+                    ));
+                }
+            }
+        });
+
+        diagnostics
+    }
+}
+
+/// **MD051** - links to a `#fragment` should point at a heading (or an
+/// HTML element with a matching `id` or `name`) in the document; the
+/// headings' ids must have been assigned (see `slug::assign_ids`)
+pub struct LinkFragments;
+
+impl LintRule for LinkFragments {
+    fn id(&self) -> &'static str {
+        "MD051"
+    }
+    fn alias(&self) -> &'static str {
+        "link-fragments"
+    }
+    fn description(&self) -> &'static str {
+        "Link fragments should be valid"
+    }
+
+    fn check(&self, ctx: &LintContext, options: &Value) -> Vec<Diagnostic> {
+        let ignore_case = options
+            .get("ignore_case")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let normalize = |anchor: &str| match ignore_case {
+            true => anchor.to_lowercase(),
+            false => anchor.to_string(),
+        };
+
+        let mut valid: Vec<String> = vec![];
+        walk(ctx.tree, &mut vec![], &mut |node, _| match &node.kind {
+            NodeKind::Heading { id: Some(id), .. } => valid.push(normalize(id)),
+            NodeKind::Tag { attrs, .. } => valid.extend(
+                attrs
+                    .iter()
+                    .filter(|(k, _)| k == "id" || k == "name")
+                    .map(|(_, v)| normalize(v)),
+            ),
+            _ => (),
+        });
+
+        let mut diagnostics = vec![];
+        walk(ctx.tree, &mut vec![], &mut |node, _| {
+            if let NodeKind::Link { dest } = &node.kind {
+                if let Some(fragment) = dest.strip_prefix('#') {
+                    if !fragment.is_empty() && !valid.contains(&normalize(fragment)) {
+                        diagnostics.push(Diagnostic::warning(
+                            &format!("Link fragment doesn't match a heading: {}", dest),
+                            node.span,
+                        ));
+                    }
+                }
+            }
+        });
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::build::{link, node, text},
+        slug::assign_ids,
+    };
+
+    fn heading(level: u8, title: &str, start: usize) -> Node {
+        let end = start + level as usize + 1 + title.len();
+        node(
+            NodeKind::Heading { level, id: None },
+            vec![text(title).at(end - title.len(), end)],
+        )
+        .at(start, end)
+    }
+
+    fn document(children: Vec<Node>) -> Node {
+        node(NodeKind::Document, children).at(0, 100)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .map(|d| d.code.as_deref().unwrap_or(""))
+            .collect()
+    }
+
+    #[test]
+    fn config_reads_markdownlint_format() {
+        let config = LintConfig::from_json(
+            r#"{ "default": true, "MD004": false, "no-duplicate-heading": { "siblings_only": true } }"#,
+        )
+        .unwrap();
+
+        assert!(!config.is_enabled(&ListMarkerStyle));
+        assert!(config.is_enabled(&DuplicateHeading));
+        assert!(config.is_enabled(&HeadingIncrement));
+        assert_eq!(
+            config.options(&DuplicateHeading).get("siblings_only"),
+            Some(&Value::Bool(true))
+        );
+    }
+
+    #[test]
+    fn heading_increment_and_duplicates() {
+        let tree = document(vec![
+            heading(1, "Foo", 0),
+            heading(3, "Bar", 10),
+            heading(2, "Foo", 20),
+        ]);
+        let diagnostics = Linter::default().lint("", &tree);
+
+        assert_eq!(codes(&diagnostics), vec!["MD001", "MD024"]);
+        assert_eq!(diagnostics[0].span, Span::new(10, 17));
+    }
+
+    #[test]
+    fn disabled_rules_are_not_run() {
+        let tree = document(vec![heading(1, "Foo", 0), heading(3, "Bar", 10)]);
+        let config = LintConfig::from_json(r#"{ "heading-increment": false }"#).unwrap();
+
+        assert!(Linter::new(config).lint("", &tree).is_empty());
+    }
+
+    #[test]
+    fn list_markers_must_be_consistent() {
+        let item = |marker: &str, start: usize| {
+            node(
+                NodeKind::ListItem {
+                    marker: marker.to_string(),
+                },
+                vec![],
+            )
+            .at(start, start + 5)
+        };
+        let list = node(
+            NodeKind::List { ordered: false },
+            vec![item("-", 0), item("+", 5), item("-", 10)],
+        )
+        .at(0, 15);
+        let diagnostics = Linter::default().lint("", &document(vec![list]));
+
+        assert_eq!(codes(&diagnostics), vec!["MD004"]);
+        assert_eq!(diagnostics[0].span, Span::new(5, 6));
+    }
+
+    #[test]
+    fn trailing_spaces_allow_hard_breaks() {
+        let source = "foo \nbar  \nbaz   \n";
+        let diagnostics = Linter::default().lint(source, &document(vec![]));
+
+        assert_eq!(codes(&diagnostics), vec!["MD009", "MD009"]);
+        assert_eq!(diagnostics[0].span, Span::new(3, 4));
+        assert_eq!(diagnostics[1].span, Span::new(14, 17));
+    }
+
+    #[test]
+    fn bare_urls_outside_of_links() {
+        let paragraph = node(
+            NodeKind::Paragraph,
+            vec![text("see https://bar.com.").at(20, 40)],
+        )
+        .at(20, 50);
+        let tree = document(vec![link("https://foo.com").at(0, 10), paragraph]);
+        let diagnostics = Linter::default().lint("", &tree);
+
+        assert_eq!(codes(&diagnostics), vec!["MD034"]);
+        assert_eq!(diagnostics[0].span, Span::new(24, 39));
+    }
+
+    #[test]
+    fn bare_urls_are_found_in_the_source() {
+        let source = "a &amp; b https://bar.com\n";
+        let paragraph = node(
+            NodeKind::Paragraph,
+            vec![text("a & b https://bar.com").at(0, 25)],
+        )
+        .at(0, 25);
+        let diagnostics = Linter::default().lint(source, &document(vec![paragraph]));
+
+        assert_eq!(codes(&diagnostics), vec!["MD034"]);
+        assert_eq!(
+            &source[diagnostics[0].span.start..diagnostics[0].span.end],
+            "https://bar.com"
+        );
+    }
+
+    #[test]
+    fn images_need_alt_text() {
+        let image = node(
+            NodeKind::Image {
+                src: "foo.png".to_string(),
+            },
+            vec![],
+        )
+        .at(0, 12);
+        let diagnostics = Linter::default().lint("", &document(vec![image]));
+
+        assert_eq!(codes(&diagnostics), vec!["MD045"]);
+    }
+
+    #[test]
+    fn link_fragments_must_match_a_heading() {
+        let mut tree = document(vec![
+            heading(1, "Test Markdown", 0),
+            heading(2, "Topic 1", 20),
+            link("#test-markdown").at(40, 50),
+            link("#topic-1").at(50, 60),
+            link("#Topic-1").at(60, 70),
+            link("#topic-2").at(70, 80),
+        ]);
+        assign_ids(&mut tree);
+
+        let diagnostics = Linter::default().lint("", &tree);
+        assert_eq!(codes(&diagnostics), vec!["MD051", "MD051"]);
+        assert_eq!(diagnostics[1].span, Span::new(70, 80));
+
+        let config = LintConfig::from_json(r#"{ "MD051": { "ignore_case": true } }"#).unwrap();
+        assert_eq!(Linter::new(config).lint("", &tree).len(), 1);
+    }
+}
@@ -0,0 +1,477 @@
+//! Renders the tree back into _normalized_ Markdown.
+
+use crate::{
+    ast::{Node, NodeKind},
+    entity::escape_references,
+};
+
+/// Options which control the style of the Markdown produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    /// the column at which paragraphs are wrapped; `0` turns
+    /// wrapping off
+    pub width: usize,
+    /// the marker used for all bullet list items
+    pub bullet: char,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            width: 80,
+            bullet: '-',
+        }
+    }
+}
+
+/// Renders the node (and its children) as Markdown using the
+/// given options.
+///
+/// The output is normalized: headings are always ATX style, code
+/// blocks are fenced (with a longer fence when the code contains a
+/// closing fence), bullet lists share a single marker and
+/// the attributes of block emoji use the `{ key: "value" }` syntax.
+pub fn to_markdown(node: &Node, options: &MarkdownOptions) -> String {
+    let mut md = block(node, options);
+    if !md.is_empty() {
+        md.push('\n');
+    }
+
+    md
+}
+
+fn blocks(nodes: &[Node], options: &MarkdownOptions) -> String {
+    nodes
+        .iter()
+        .map(|n| block(n, options))
+        .filter(|b| !b.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+fn block(node: &Node, options: &MarkdownOptions) -> String {
+    match &node.kind {
+        NodeKind::Document => blocks(&node.children, options),
+        NodeKind::Heading { level, .. } => format!(
+            "{} {}",
+            "#".repeat(*level as usize),
+            heading_title(inline(&node.children).trim())
+        ),
+        NodeKind::Paragraph => wrap(&words(&node.children), options.width),
+        NodeKind::ThematicBreak => "---".to_string(),
+        NodeKind::Toc => "[[toc]]".to_string(),
+        NodeKind::Html(html) => html.clone(),
+        NodeKind::CodeBlock { lang, info } => {
+            let defn = [lang.as_deref(), info.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<&str>>()
+                .join(" ");
+            let code = node.text_content();
+            // the fence is longer than any line of the code which would
+            // otherwise close it early
+            let longest = code.lines().filter_map(closing_fence).max().unwrap_or(0);
+            let fence = "`".repeat(longest.max(2) + 1);
+            if code.is_empty() {
+                format!("{}{}\n{}", fence, defn, fence)
+            } else {
+                format!("{}{}\n{}\n{}", fence, defn, code, fence)
+            }
+        }
+        NodeKind::List { ordered } => list(node, *ordered, options),
+        NodeKind::BlockQuote => blocks(&node.children, options)
+            .lines()
+            .map(|line| match line {
+                "" => ">".to_string(),
+                line => format!("> {}", line),
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        NodeKind::BlockEmoji { name, attrs } => {
+            if attrs.is_empty() {
+                format!("::{}", name)
+            } else {
+                format!("::{} {}", name, dict(attrs))
+            }
+        }
+        NodeKind::Tag { name, attrs } if node.is_block() => format!(
+            "<{}{}>\n\n{}\n\n</{}>",
+            name,
+            attributes(attrs),
+            blocks(&node.children, options),
+            name
+        ),
+        // inline content found at the block level
+        _ => wrap(&words(std::slice::from_ref(node)), options.width),
+    }
+}
+
+/// the length of the fence when the line of code would close a fenced
+/// code block (i.e., it's three or more backticks)
+fn closing_fence(line: &str) -> Option<usize> {
+    let line = line.trim_end_matches([' ', '\t']);
+    (line.len() >= 3 && line.chars().all(|c| c == '`')).then_some(line.len())
+}
+
+/// a heading's title with any `#` at its start or end written as a
+/// reference so it isn't mistaken for part of the heading's markers
+fn heading_title(title: &str) -> String {
+    let start = title.len() - title.trim_start_matches('#').len();
+    let end = title.trim_end_matches('#').len().max(start);
+
+    format!(
+        "{}{}{}",
+        "&#35;".repeat(start),
+        &title[start..end],
+        "&#35;".repeat(title.len() - end)
+    )
+}
+
+fn list(node: &Node, ordered: bool, options: &MarkdownOptions) -> String {
+    let start = match node.children.first().map(|i| &i.kind) {
+        Some(NodeKind::ListItem { marker }) if ordered => marker
+            .trim_end_matches(['.', ')'])
+            .parse::<usize>()
+            .unwrap_or(1),
+        _ => 1,
+    };
+
+    node.children
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let marker = if ordered {
+                format!("{}.", start + idx)
+            } else {
+                options.bullet.to_string()
+            };
+            let indent = " ".repeat(marker.len() + 1);
+            let content = item_blocks(&item.children, options);
+
+            let mut lines = content.lines();
+            let mut md = format!("{} {}", marker, lines.next().unwrap_or(""));
+            for line in lines {
+                md.push('\n');
+                if !line.is_empty() {
+                    md.push_str(&indent);
+                    md.push_str(line);
+                }
+            }
+            md
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// the blocks within a list item; nested lists are kept "tight"
+/// against the block which precedes them
+fn item_blocks(nodes: &[Node], options: &MarkdownOptions) -> String {
+    let mut md = String::new();
+
+    for node in nodes {
+        let content = block(node, options);
+        if content.is_empty() {
+            continue;
+        }
+        if !md.is_empty() {
+            match node.kind {
+                NodeKind::List { .. } => md.push('\n'),
+                _ => md.push_str("\n\n"),
+            }
+        }
+        md.push_str(&content);
+    }
+
+    md
+}
+
+/// the normalized `dict` syntax for a set of attributes
+fn dict(attrs: &[(String, String)]) -> String {
+    let props: Vec<String> = attrs
+        .iter()
+        .map(|(k, v)| format!("{}: \"{}\"", k, v))
+        .collect();
+
+    format!("{{ {} }}", props.join(", "))
+}
+
+fn attributes(attrs: &[(String, String)]) -> String {
+    attrs
+        .iter()
+        .map(|(k, v)| format!(" {}=\"{}\"", k, v))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+fn inline(nodes: &[Node]) -> String {
+    nodes.iter().map(inline_node).collect::<Vec<String>>().concat()
+}
+
+fn inline_node(node: &Node) -> String {
+    match &node.kind {
+        NodeKind::Text(text) => escape_references(text).into_owned(),
+        NodeKind::Html(html) => html.clone(),
+        NodeKind::SoftBreak => "\n".to_string(),
+        NodeKind::HardBreak => "\\\n".to_string(),
+        NodeKind::Link { dest } => {
+            format!("[{}]({})", inline(&node.children), escape_references(dest))
+        }
+        NodeKind::Image { src } => {
+            format!("![{}]({})", inline(&node.children), escape_references(src))
+        }
+        NodeKind::Emoji { name } => format!("::{}::", name),
+        NodeKind::Tag { name, attrs } => {
+            if node.children.is_empty() {
+                format!("<{}{} />", name, attributes(attrs))
+            } else {
+                format!(
+                    "<{}{}>{}</{}>",
+                    name,
+                    attributes(attrs),
+                    inline(&node.children),
+                    name
+                )
+            }
+        }
+        _ => inline(&node.children),
+    }
+}
+
+/// the "word" which ends a line regardless of the width
+const LINE_BREAK: &str = "\n";
+
+/// Breaks inline content into the "words" which paragraph wrapping
+/// can occur between. Only whitespace in text (and soft breaks) is a
+/// valid break point; links, tags, etc. are never split across lines.
+/// Hard breaks are always written as a trailing backslash.
+fn words(nodes: &[Node]) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+    // whether the next piece of content attaches to the last word
+    let mut attached = false;
+
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Text(text) => {
+                let text = escape_references(text);
+                let starts_with_space = text.starts_with(char::is_whitespace);
+                for (idx, word) in text.split_whitespace().enumerate() {
+                    match words.last_mut() {
+                        Some(last) if idx == 0 && attached && !starts_with_space => {
+                            last.push_str(word)
+                        }
+                        _ => words.push(word.to_string()),
+                    }
+                }
+                if !text.trim().is_empty() {
+                    attached = !text.ends_with(char::is_whitespace);
+                } else if !text.is_empty() {
+                    attached = false;
+                }
+            }
+            NodeKind::SoftBreak => attached = false,
+            NodeKind::HardBreak => {
+                if let Some(last) = words.last_mut() {
+                    last.push('\\');
+                }
+                words.push(LINE_BREAK.to_string());
+                attached = false;
+            }
+            _ => {
+                let content = inline_node(node);
+                match words.last_mut() {
+                    Some(last) if attached => last.push_str(&content),
+                    _ => words.push(content),
+                }
+                attached = true;
+            }
+        }
+    }
+
+    words
+}
+
+/// whether a line starting with the word could be parsed as the start
+/// of a block (e.g., a heading or list item) rather than a continuation
+fn could_start_block(word: &str) -> bool {
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    word.starts_with(['#', '>', '-', '+', '*', '_', '=', '<', '`', '~', ':'])
+        || word.starts_with("[[")
+        || (digits > 0 && word[digits..].starts_with(['.', ')']))
+}
+
+/// A word which starts a line with its first character written as a
+/// reference when it could otherwise start a block.
+fn escape_block_start(word: &str) -> String {
+    match word.chars().next() {
+        Some(c) if could_start_block(word) && !matches!(c, '<' | ':' | '[') => {
+            format!("&#{};{}", c as u32, &word[c.len_utf8()..])
+        }
+        _ => word.to_string(),
+    }
+}
+
+/// greedily fills lines with words up to the given width; a word is
+/// never moved to the start of a line where it could start a block
+fn wrap(words: &[String], width: usize) -> String {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+
+    for word in words {
+        if word == LINE_BREAK {
+            lines.push(std::mem::take(&mut line));
+        } else if line.is_empty() {
+            line.push_str(&escape_block_start(word));
+        } else if width > 0
+            && line.chars().count() + 1 + word.chars().count() > width
+            && !could_start_block(word)
+        {
+            lines.push(line);
+            line = word.clone();
+        } else {
+            line.push(' ');
+            line.push_str(word);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::build::{node, text};
+
+    #[test]
+    fn soft_breaks_reflow_and_hard_breaks_are_kept() {
+        let p = node(
+            NodeKind::Paragraph,
+            vec![
+                text("foo"),
+                node(NodeKind::SoftBreak, vec![]),
+                text("bar"),
+                node(NodeKind::HardBreak, vec![]),
+                text("baz"),
+            ],
+        );
+
+        assert_eq!(
+            to_markdown(&p, &MarkdownOptions::default()),
+            "foo bar\\\nbaz\n"
+        );
+    }
+
+    #[test]
+    fn paragraphs_wrap_at_width() {
+        let p = node(
+            NodeKind::Paragraph,
+            vec![
+                text("the quick brown fox "),
+                node(
+                    NodeKind::Link {
+                        dest: "https://fox.com".to_string(),
+                    },
+                    vec![text("jumps over")],
+                ),
+                text(", the lazy dog"),
+            ],
+        );
+        let options = MarkdownOptions {
+            width: 24,
+            ..MarkdownOptions::default()
+        };
+
+        assert_eq!(
+            to_markdown(&p, &options),
+            "the quick brown fox\n[jumps over](https://fox.com),\nthe lazy dog\n"
+        );
+    }
+
+    #[test]
+    fn lists_use_a_consistent_marker() {
+        let item = |marker: &str, t: &str, children: Vec<Node>| {
+            let mut c = vec![node(NodeKind::Paragraph, vec![text(t)])];
+            c.extend(children);
+            node(
+                NodeKind::ListItem {
+                    marker: marker.to_string(),
+                },
+                c,
+            )
+        };
+        let nested = node(
+            NodeKind::List { ordered: true },
+            vec![item("3)", "baz", vec![]), item("4)", "qux", vec![])],
+        );
+        let list = node(
+            NodeKind::List { ordered: false },
+            vec![item("+", "foo", vec![nested]), item("*", "bar", vec![])],
+        );
+
+        assert_eq!(
+            to_markdown(&list, &MarkdownOptions::default()),
+            "- foo\n  3. baz\n  4. qux\n- bar\n"
+        );
+    }
+
+    #[test]
+    fn block_emoji_attributes_are_normalized() {
+        let emoji = node(
+            NodeKind::BlockEmoji {
+                name: "smile".to_string(),
+                attrs: vec![
+                    ("color".to_string(), "red".to_string()),
+                    ("size".to_string(), "2x".to_string()),
+                ],
+            },
+            vec![],
+        );
+
+        assert_eq!(
+            to_markdown(&emoji, &MarkdownOptions::default()),
+            "::smile { color: \"red\", size: \"2x\" }\n"
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_fenced() {
+        let code = node(
+            NodeKind::CodeBlock {
+                lang: Some("rust".to_string()),
+                info: None,
+            },
+            vec![text("let x = 1;")],
+        );
+
+        assert_eq!(
+            to_markdown(&code, &MarkdownOptions::default()),
+            "```rust\nlet x = 1;\n```\n"
+        );
+    }
+
+    #[test]
+    fn code_containing_a_fence_has_a_longer_fence() {
+        let code = |lang: Option<&str>, code: &str| {
+            node(
+                NodeKind::CodeBlock {
+                    lang: lang.map(String::from),
+                    info: None,
+                },
+                vec![text(code)],
+            )
+        };
+        let options = MarkdownOptions::default();
+
+        assert_eq!(
+            to_markdown(&code(None, "a\n```\nb"), &options),
+            "````\na\n```\nb\n````\n"
+        );
+        assert_eq!(
+            to_markdown(&code(Some("md"), "a\n`````  \n```"), &options),
+            "``````md\na\n`````  \n```\n``````\n"
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! Conversion to (and from) [mdast](https://github.com/syntax-tree/mdast),
+//! the Markdown syntax tree used by the remark ecosystem, so documents
+//! parsed here can be handed to (or received from) JavaScript tooling.
+//!
+//! Most of the tree maps directly onto mdast's types. The rest map to
+//! the types of widely used mdast extensions or -- where there are none
+//! -- to custom types:
+//!
+//! - tags are `mdxJsxFlowElement`s (or `mdxJsxTextElement`s when inline)
+//!   as defined by `mdast-util-mdx-jsx`
+//! - emoji are `emoji` nodes and block emoji are `blockEmoji` nodes with
+//!   the same `attributes` as an MDX element
+//! - the table of contents placeholder is a `toc` node
+//! - a heading's `id` is kept as `data.hProperties.id` (which is how
+//!   `remark-rehype` sets an element's attributes)
+//!
+//! mdast has no soft breaks; they're exported as a newline within the
+//! surrounding text and split back out when imported.
+//!
+//! Types the tree has no node for (e.g., remark's `emphasis`) are
+//! imported as their children -- or their `value` as text -- so the
+//! content isn't lost.
+//!
+//! ```
+//! use parser::{ast::{Node, NodeKind, Span}, mdast::{from_mdast, to_mdast}};
+//!
+//! let heading = Node::with_children(
+//!     NodeKind::Heading { level: 2, id: None },
+//!     Span::new(0, 6),
+//!     vec![Node::text("Foo", Span::new(3, 6))],
+//! );
+//! let mdast = to_mdast(&heading, "## Foo");
+//!
+//! assert_eq!(
+//!     serde_json::to_value(&mdast).unwrap()["type"],
+//!     "heading"
+//! );
+//! assert!(from_mdast(&mdast).is_equivalent(&heading));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::ast::{Node, NodeKind, Span};
+
+/// A point in the source; the line and column start at 1 while the
+/// offset is the byte offset from the start of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Point {
+    pub line: usize,
+    pub column: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// Where a node was found in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub start: Point,
+    pub end: Point,
+}
+
+/// An attribute of an MDX element (or block emoji).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "mdxJsxAttribute")]
+pub struct Attribute {
+    pub name: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// The type of an mdast node along with the fields specific to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum MdastKind {
+    Root,
+    Heading {
+        depth: u8,
+    },
+    Paragraph,
+    ThematicBreak,
+    Code {
+        #[serde(default)]
+        lang: Option<String>,
+        #[serde(default)]
+        meta: Option<String>,
+        value: String,
+    },
+    List {
+        ordered: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start: Option<u64>,
+        #[serde(default)]
+        spread: bool,
+    },
+    ListItem {
+        #[serde(default)]
+        spread: bool,
+    },
+    Blockquote,
+    Link {
+        url: String,
+        #[serde(default)]
+        title: Option<String>,
+    },
+    Image {
+        url: String,
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        alt: Option<String>,
+    },
+    Break,
+    Html {
+        value: String,
+    },
+    Text {
+        value: String,
+    },
+    MdxJsxFlowElement {
+        name: String,
+        #[serde(default)]
+        attributes: Vec<Attribute>,
+    },
+    MdxJsxTextElement {
+        name: String,
+        #[serde(default)]
+        attributes: Vec<Attribute>,
+    },
+    Emoji {
+        name: String,
+    },
+    BlockEmoji {
+        name: String,
+        #[serde(default)]
+        attributes: Vec<Attribute>,
+    },
+    Toc,
+    /// any other type (e.g., `emphasis` or `inlineCode`)
+    #[serde(untagged)]
+    Other {
+        #[serde(rename = "type")]
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+    },
+}
+
+/// An mdast node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MdastNode {
+    #[serde(flatten)]
+    pub kind: MdastKind,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<MdastNode>,
+    /// information for tools further along (e.g., `hProperties`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+}
+
+impl MdastNode {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+/// the byte offsets at which each line of the content starts
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(idx, _)| idx + 1))
+        .collect()
+}
+
+fn point(content: &str, starts: &[usize], offset: usize) -> Point {
+    let offset = offset.min(content.len());
+    let line = starts.partition_point(|start| *start <= offset);
+    let column = content[starts[line - 1]..offset].chars().count() + 1;
+
+    Point {
+        line,
+        column,
+        offset: Some(offset),
+    }
+}
+
+fn attributes(attrs: &[(String, String)]) -> Vec<Attribute> {
+    attrs
+        .iter()
+        .map(|(name, value)| Attribute {
+            name: name.clone(),
+            value: Some(value.clone()),
+        })
+        .collect()
+}
+
+/// the children with any soft breaks merged into the surrounding text
+fn children(nodes: &[Node], content: &str, starts: &[usize]) -> Vec<MdastNode> {
+    let mut children: Vec<MdastNode> = vec![];
+
+    for node in nodes {
+        let text = match &node.kind {
+            NodeKind::Text(text) => text.as_str(),
+            NodeKind::SoftBreak => "\n",
+            _ => {
+                children.push(convert(node, content, starts));
+                continue;
+            }
+        };
+        match children.last_mut() {
+            Some(MdastNode {
+                kind: MdastKind::Text { value },
+                position,
+                ..
+            }) => {
+                value.push_str(text);
+                if let Some(position) = position {
+                    position.end = point(content, starts, node.span.end);
+                }
+            }
+            _ => children.push(convert(&Node::text(text, node.span), content, starts)),
+        }
+    }
+
+    children
+}
+
+fn convert(node: &Node, content: &str, starts: &[usize]) -> MdastNode {
+    let mut data = None;
+    let kind = match &node.kind {
+        NodeKind::Document => MdastKind::Root,
+        NodeKind::Heading { level, id } => {
+            data = id.as_ref().map(|id| json!({ "hProperties": { "id": id } }));
+            MdastKind::Heading { depth: *level }
+        }
+        NodeKind::Paragraph => MdastKind::Paragraph,
+        NodeKind::ThematicBreak => MdastKind::ThematicBreak,
+        NodeKind::CodeBlock { lang, info } => MdastKind::Code {
+            lang: lang.clone(),
+            meta: info.clone(),
+            value: node.text_content(),
+        },
+        NodeKind::Tag { name, attrs } if node.is_block() => MdastKind::MdxJsxFlowElement {
+            name: name.clone(),
+            attributes: attributes(attrs),
+        },
+        NodeKind::Tag { name, attrs } => MdastKind::MdxJsxTextElement {
+            name: name.clone(),
+            attributes: attributes(attrs),
+        },
+        NodeKind::List { ordered } => MdastKind::List {
+            ordered: *ordered,
+            start: match ordered {
+                true => node.children.first().and_then(|item| match &item.kind {
+                    NodeKind::ListItem { marker } => {
+                        marker.trim_end_matches(['.', ')']).parse().ok()
+                    }
+                    _ => None,
+                }),
+                false => None,
+            },
+            spread: false,
+        },
+        NodeKind::ListItem { .. } => MdastKind::ListItem { spread: false },
+        NodeKind::BlockQuote => MdastKind::Blockquote,
+        NodeKind::Link { dest } => MdastKind::Link {
+            url: dest.clone(),
+            title: None,
+        },
+        NodeKind::Image { src } => MdastKind::Image {
+            url: src.clone(),
+            title: None,
+            alt: Some(node.text_content()),
+        },
+        NodeKind::Emoji { name } => MdastKind::Emoji { name: name.clone() },
+        NodeKind::BlockEmoji { name, attrs } => MdastKind::BlockEmoji {
+            name: name.clone(),
+            attributes: attributes(attrs),
+        },
+        NodeKind::Toc => MdastKind::Toc,
+        NodeKind::SoftBreak => MdastKind::Text {
+            value: "\n".to_string(),
+        },
+        NodeKind::HardBreak => MdastKind::Break,
+        NodeKind::Html(html) => MdastKind::Html {
+            value: html.clone(),
+        },
+        NodeKind::Text(text) => MdastKind::Text {
+            value: text.clone(),
+        },
+    };
+    // the content of code and images are fields rather than children
+    let children = match kind {
+        MdastKind::Code { .. } | MdastKind::Image { .. } => vec![],
+        _ => children(&node.children, content, starts),
+    };
+
+    MdastNode {
+        kind,
+        children,
+        data,
+        position: Some(Position {
+            start: point(content, starts, node.span.start),
+            end: point(content, starts, node.span.end),
+        }),
+    }
+}
+
+/// Converts the tree to mdast; the content is the source the tree was
+/// parsed from and is used to work out the line and column of each
+/// node's position.
+pub fn to_mdast(tree: &Node, content: &str) -> MdastNode {
+    convert(tree, content, &line_starts(content))
+}
+
+fn span(node: &MdastNode) -> Span {
+    match node.position {
+        Some(Position {
+            start: Point {
+                offset: Some(start),
+                ..
+            },
+            end: Point {
+                offset: Some(end), ..
+            },
+        }) => Span::new(start, end),
+        _ => Span::default(),
+    }
+}
+
+fn attrs(attributes: &[Attribute]) -> Vec<(String, String)> {
+    attributes
+        .iter()
+        .map(|a| (a.name.clone(), a.value.clone().unwrap_or_default()))
+        .collect()
+}
+
+/// text with each newline as a soft break
+fn text(value: &str, span: Span) -> Vec<Node> {
+    let mut nodes = vec![];
+
+    for (idx, line) in value.split('\n').enumerate() {
+        if idx > 0 {
+            nodes.push(Node::new(NodeKind::SoftBreak, span));
+        }
+        if !line.is_empty() {
+            nodes.push(Node::text(line, span));
+        }
+    }
+
+    nodes
+}
+
+fn import(node: &MdastNode) -> Vec<Node> {
+    let span = span(node);
+    let kind = match &node.kind {
+        MdastKind::Root => NodeKind::Document,
+        MdastKind::Heading { depth } => NodeKind::Heading {
+            level: *depth,
+            id: node
+                .data
+                .as_ref()
+                .and_then(|data| data["hProperties"]["id"].as_str())
+                .map(String::from),
+        },
+        MdastKind::Paragraph => NodeKind::Paragraph,
+        MdastKind::ThematicBreak => NodeKind::ThematicBreak,
+        MdastKind::Code { lang, meta, value } => {
+            let kind = NodeKind::CodeBlock {
+                lang: lang.clone(),
+                info: meta.clone(),
+            };
+            return vec![Node::with_children(
+                kind,
+                span,
+                vec![Node::text(value, span)],
+            )];
+        }
+        MdastKind::List { ordered, start, .. } => {
+            let start = start.unwrap_or(1);
+            let items = node
+                .children
+                .iter()
+                .enumerate()
+                .map(|(idx, item)| {
+                    let marker = match ordered {
+                        true => format!("{}.", start + idx as u64),
+                        false => "-".to_string(),
+                    };
+                    Node::with_children(
+                        NodeKind::ListItem { marker },
+                        self::span(item),
+                        item.children.iter().flat_map(import).collect(),
+                    )
+                })
+                .collect();
+            return vec![Node::with_children(
+                NodeKind::List { ordered: *ordered },
+                span,
+                items,
+            )];
+        }
+        MdastKind::ListItem { .. } => NodeKind::ListItem {
+            marker: "-".to_string(),
+        },
+        MdastKind::Blockquote => NodeKind::BlockQuote,
+        MdastKind::Link { url, .. } => NodeKind::Link { dest: url.clone() },
+        MdastKind::Image { url, alt, .. } => {
+            let kind = NodeKind::Image { src: url.clone() };
+            let alt = alt.as_deref().map(|alt| text(alt, span));
+            return vec![Node::with_children(kind, span, alt.unwrap_or_default())];
+        }
+        MdastKind::Break => NodeKind::HardBreak,
+        MdastKind::Html { value } => NodeKind::Html(value.clone()),
+        MdastKind::Text { value } => return text(value, span),
+        MdastKind::MdxJsxFlowElement { name, attributes }
+        | MdastKind::MdxJsxTextElement { name, attributes } => NodeKind::Tag {
+            name: name.clone(),
+            attrs: attrs(attributes),
+        },
+        MdastKind::Emoji { name } => NodeKind::Emoji { name: name.clone() },
+        MdastKind::BlockEmoji { name, attributes } => NodeKind::BlockEmoji {
+            name: name.clone(),
+            attrs: attrs(attributes),
+        },
+        MdastKind::Toc => NodeKind::Toc,
+        MdastKind::Other { value, .. } => {
+            return match (value, node.children.is_empty()) {
+                (Some(value), true) => text(value, span),
+                _ => node.children.iter().flat_map(import).collect(),
+            }
+        }
+    };
+
+    vec![Node::with_children(
+        kind,
+        span,
+        node.children.iter().flat_map(import).collect(),
+    )]
+}
+
+/// Converts mdast to a tree; a node's span is taken from the offsets
+/// of its position (when it has them).
+///
+/// If the root is converted to several nodes (e.g., text with newlines)
+/// they're wrapped in a `Document`.
+pub fn from_mdast(mdast: &MdastNode) -> Node {
+    let mut nodes = import(mdast);
+
+    match nodes.len() {
+        1 => nodes.remove(0),
+        _ => Node::with_children(NodeKind::Document, span(mdast), nodes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::build::{node, text};
+
+    /// the tree of `CONTENT`
+    fn tree() -> Node {
+        node(
+            NodeKind::Document,
+            vec![
+                node(
+                    NodeKind::Heading {
+                        level: 1,
+                        id: Some("foo".to_string()),
+                    },
+                    vec![text("Foo").at(2, 5)],
+                )
+                .at(0, 5),
+                node(
+                    NodeKind::Paragraph,
+                    vec![
+                        text("bär").at(7, 11),
+                        node(NodeKind::SoftBreak, vec![]).at(11, 12),
+                        node(
+                            NodeKind::Link {
+                                dest: "/x".to_string(),
+                            },
+                            vec![text("x").at(13, 14)],
+                        )
+                        .at(12, 19),
+                    ],
+                )
+                .at(7, 19),
+                node(
+                    NodeKind::List { ordered: true },
+                    vec![node(
+                        NodeKind::ListItem {
+                            marker: "3.".to_string(),
+                        },
+                        vec![node(
+                            NodeKind::Emoji {
+                                name: "smile".to_string(),
+                            },
+                            vec![],
+                        )
+                        .at(24, 27)],
+                    )
+                    .at(21, 27)],
+                )
+                .at(21, 27),
+                node(
+                    NodeKind::Tag {
+                        name: "my-tag".to_string(),
+                        attrs: vec![("a".to_string(), "1".to_string())],
+                    },
+                    vec![node(
+                        NodeKind::CodeBlock {
+                            lang: Some("rust".to_string()),
+                            info: None,
+                        },
+                        vec![text("x").at(40, 43)],
+                    )
+                    .at(40, 43)],
+                )
+                .at(29, 52),
+            ],
+        )
+        .at(0, 52)
+    }
+
+    const CONTENT: &str = "# Foo\n\nbär\n[x](/x)\n\n3. :s:\n\n<my-tag a=\"1\">\n```\n</my-tag>\n";
+
+    #[test]
+    fn trees_export_to_mdast() {
+        let mdast = serde_json::to_value(to_mdast(&tree(), CONTENT)).unwrap();
+        let children = &mdast["children"];
+
+        assert_eq!(mdast["type"], "root");
+        assert_eq!(children[0]["type"], "heading");
+        assert_eq!(children[0]["depth"], 1);
+        assert_eq!(children[0]["data"]["hProperties"]["id"], "foo");
+
+        let paragraph = &children[1]["children"];
+        assert_eq!(
+            paragraph[0],
+            json!({
+                "type": "text",
+                "value": "bär\n",
+                "position": {
+                    "start": { "line": 3, "column": 1, "offset": 7 },
+                    "end": { "line": 4, "column": 1, "offset": 12 }
+                }
+            })
+        );
+        assert_eq!(paragraph[1]["url"], "/x");
+
+        assert_eq!(children[2]["start"], 3);
+        assert_eq!(children[2]["children"][0]["children"][0]["type"], "emoji");
+        assert_eq!(children[3]["type"], "mdxJsxFlowElement");
+        assert_eq!(
+            children[3]["attributes"],
+            json!([{ "type": "mdxJsxAttribute", "name": "a", "value": "1" }])
+        );
+        assert_eq!(children[3]["children"][0]["value"], "x");
+        assert!(children[3]["children"][0].get("children").is_none());
+    }
+
+    #[test]
+    fn mdast_imports_to_an_equivalent_tree() {
+        let json = to_mdast(&tree(), CONTENT).to_json().unwrap();
+        let imported = from_mdast(&MdastNode::from_json(&json).unwrap());
+
+        assert!(imported.is_equivalent(&tree()));
+        assert_eq!(imported.children[3].span, Span::new(29, 52));
+    }
+
+    #[test]
+    fn mdast_from_remark_imports() {
+        let json = r#"{
+            "type": "root",
+            "children": [
+                {
+                    "type": "paragraph",
+                    "children": [
+                        { "type": "text", "value": "a" },
+                        { "type": "break" },
+                        { "type": "image", "url": "b.png", "alt": "b", "title": null }
+                    ]
+                }
+            ]
+        }"#;
+        let tree = from_mdast(&MdastNode::from_json(json).unwrap());
+        let paragraph = &tree.children[0];
+
+        assert_eq!(paragraph.children[1].kind, NodeKind::HardBreak);
+        assert_eq!(paragraph.children[2].text_content(), "b");
+        assert!(MdastNode::from_json(r#"{ "value": "a" }"#).is_err());
+    }
+
+    #[test]
+    fn unknown_types_keep_their_content() {
+        // remark's tree for `a *b* `c``
+        let json = r#"{
+            "type": "root",
+            "children": [
+                {
+                    "type": "paragraph",
+                    "children": [
+                        { "type": "text", "value": "a " },
+                        { "type": "emphasis", "children": [{ "type": "text", "value": "b" }] },
+                        { "type": "text", "value": " " },
+                        { "type": "inlineCode", "value": "c" }
+                    ]
+                }
+            ]
+        }"#;
+        let mdast = MdastNode::from_json(json).unwrap();
+        let emphasis = &mdast.children[0].children[1];
+
+        assert_eq!(
+            emphasis.kind,
+            MdastKind::Other {
+                name: "emphasis".to_string(),
+                value: None
+            }
+        );
+        assert_eq!(serde_json::to_value(emphasis).unwrap()["type"], "emphasis");
+        assert_eq!(from_mdast(&mdast).text_content(), "a b c");
+    }
+}
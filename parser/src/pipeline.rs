@@ -0,0 +1,536 @@
+//! Named transform plugins which run over the tree once it's parsed and
+//! before it's rendered (i.e., between the `Parsed` and `Transformed`
+//! stages) in an order worked out from their declared dependencies.
+//!
+//! ```
+//! use parser::{
+//!     ast::{Node, NodeKind, Span},
+//!     pipeline::{Pipeline, PipelineConfig, PluginRegistry},
+//! };
+//!
+//! let config: PipelineConfig = serde_json::from_str(r#"{
+//!     "plugins": [
+//!         { "name": "rewrite-links", "options": { "base": "https://docs.com" } },
+//!         { "name": "strip-comments" }
+//!     ]
+//! }"#).unwrap();
+//! let mut pipeline = PluginRegistry::builtin().pipeline(&config).unwrap();
+//!
+//! let mut tree = Node::new(NodeKind::Document, Span::default());
+//! pipeline.run(&mut tree).unwrap();
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    ast::{Node, NodeKind},
+    sanitize::{sanitize, SanitizePolicy},
+    slug::assign_ids,
+    visit::{transform, Transformer},
+};
+
+/// A named pass over the tree.
+pub trait Plugin {
+    /// the name the plugin is registered (and configured) under
+    fn name(&self) -> &str;
+
+    /// plugins which must be in the pipeline and run before this one
+    fn requires(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// plugins which -- _if_ they're in the pipeline -- run before
+    /// this one
+    fn after(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineError {
+    /// a plugin was added twice
+    Duplicate(String),
+    /// a configured plugin isn't in the registry
+    Unknown(String),
+    /// a plugin requires a plugin which isn't in the pipeline
+    MissingDependency { plugin: String, dependency: String },
+    /// the plugins which depend on each other
+    Cycle(Vec<String>),
+    /// a plugin's options couldn't be used
+    InvalidOptions { plugin: String, message: String },
+    /// a plugin failed while running
+    Failed { plugin: String, message: String },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(name) => write!(f, "the plugin '{}' was added twice", name),
+            Self::Unknown(name) => write!(f, "there's no plugin called '{}'", name),
+            Self::MissingDependency { plugin, dependency } => write!(
+                f,
+                "the plugin '{}' requires '{}' which isn't in the pipeline",
+                plugin, dependency
+            ),
+            Self::Cycle(names) => {
+                write!(f, "the plugins {} depend on each other", names.join(", "))
+            }
+            Self::InvalidOptions { plugin, message } => {
+                write!(
+                    f,
+                    "invalid options for the plugin '{}': {}",
+                    plugin, message
+                )
+            }
+            Self::Failed { plugin, message } => {
+                write!(f, "the plugin '{}' failed: {}", plugin, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+/// Plugins in the order they run.
+#[derive(Default)]
+pub struct Pipeline {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// adds a plugin; plugins with no ordering between them run in
+    /// the order they were added
+    pub fn add<P: Plugin + 'static>(&mut self, plugin: P) -> Result<&mut Self, PipelineError> {
+        self.add_boxed(Box::new(plugin))
+    }
+
+    pub fn add_boxed(&mut self, plugin: Box<dyn Plugin>) -> Result<&mut Self, PipelineError> {
+        if self.plugins.iter().any(|p| p.name() == plugin.name()) {
+            return Err(PipelineError::Duplicate(plugin.name().to_string()));
+        }
+        self.plugins.push(plugin);
+        Ok(self)
+    }
+
+    /// The names of the plugins in the order they run; plugins run
+    /// after the plugins they require (or should run after) and are
+    /// otherwise kept in the order they were added.
+    pub fn order(&self) -> Result<Vec<&str>, PipelineError> {
+        let names: Vec<&str> = self.plugins.iter().map(|p| p.name()).collect();
+        let index = |name: &str| names.iter().position(|n| *n == name);
+
+        // the plugins which must run before each plugin
+        let mut before: Vec<Vec<usize>> = vec![vec![]; names.len()];
+        for (idx, plugin) in self.plugins.iter().enumerate() {
+            for dependency in plugin.requires() {
+                match index(&dependency) {
+                    Some(dep) => before[idx].push(dep),
+                    None => {
+                        return Err(PipelineError::MissingDependency {
+                            plugin: plugin.name().to_string(),
+                            dependency,
+                        })
+                    }
+                }
+            }
+            before[idx].extend(plugin.after().iter().filter_map(|n| index(n)));
+        }
+
+        let mut order: Vec<usize> = vec![];
+        while order.len() < names.len() {
+            let next = (0..names.len()).find(|idx| {
+                !order.contains(idx) && before[*idx].iter().all(|dep| order.contains(dep))
+            });
+            match next {
+                Some(idx) => order.push(idx),
+                None => {
+                    let cycle = (0..names.len())
+                        .filter(|idx| !order.contains(idx))
+                        .map(|idx| names[idx].to_string())
+                        .collect();
+                    return Err(PipelineError::Cycle(cycle));
+                }
+            }
+        }
+
+        Ok(order.into_iter().map(|idx| names[idx]).collect())
+    }
+
+    /// runs every plugin over the tree
+    pub fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError> {
+        let order: Vec<String> = self.order()?.into_iter().map(String::from).collect();
+
+        for name in order {
+            if let Some(plugin) = self.plugins.iter_mut().find(|p| p.name() == name) {
+                plugin.run(tree)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A plugin which applies a `Transformer` to the whole tree.
+pub struct TransformPlugin<T: Transformer> {
+    name: String,
+    requires: Vec<String>,
+    after: Vec<String>,
+    transformer: T,
+}
+
+impl<T: Transformer> TransformPlugin<T> {
+    pub fn new(name: &str, transformer: T) -> Self {
+        Self {
+            name: name.to_string(),
+            requires: vec![],
+            after: vec![],
+            transformer,
+        }
+    }
+
+    pub fn requires(mut self, plugin: &str) -> Self {
+        self.requires.push(plugin.to_string());
+        self
+    }
+
+    pub fn after(mut self, plugin: &str) -> Self {
+        self.after.push(plugin.to_string());
+        self
+    }
+}
+
+impl<T: Transformer> Plugin for TransformPlugin<T> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn requires(&self) -> Vec<String> {
+        self.requires.clone()
+    }
+
+    fn after(&self) -> Vec<String> {
+        self.after.clone()
+    }
+
+    fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError> {
+        transform(tree, &mut self.transformer);
+        Ok(())
+    }
+}
+
+/// The configuration of a single plugin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginConfig {
+    pub name: String,
+    /// the plugin's options; their shape is up to the plugin
+    #[serde(default)]
+    pub options: Value,
+}
+
+/// The plugins to enable (e.g., from the CLI's config file).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+}
+
+type Factory = dyn Fn(&Value) -> Result<Box<dyn Plugin>, PipelineError> + Send + Sync;
+
+/// The plugins which can be enabled by name, along with how each is
+/// created from its options.
+#[derive(Default)]
+pub struct PluginRegistry {
+    factories: HashMap<String, Box<Factory>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// a registry with the plugins which ship with the parser:
+    ///
+    /// - `heading-ids` -- sets each heading's `id` to its slug
+    /// - `strip-comments` -- removes HTML comments
+    /// - `rewrite-links` -- prefixes relative links and images with the
+    ///   `base` option
+    /// - `sanitize` -- applies a `SanitizePolicy`; the options are the
+    ///   policy (or empty for the default policy)
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry
+            .register("heading-ids", |_| Ok(Box::new(HeadingIds)))
+            .register("strip-comments", |_| {
+                Ok(Box::new(TransformPlugin::new(
+                    "strip-comments",
+                    StripComments,
+                )))
+            })
+            .register("rewrite-links", |options| {
+                let base = options
+                    .get("base")
+                    .and_then(|b| b.as_str())
+                    .ok_or_else(|| PipelineError::InvalidOptions {
+                        plugin: "rewrite-links".to_string(),
+                        message: "the 'base' URL is required".to_string(),
+                    })?;
+                Ok(Box::new(TransformPlugin::new(
+                    "rewrite-links",
+                    RewriteLinks {
+                        base: base.trim_end_matches('/').to_string(),
+                    },
+                )))
+            })
+            .register("sanitize", |options| {
+                let policy = match options {
+                    Value::Null => SanitizePolicy::default(),
+                    options => serde_json::from_value(options.clone()).map_err(|e| {
+                        PipelineError::InvalidOptions {
+                            plugin: "sanitize".to_string(),
+                            message: e.to_string(),
+                        }
+                    })?,
+                };
+                Ok(Box::new(Sanitize(policy)))
+            });
+
+        registry
+    }
+
+    /// registers the factory which creates the plugin `name` from its
+    /// options; any existing factory for the name is replaced
+    pub fn register<F>(&mut self, name: &str, factory: F) -> &mut Self
+    where
+        F: Fn(&Value) -> Result<Box<dyn Plugin>, PipelineError> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
+        self
+    }
+
+    /// the names of the registered plugins
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn create(&self, config: &PluginConfig) -> Result<Box<dyn Plugin>, PipelineError> {
+        match self.factories.get(&config.name) {
+            Some(factory) => factory(&config.options),
+            None => Err(PipelineError::Unknown(config.name.clone())),
+        }
+    }
+
+    /// a pipeline of the configured plugins; the order is checked so a
+    /// pipeline which can't run is an error here rather than later
+    pub fn pipeline(&self, config: &PipelineConfig) -> Result<Pipeline, PipelineError> {
+        let mut pipeline = Pipeline::new();
+        for plugin in &config.plugins {
+            pipeline.add_boxed(self.create(plugin)?)?;
+        }
+        pipeline.order()?;
+
+        Ok(pipeline)
+    }
+}
+
+impl fmt::Debug for PluginRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginRegistry")
+            .field("plugins", &self.names())
+            .finish()
+    }
+}
+
+struct HeadingIds;
+
+impl Plugin for HeadingIds {
+    fn name(&self) -> &str {
+        "heading-ids"
+    }
+
+    fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError> {
+        assign_ids(tree);
+        Ok(())
+    }
+}
+
+struct StripComments;
+
+impl Transformer for StripComments {
+    fn transform(&mut self, node: Node) -> Vec<Node> {
+        match &node.kind {
+            NodeKind::Html(html) if html.trim_start().starts_with("<!--") => vec![],
+            _ => vec![node],
+        }
+    }
+}
+
+/// whether the URL is relative to the document (rather than absolute,
+/// root-relative or a fragment)
+fn is_relative(url: &str) -> bool {
+    !(url.is_empty() || url.contains(':') || url.starts_with(['/', '#', '?']))
+}
+
+struct RewriteLinks {
+    base: String,
+}
+
+impl Transformer for RewriteLinks {
+    fn enter(&mut self, node: &mut Node) {
+        if let NodeKind::Link { dest: url } | NodeKind::Image { src: url } = &mut node.kind {
+            if is_relative(url) {
+                *url = format!("{}/{}", self.base, url.trim_start_matches("./"));
+            }
+        }
+    }
+}
+
+struct Sanitize(SanitizePolicy);
+
+impl Plugin for Sanitize {
+    fn name(&self) -> &str {
+        "sanitize"
+    }
+
+    /// links are rewritten first so their final URLs are checked
+    fn after(&self) -> Vec<String> {
+        vec!["rewrite-links".to_string()]
+    }
+
+    fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError> {
+        sanitize(tree, &self.0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{build::link, Span};
+
+    struct Named(&'static str, Vec<&'static str>, Vec<&'static str>);
+
+    impl Plugin for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn requires(&self) -> Vec<String> {
+            self.1.iter().map(|s| s.to_string()).collect()
+        }
+
+        fn after(&self) -> Vec<String> {
+            self.2.iter().map(|s| s.to_string()).collect()
+        }
+
+        fn run(&mut self, tree: &mut Node) -> Result<(), PipelineError> {
+            tree.children.push(Node::text(self.0, Span::default()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn plugins_run_after_their_dependencies() {
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .add(Named("c", vec!["b"], vec![]))
+            .unwrap()
+            .add(Named("a", vec![], vec!["missing"]))
+            .unwrap()
+            .add(Named("b", vec![], vec!["a"]))
+            .unwrap()
+            .add(Named("d", vec![], vec![]))
+            .unwrap();
+        assert_eq!(pipeline.order().unwrap(), vec!["a", "b", "c", "d"]);
+
+        let mut tree = Node::new(NodeKind::Document, Span::default());
+        pipeline.run(&mut tree).unwrap();
+        assert_eq!(tree.text_content(), "abcd");
+    }
+
+    #[test]
+    fn invalid_pipelines_are_an_error() {
+        let mut pipeline = Pipeline::new();
+        pipeline.add(Named("a", vec!["b"], vec![])).unwrap();
+        assert!(matches!(
+            pipeline.order(),
+            Err(PipelineError::MissingDependency { .. })
+        ));
+
+        pipeline.add(Named("b", vec![], vec!["a"])).unwrap();
+        assert_eq!(
+            pipeline.order(),
+            Err(PipelineError::Cycle(vec!["a".to_string(), "b".to_string()]))
+        );
+        assert!(matches!(
+            pipeline.add(Named("b", vec![], vec![])),
+            Err(PipelineError::Duplicate(_))
+        ));
+    }
+
+    #[test]
+    fn builtin_plugins_are_created_from_config() {
+        let config: PipelineConfig = serde_json::from_str(
+            r#"{
+                "plugins": [
+                    { "name": "sanitize" },
+                    { "name": "rewrite-links", "options": { "base": "https://docs.com/" } },
+                    { "name": "strip-comments" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let registry = PluginRegistry::builtin();
+        let mut pipeline = registry.pipeline(&config).unwrap();
+        assert_eq!(
+            pipeline.order().unwrap(),
+            vec!["rewrite-links", "sanitize", "strip-comments"]
+        );
+
+        let mut tree = Node::with_children(
+            NodeKind::Document,
+            Span::default(),
+            vec![
+                link("./guide.md"),
+                link("#top"),
+                link("javascript:alert(1)"),
+                Node::new(NodeKind::Html("<!-- x -->".to_string()), Span::default()),
+            ],
+        );
+        pipeline.run(&mut tree).unwrap();
+
+        let kinds: Vec<&NodeKind> = tree.children.iter().map(|c| &c.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &NodeKind::Link {
+                    dest: "https://docs.com/guide.md".to_string()
+                },
+                &NodeKind::Link {
+                    dest: "#top".to_string()
+                },
+                &NodeKind::Text("javascript:alert(1)".to_string()),
+            ]
+        );
+
+        let unknown = PipelineConfig {
+            plugins: vec![PluginConfig {
+                name: "nope".to_string(),
+                options: Value::Null,
+            }],
+        };
+        assert!(matches!(
+            registry.pipeline(&unknown),
+            Err(PipelineError::Unknown(_))
+        ));
+    }
+}
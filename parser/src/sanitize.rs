@@ -0,0 +1,350 @@
+//! Sanitizes the raw HTML tags (and link destinations) in a tree so
+//! that untrusted content can be rendered safely.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{Node, NodeKind};
+
+/// the attributes whose values are URLs and must have an allowed scheme
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite", "action", "formaction", "poster"];
+
+fn set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|i| i.to_string()).collect()
+}
+
+/// The tags, attributes and URL schemes which are allowed through
+/// sanitization.
+///
+/// The default policy is safe for untrusted content: only common
+/// formatting tags are kept, `<script>` and `<style>` are removed
+/// along with their content and URLs are limited to `http`, `https`
+/// and `mailto` (or relative URLs). Event handler (`on*`) attributes
+/// are _always_ removed, regardless of the policy.
+///
+/// Raw HTML (e.g., an HTML block) can't be checked against the policy
+/// so it's kept as literal text which is escaped when rendered; raw
+/// comments, processing instructions, etc. are removed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SanitizePolicy {
+    /// tags which are kept; any other tag is removed but its
+    /// content is kept
+    pub tags: HashSet<String>,
+    /// tags which are removed along with all of their content
+    pub remove_content: HashSet<String>,
+    /// attributes allowed on _any_ allowed tag
+    pub generic_attributes: HashSet<String>,
+    /// attributes allowed on a specific tag
+    pub attributes: HashMap<String, HashSet<String>>,
+    /// the URL schemes allowed in links, images and URL attributes
+    pub url_schemes: HashSet<String>,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        let attributes = [
+            ("a", &["href", "title"][..]),
+            ("img", &["src", "alt", "title", "width", "height"]),
+            ("ol", &["start"]),
+            ("td", &["colspan", "rowspan", "align"]),
+            ("th", &["colspan", "rowspan", "align"]),
+            ("blockquote", &["cite"]),
+            ("q", &["cite"]),
+            ("del", &["cite"]),
+            ("ins", &["cite"]),
+        ];
+
+        Self {
+            tags: set(&[
+                "a",
+                "abbr",
+                "b",
+                "blockquote",
+                "br",
+                "code",
+                "dd",
+                "del",
+                "details",
+                "div",
+                "dl",
+                "dt",
+                "em",
+                "figcaption",
+                "figure",
+                "h1",
+                "h2",
+                "h3",
+                "h4",
+                "h5",
+                "h6",
+                "hr",
+                "i",
+                "img",
+                "ins",
+                "kbd",
+                "li",
+                "mark",
+                "ol",
+                "p",
+                "pre",
+                "q",
+                "s",
+                "small",
+                "span",
+                "strong",
+                "sub",
+                "summary",
+                "sup",
+                "table",
+                "tbody",
+                "td",
+                "tfoot",
+                "th",
+                "thead",
+                "tr",
+                "u",
+                "ul",
+            ]),
+            remove_content: set(&["script", "style"]),
+            generic_attributes: set(&["title", "lang", "dir"]),
+            attributes: attributes
+                .iter()
+                .map(|(tag, attrs)| (tag.to_string(), set(attrs)))
+                .collect(),
+            url_schemes: set(&["http", "https", "mailto"]),
+        }
+    }
+}
+
+impl SanitizePolicy {
+    /// a policy which allows nothing; build it up with the
+    /// `allow_*` methods
+    pub fn empty() -> Self {
+        Self {
+            tags: HashSet::new(),
+            remove_content: set(&["script", "style"]),
+            generic_attributes: HashSet::new(),
+            attributes: HashMap::new(),
+            url_schemes: HashSet::new(),
+        }
+    }
+
+    pub fn allow_tag(mut self, tag: &str) -> Self {
+        self.tags.insert(tag.to_string());
+        self
+    }
+
+    pub fn allow_attribute(mut self, tag: &str, attribute: &str) -> Self {
+        self.attributes
+            .entry(tag.to_string())
+            .or_default()
+            .insert(attribute.to_string());
+        self
+    }
+
+    pub fn allow_url_scheme(mut self, scheme: &str) -> Self {
+        self.url_schemes.insert(scheme.to_lowercase());
+        self
+    }
+
+    /// Whether the URL is allowed; relative URLs are always allowed.
+    ///
+    /// Browsers ignore whitespace and control characters within a
+    /// scheme (e.g., `java\tscript:`) so they're ignored here too.
+    pub fn allows_url(&self, url: &str) -> bool {
+        let url: String = url
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect();
+        let scheme_end = url.find(':');
+        let path_start = url.find(['/', '?', '#']);
+
+        match (scheme_end, path_start) {
+            (Some(end), Some(path)) if path < end => true,
+            (Some(end), _) => self.url_schemes.contains(&url[..end].to_lowercase()),
+            (None, _) => true,
+        }
+    }
+
+    pub fn allows_attribute(&self, tag: &str, name: &str, value: &str) -> bool {
+        let name = name.to_lowercase();
+        if name.starts_with("on") {
+            return false;
+        }
+        let allowed = self.generic_attributes.contains(&name)
+            || self
+                .attributes
+                .get(tag)
+                .map(|attrs| attrs.contains(&name))
+                .unwrap_or(false);
+
+        allowed && (!URL_ATTRIBUTES.contains(&name.as_str()) || self.allows_url(value))
+    }
+
+    /// the nodes which replace `node` once it's sanitized
+    fn clean(&self, mut node: Node) -> Vec<Node> {
+        node.children = node
+            .children
+            .into_iter()
+            .flat_map(|c| self.clean(c))
+            .collect();
+
+        match &mut node.kind {
+            NodeKind::Tag { name, attrs } => {
+                let tag = name.to_lowercase();
+                if self.remove_content.contains(&tag) {
+                    vec![]
+                } else if !self.tags.contains(&tag) {
+                    node.children
+                } else {
+                    attrs.retain(|(k, v)| self.allows_attribute(&tag, k, v));
+                    vec![node]
+                }
+            }
+            NodeKind::Html(html) => {
+                let raw = html.trim_start();
+                if raw.starts_with("<!") || raw.starts_with("<?") {
+                    vec![]
+                } else {
+                    vec![Node::text(html, node.span)]
+                }
+            }
+            NodeKind::Link { dest } if !self.allows_url(dest) => node.children,
+            NodeKind::Image { src } if !self.allows_url(src) => vec![],
+            _ => vec![node],
+        }
+    }
+}
+
+/// Removes everything from the tree which the policy doesn't allow.
+///
+/// If the root itself is removed (or unwrapped) it's replaced with a
+/// `Document` containing whatever remains.
+pub fn sanitize(tree: &mut Node, policy: &SanitizePolicy) {
+    let root = std::mem::replace(tree, Node::new(NodeKind::Document, tree.span));
+    let mut nodes = policy.clean(root);
+
+    match nodes.len() {
+        1 => *tree = nodes.remove(0),
+        _ => tree.children = nodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Span;
+
+    fn tag(name: &str, attrs: &[(&str, &str)], children: Vec<Node>) -> Node {
+        Node::with_children(
+            NodeKind::Tag {
+                name: name.to_string(),
+                attrs: attrs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            },
+            Span::default(),
+            children,
+        )
+    }
+
+    fn document(children: Vec<Node>) -> Node {
+        Node::with_children(NodeKind::Document, Span::default(), children)
+    }
+
+    #[test]
+    fn unsafe_urls_are_removed() {
+        let policy = SanitizePolicy::default();
+
+        assert!(policy.allows_url("https://foo.com"));
+        assert!(policy.allows_url("./foo.png"));
+        assert!(policy.allows_url("#topic-1"));
+        assert!(policy.allows_url("/a:b"));
+        assert!(!policy.allows_url("javascript:alert(1)"));
+        assert!(!policy.allows_url(" JaVa\tScRiPt:alert(1)"));
+        assert!(!policy.allows_url("data:text/html,foo"));
+    }
+
+    #[test]
+    fn disallowed_tags_are_unwrapped() {
+        let text = Node::text("inner", Span::default());
+        let mut tree = document(vec![
+            tag("script", &[], vec![Node::text("alert(1)", Span::default())]),
+            tag("my-inline", &[], vec![text.clone()]),
+            tag(
+                "a",
+                &[
+                    ("href", "javascript:alert(1)"),
+                    ("onclick", "x()"),
+                    ("title", "t"),
+                ],
+                vec![text.clone()],
+            ),
+        ]);
+        sanitize(&mut tree, &SanitizePolicy::default());
+
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[0], text);
+        assert_eq!(
+            tree.children[1].kind,
+            NodeKind::Tag {
+                name: "a".to_string(),
+                attrs: vec![("title".to_string(), "t".to_string())]
+            }
+        );
+    }
+
+    #[test]
+    fn event_handlers_are_always_removed() {
+        let policy = SanitizePolicy::empty()
+            .allow_tag("my-inline")
+            .allow_attribute("my-inline", "onclick")
+            .allow_attribute("my-inline", "size");
+        let mut tree = document(vec![tag(
+            "my-inline",
+            &[("onclick", "x()"), ("size", "2")],
+            vec![],
+        )]);
+        sanitize(&mut tree, &policy);
+
+        assert_eq!(
+            tree.children[0].kind,
+            NodeKind::Tag {
+                name: "my-inline".to_string(),
+                attrs: vec![("size".to_string(), "2".to_string())]
+            }
+        );
+    }
+
+    #[test]
+    fn raw_html_is_kept_as_text() {
+        let html = |raw: &str| Node::new(NodeKind::Html(raw.to_string()), Span::default());
+        let mut tree = document(vec![
+            html("<!-- comment -->"),
+            html("<div onclick=\"x()\">\nfoo"),
+        ]);
+        sanitize(&mut tree, &SanitizePolicy::default());
+
+        assert_eq!(
+            tree.children,
+            vec![Node::text("<div onclick=\"x()\">\nfoo", Span::default())]
+        );
+    }
+
+    #[test]
+    fn links_with_unsafe_urls_keep_their_text() {
+        let link = Node::with_children(
+            NodeKind::Link {
+                dest: "javascript:void(0)".to_string(),
+            },
+            Span::default(),
+            vec![Node::text("click", Span::default())],
+        );
+        let mut tree = document(vec![link]);
+        sanitize(&mut tree, &SanitizePolicy::default());
+
+        assert_eq!(tree.children, vec![Node::text("click", Span::default())]);
+    }
+}
@@ -0,0 +1,150 @@
+//! GitHub compatible anchors ("slugs") for headings.
+
+use std::collections::HashMap;
+
+use crate::ast::{Node, NodeKind};
+
+/// The anchor GitHub generates for a heading's text: the text is
+/// lowercased, spaces become `-` and any other character which isn't
+/// a letter, number, `-` or `_` is removed.
+pub fn slug(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Generates unique slugs for the headings in a document; a slug which
+/// has already been used is suffixed with `-1`, `-2`, etc.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    pub fn slug(&mut self, text: &str) -> String {
+        self.unique(&slug(text))
+    }
+
+    /// the slug -- which was already generated from a heading's text
+    /// (e.g., to be stored) -- made unique
+    pub fn unique(&mut self, original: &str) -> String {
+        let mut slug = original.to_string();
+
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(original.to_string()).or_insert(0);
+            *count += 1;
+            slug = format!("{}-{}", original, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+
+        slug
+    }
+}
+
+/// the text and level of every heading in the tree, in document order
+pub fn headings(tree: &Node) -> Vec<(u8, String)> {
+    let mut found = vec![];
+    let mut stack = vec![tree];
+
+    while let Some(node) = stack.pop() {
+        if let NodeKind::Heading { level, .. } = node.kind {
+            found.push((level, node.text_content()));
+        }
+        stack.extend(node.children.iter().rev());
+    }
+
+    found
+}
+
+/// the anchor of every heading in the tree, in document order
+pub fn anchors(tree: &Node) -> Vec<String> {
+    let mut slugger = Slugger::default();
+
+    headings(tree)
+        .iter()
+        .map(|(_, text)| slugger.slug(text))
+        .collect()
+}
+
+/// sets the `id` of every heading in the tree to its unique slug
+pub fn assign_ids(tree: &mut Node) {
+    assign_ids_with(tree, &mut Slugger::default());
+}
+
+/// sets the `id` of every heading in the tree using the slugger; a
+/// document which is processed a block at a time shares one slugger
+/// across the blocks so that the ids stay unique
+pub fn assign_ids_with(tree: &mut Node, slugger: &mut Slugger) {
+    fn assign(node: &mut Node, slugger: &mut Slugger) {
+        if let NodeKind::Heading { id, .. } = &mut node.kind {
+            let text = node
+                .children
+                .iter()
+                .map(|c| c.text_content())
+                .collect::<String>();
+            *id = Some(slugger.slug(&text));
+        }
+        for child in node.children.iter_mut() {
+            assign(child, slugger);
+        }
+    }
+
+    assign(tree, slugger);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Span;
+
+    #[test]
+    fn slugs_match_github() {
+        assert_eq!(slug("Test Markdown"), "test-markdown");
+        assert_eq!(slug("What's new? (v2.0)"), "whats-new-v20");
+        assert_eq!(slug("snake_case & kebab-case"), "snake_case--kebab-case");
+        assert_eq!(slug("Über 👍"), "über-");
+    }
+
+    #[test]
+    fn duplicates_are_disambiguated() {
+        let mut slugger = Slugger::default();
+        let slugs: Vec<String> = ["Foo", "Foo", "Foo 1", "Foo"]
+            .iter()
+            .map(|t| slugger.slug(t))
+            .collect();
+
+        assert_eq!(slugs, vec!["foo", "foo-1", "foo-1-1", "foo-2"]);
+    }
+
+    #[test]
+    fn ids_are_assigned_to_headings() {
+        let heading = |text: &str| {
+            Node::with_children(
+                NodeKind::Heading { level: 2, id: None },
+                Span::default(),
+                vec![Node::text(text, Span::default())],
+            )
+        };
+        let mut tree = Node::with_children(
+            NodeKind::Document,
+            Span::default(),
+            vec![heading("Topic"), heading("Topic")],
+        );
+        assign_ids(&mut tree);
+
+        assert_eq!(
+            tree.children[1].kind,
+            NodeKind::Heading {
+                level: 2,
+                id: Some("topic-1".to_string())
+            }
+        );
+        assert_eq!(anchors(&tree), vec!["topic", "topic-1"]);
+    }
+}
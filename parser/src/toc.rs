@@ -0,0 +1,161 @@
+//! Generates a table of contents from the headings in a document.
+
+use crate::{
+    ast::{Node, NodeKind, Span},
+    slug::Slugger,
+};
+
+/// the level, title and anchor of every heading in the tree, in
+/// document order; the anchor is the `id` assigned to the heading (or
+/// its slug when it hasn't been assigned one)
+fn headings(tree: &Node) -> Vec<(u8, String, String)> {
+    let mut slugger = Slugger::default();
+    let mut found = vec![];
+    let mut stack = vec![tree];
+
+    while let Some(node) = stack.pop() {
+        if let NodeKind::Heading { level, id } = &node.kind {
+            let title = node.text_content();
+            let slug = slugger.slug(&title);
+            found.push((*level, title, id.clone().unwrap_or(slug)));
+        }
+        stack.extend(node.children.iter().rev());
+    }
+
+    found
+}
+
+/// the list items for `headings`; headings which follow an item and
+/// have a _deeper_ level are nested under it
+fn items(headings: &[(u8, String, String)], span: Span) -> Vec<Node> {
+    let mut found = vec![];
+    let mut idx = 0;
+
+    while idx < headings.len() {
+        let (level, title, anchor) = &headings[idx];
+        let nested = headings[idx + 1..]
+            .iter()
+            .take_while(|(l, _, _)| l > level)
+            .count();
+
+        let link = Node::with_children(
+            NodeKind::Link {
+                dest: format!("#{}", anchor),
+            },
+            span,
+            vec![Node::text(title, span)],
+        );
+        let mut children = vec![Node::with_children(NodeKind::Paragraph, span, vec![link])];
+        if nested > 0 {
+            children.push(Node::with_children(
+                NodeKind::List { ordered: false },
+                span,
+                items(&headings[idx + 1..idx + 1 + nested], span),
+            ));
+        }
+
+        found.push(Node::with_children(
+            NodeKind::ListItem {
+                marker: "-".to_string(),
+            },
+            span,
+            children,
+        ));
+        idx += 1 + nested;
+    }
+
+    found
+}
+
+/// A nested bullet list linking to every heading in the tree; the
+/// nodes of the list are all given the `span`.
+pub fn table_of_contents(tree: &Node, span: Span) -> Node {
+    Node::with_children(
+        NodeKind::List { ordered: false },
+        span,
+        items(&headings(tree), span),
+    )
+}
+
+/// replaces every `Toc` placeholder in the tree with the table of contents
+pub fn expand(tree: &mut Node) {
+    fn replace(node: &mut Node, toc: &Node) {
+        for child in node.children.iter_mut() {
+            if child.kind == NodeKind::Toc {
+                let span = child.span;
+                *child = toc.clone();
+                child.span = span;
+            } else {
+                replace(child, toc);
+            }
+        }
+    }
+
+    let toc = table_of_contents(tree, Span::default());
+    replace(tree, &toc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(level: u8, text: &str) -> Node {
+        Node::with_children(
+            NodeKind::Heading { level, id: None },
+            Span::default(),
+            vec![Node::text(text, Span::default())],
+        )
+    }
+
+    #[test]
+    fn headings_are_nested_by_level() {
+        let mut tree = Node::with_children(
+            NodeKind::Document,
+            Span::default(),
+            vec![
+                heading(1, "Test Markdown"),
+                Node::new(NodeKind::Toc, Span::new(16, 24)),
+                heading(2, "Topic 1"),
+                heading(3, "Detail"),
+                heading(2, "Topic 1"),
+            ],
+        );
+        expand(&mut tree);
+        let toc = &tree.children[1];
+
+        assert_eq!(toc.span, Span::new(16, 24));
+        assert_eq!(toc.children.len(), 1);
+        let nested = &toc.children[0].children[1];
+        assert_eq!(nested.children.len(), 2);
+        assert_eq!(
+            nested.children[1].children[0].children[0].kind,
+            NodeKind::Link {
+                dest: "#topic-1-1".to_string()
+            }
+        );
+        assert_eq!(nested.children[0].children[1].text_content(), "Detail");
+    }
+
+    #[test]
+    fn links_use_the_assigned_ids() {
+        let mut custom = heading(2, "Topic");
+        custom.kind = NodeKind::Heading {
+            level: 2,
+            id: Some("custom".to_string()),
+        };
+        let tree = Node::with_children(
+            NodeKind::Document,
+            Span::default(),
+            vec![heading(1, "Title"), custom],
+        );
+        let toc = table_of_contents(&tree, Span::default());
+        let link = &toc.children[0].children[1].children[0].children[0].children[0];
+
+        assert_eq!(
+            link.kind,
+            NodeKind::Link {
+                dest: "#custom".to_string()
+            }
+        );
+    }
+}
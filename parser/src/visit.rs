@@ -0,0 +1,318 @@
+//! Depth-first traversal of the tree: a `Visitor` which is told about
+//! each node as it's entered and exited and a `Transformer` which can
+//! replace, remove or insert nodes. Both are small enough that a pass
+//! (e.g., "rewrite relative links") is a single `impl`.
+
+use crate::ast::{Node, NodeKind};
+
+/// What the walk does once a node has been entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visit {
+    /// visit the node's children and then exit it
+    #[default]
+    Continue,
+    /// don't visit the node's children; it's still exited
+    SkipChildren,
+    /// stop the walk; no further nodes are entered or exited
+    Stop,
+}
+
+macro_rules! visitor {
+    ($($kind:pat => $enter:ident, $exit:ident;)*) => {
+        /// Is told about every node in the tree in document order.
+        ///
+        /// `enter` and `exit` are called for every node; by default they
+        /// call the hook for the node's kind (e.g., `enter_heading`) so
+        /// a visitor only implements the hooks it cares about.
+        pub trait Visitor {
+            fn enter(&mut self, node: &Node) -> Visit {
+                match node.kind {
+                    $($kind => self.$enter(node),)*
+                }
+            }
+
+            fn exit(&mut self, node: &Node) {
+                match node.kind {
+                    $($kind => self.$exit(node),)*
+                }
+            }
+
+            $(
+                fn $enter(&mut self, _node: &Node) -> Visit {
+                    Visit::Continue
+                }
+
+                fn $exit(&mut self, _node: &Node) {}
+            )*
+        }
+    };
+}
+
+visitor! {
+    NodeKind::Document => enter_document, exit_document;
+    NodeKind::Heading { .. } => enter_heading, exit_heading;
+    NodeKind::Paragraph => enter_paragraph, exit_paragraph;
+    NodeKind::ThematicBreak => enter_thematic_break, exit_thematic_break;
+    NodeKind::CodeBlock { .. } => enter_code_block, exit_code_block;
+    NodeKind::Tag { .. } => enter_tag, exit_tag;
+    NodeKind::List { .. } => enter_list, exit_list;
+    NodeKind::ListItem { .. } => enter_list_item, exit_list_item;
+    NodeKind::BlockQuote => enter_block_quote, exit_block_quote;
+    NodeKind::Link { .. } => enter_link, exit_link;
+    NodeKind::Image { .. } => enter_image, exit_image;
+    NodeKind::Emoji { .. } => enter_emoji, exit_emoji;
+    NodeKind::BlockEmoji { .. } => enter_block_emoji, exit_block_emoji;
+    NodeKind::Toc => enter_toc, exit_toc;
+    NodeKind::SoftBreak => enter_soft_break, exit_soft_break;
+    NodeKind::HardBreak => enter_hard_break, exit_hard_break;
+    NodeKind::Html(_) => enter_html, exit_html;
+    NodeKind::Text(_) => enter_text, exit_text;
+}
+
+/// Walks the tree depth-first, entering each node before its children
+/// and exiting it after them. Returns `Visit::Stop` when the visitor
+/// stopped the walk.
+pub fn walk<V: Visitor + ?Sized>(node: &Node, visitor: &mut V) -> Visit {
+    match visitor.enter(node) {
+        Visit::Stop => return Visit::Stop,
+        Visit::SkipChildren => (),
+        Visit::Continue => {
+            for child in &node.children {
+                if walk(child, visitor) == Visit::Stop {
+                    return Visit::Stop;
+                }
+            }
+        }
+    }
+    visitor.exit(node);
+
+    Visit::Continue
+}
+
+/// Rewrites the tree depth-first.
+///
+/// `enter` can change a node before its children are transformed while
+/// `transform` is given the node once its children have been and
+/// returns the nodes which replace it: none to remove it, the node
+/// itself to keep it or several to insert nodes alongside it.
+///
+/// ```
+/// use parser::{ast::{Node, NodeKind, Span}, visit::{transform, Transformer}};
+///
+/// /// removes HTML comments
+/// struct StripComments;
+///
+/// impl Transformer for StripComments {
+///     fn transform(&mut self, node: Node) -> Vec<Node> {
+///         match &node.kind {
+///             NodeKind::Html(html) if html.starts_with("<!--") => vec![],
+///             _ => vec![node],
+///         }
+///     }
+/// }
+///
+/// let comment = Node::new(NodeKind::Html("<!-- x -->".to_string()), Span::default());
+/// let mut tree = Node::with_children(NodeKind::Document, Span::default(), vec![comment]);
+/// transform(&mut tree, &mut StripComments);
+///
+/// assert!(tree.children.is_empty());
+/// ```
+pub trait Transformer {
+    fn enter(&mut self, _node: &mut Node) {}
+
+    fn transform(&mut self, node: Node) -> Vec<Node> {
+        vec![node]
+    }
+}
+
+fn transform_node<T: Transformer + ?Sized>(mut node: Node, transformer: &mut T) -> Vec<Node> {
+    transformer.enter(&mut node);
+    node.children = std::mem::take(&mut node.children)
+        .into_iter()
+        .flat_map(|c| transform_node(c, transformer))
+        .collect();
+
+    transformer.transform(node)
+}
+
+/// Applies the transformer to the whole tree.
+///
+/// If the root itself is removed (or replaced by several nodes) it's
+/// replaced with a `Document` containing whatever remains.
+pub fn transform<T: Transformer + ?Sized>(tree: &mut Node, transformer: &mut T) {
+    let root = std::mem::replace(tree, Node::new(NodeKind::Document, tree.span));
+    let mut nodes = transform_node(root, transformer);
+
+    match nodes.len() {
+        1 => *tree = nodes.remove(0),
+        _ => tree.children = nodes,
+    }
+}
+
+/// Several transformers applied one after another as a single pass
+/// over the tree; each node is given to every transformer in turn.
+impl Transformer for [&mut dyn Transformer] {
+    fn enter(&mut self, node: &mut Node) {
+        for transformer in self.iter_mut() {
+            transformer.enter(node);
+        }
+    }
+
+    fn transform(&mut self, node: Node) -> Vec<Node> {
+        self.iter_mut().fold(vec![node], |nodes, transformer| {
+            nodes
+                .into_iter()
+                .flat_map(|n| transformer.transform(n))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::build::{link, node, text};
+
+    fn tree() -> Node {
+        node(
+            NodeKind::Document,
+            vec![
+                node(
+                    NodeKind::Heading { level: 1, id: None },
+                    vec![text("Title")],
+                ),
+                node(
+                    NodeKind::Paragraph,
+                    vec![link("./a.md"), text(" and "), link("https://b.com")],
+                ),
+                node(NodeKind::Html("<!-- note -->".to_string()), vec![]),
+                node(
+                    NodeKind::Image {
+                        src: "one.png".to_string(),
+                    },
+                    vec![],
+                ),
+                node(
+                    NodeKind::Image {
+                        src: "two.png".to_string(),
+                    },
+                    vec![],
+                ),
+            ],
+        )
+    }
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Visitor for Events {
+        fn enter_heading(&mut self, _node: &Node) -> Visit {
+            self.0.push("enter heading".to_string());
+            Visit::SkipChildren
+        }
+
+        fn exit_heading(&mut self, _node: &Node) {
+            self.0.push("exit heading".to_string());
+        }
+
+        fn enter_text(&mut self, node: &Node) -> Visit {
+            self.0.push(node.text_content());
+            Visit::Continue
+        }
+
+        fn enter_html(&mut self, _node: &Node) -> Visit {
+            Visit::Stop
+        }
+    }
+
+    #[test]
+    fn visitors_enter_and_exit_in_document_order() {
+        let mut events = Events::default();
+
+        assert_eq!(walk(&tree(), &mut events), Visit::Stop);
+        assert_eq!(
+            events.0,
+            vec![
+                "enter heading",
+                "exit heading",
+                "./a.md",
+                " and ",
+                "https://b.com"
+            ]
+        );
+    }
+
+    /// rewrites relative links to be relative to a base URL
+    struct RelativeLinks(&'static str);
+
+    impl Transformer for RelativeLinks {
+        fn enter(&mut self, node: &mut Node) {
+            if let NodeKind::Link { dest } = &mut node.kind {
+                if let Some(path) = dest.strip_prefix("./") {
+                    *dest = format!("{}/{}", self.0, path);
+                }
+            }
+        }
+    }
+
+    /// wraps each image in a `figure` with a numbered caption
+    #[derive(Default)]
+    struct NumberFigures(usize);
+
+    impl Transformer for NumberFigures {
+        fn transform(&mut self, image: Node) -> Vec<Node> {
+            if !matches!(image.kind, NodeKind::Image { .. }) {
+                return vec![image];
+            }
+            self.0 += 1;
+            let tag = |name: &str| NodeKind::Tag {
+                name: name.to_string(),
+                attrs: vec![],
+            };
+            let caption = node(tag("figcaption"), vec![text(&format!("Figure {}", self.0))]);
+
+            vec![node(tag("figure"), vec![image, caption])]
+        }
+    }
+
+    struct StripComments;
+
+    impl Transformer for StripComments {
+        fn transform(&mut self, node: Node) -> Vec<Node> {
+            match &node.kind {
+                NodeKind::Html(html) if html.starts_with("<!--") => vec![],
+                _ => vec![node],
+            }
+        }
+    }
+
+    #[test]
+    fn transformers_compose() {
+        let mut tree = tree();
+        let passes: &mut [&mut dyn Transformer] = &mut [
+            &mut RelativeLinks("https://docs.com"),
+            &mut NumberFigures::default(),
+            &mut StripComments,
+        ];
+        transform(&mut tree, passes);
+
+        let dests: Vec<&NodeKind> = tree.children[1].children.iter().map(|c| &c.kind).collect();
+        assert_eq!(
+            dests[0],
+            &NodeKind::Link {
+                dest: "https://docs.com/a.md".to_string()
+            }
+        );
+        assert_eq!(tree.children.len(), 4);
+        assert_eq!(tree.children[3].text_content(), "Figure 2");
+    }
+
+    #[test]
+    fn removing_the_root_leaves_a_document() {
+        let mut tree = node(NodeKind::Html("<!-- x -->".to_string()), vec![]);
+        transform(&mut tree, &mut StripComments);
+
+        assert_eq!(tree.kind, NodeKind::Document);
+        assert!(tree.children.is_empty());
+    }
+}